@@ -28,6 +28,28 @@ pub fn expand_derive_raw_encode(input: &syn::DeriveInput) -> Result<TokenStream,
     let (f1, f2) = raw_encode_body(&cont, &params);
     let (body, measure_body) = (Stmts(f1), Stmts(f2));
 
+    // optimize_option的编码依赖整体的flag，这种情况使用默认的raw_digest实现
+    let digest_fn = raw_digest_body(&cont, &params).map(|digest_body| {
+        quote! {
+            fn raw_digest(&self, __writer__: &mut bucky_raw_codec::RawDigestWriter, __purpose__: &Option<bucky_raw_codec::RawEncodePurpose>) -> bucky_raw_codec::CodecResult<()> {
+                #digest_body
+            }
+        }
+    });
+
+    let hash_id_impl = if cont.attrs.hash_id {
+        quote! {
+            #[automatically_derived]
+            impl #impl_generics #ident #ty_generics #where_clause {
+                pub fn hash_id(&self) -> bucky_raw_codec::CodecResult<bucky_raw_codec::H256> {
+                    bucky_raw_codec::RawHash::raw_hash_sha256(self)
+                }
+            }
+        }
+    } else {
+        quote! {}
+    };
+
     let impl_block = quote! {
         #[automatically_derived]
         #[allow(non_snake_case)]
@@ -40,8 +62,11 @@ pub fn expand_derive_raw_encode(input: &syn::DeriveInput) -> Result<TokenStream,
             fn raw_measure(&self, __purpose__: &Option<bucky_raw_codec::RawEncodePurpose>) -> bucky_raw_codec::CodecResult<usize> {
                 #measure_body
             }
-        };
 
+            #digest_fn
+        }
+
+        #hash_id_impl
     };
 
     Ok(dummy::wrap_in_const(impl_block))
@@ -146,6 +171,122 @@ fn raw_encode_body(cont: &Container, params: &Parameters) -> (Fragment, Fragment
     }
 }
 
+fn raw_digest_body(cont: &Container, params: &Parameters) -> Option<TokenStream> {
+    if cont.attrs.optimize_option {
+        return None;
+    }
+
+    let body = match &cont.data {
+        Data::Enum(variants) => {
+            let self_var = &params.self_var;
+            let this = &params.this;
+            let arms: Vec<_> = variants
+                .iter()
+                .enumerate()
+                .map(|(variant_index, variant)| {
+                    let variant_ident = &variant.ident;
+                    let (case, stmts) = match variant.style {
+                        Style::Unit => (quote!(#this::#variant_ident), vec![]),
+                        Style::Newtype | Style::Tuple => {
+                            let field_names: Vec<_> = (0..variant.fields.len())
+                                .map(|i| Ident::new(&format!("__field{}", i), Span::call_site()))
+                                .collect();
+                            let stmts = variant
+                                .fields
+                                .iter()
+                                .zip(field_names.iter())
+                                .map(|(field, name)| digest_field(field, quote!(#name)))
+                                .collect();
+                            (quote!(#this::#variant_ident(#(ref #field_names),*)), stmts)
+                        }
+                        Style::Struct => {
+                            let members = variant.fields.iter().map(|f| &f.member);
+                            let stmts = variant
+                                .fields
+                                .iter()
+                                .filter(|field| !field.attrs.skip_serializing())
+                                .map(|field| {
+                                    let member = &field.member;
+                                    digest_field(field, quote!(#member))
+                                })
+                                .collect();
+                            (quote!(#this::#variant_ident { #(ref #members),* }), stmts)
+                        }
+                    };
+                    quote! {
+                        #case => {
+                            bucky_raw_codec::USize(#variant_index).raw_digest(__writer__, __purpose__)?;
+                            #(#stmts)*
+                        }
+                    }
+                })
+                .collect();
+
+            if arms.is_empty() {
+                quote!(Ok(()))
+            } else {
+                quote! {
+                    match #self_var {
+                        #(#arms)*
+                    }
+                    Ok(())
+                }
+            }
+        }
+        Data::Struct(Style::Struct, fields) => {
+            let stmts = fields
+                .iter()
+                .filter(|field| !field.attrs.skip_serializing())
+                .map(|field| {
+                    let member = &field.member;
+                    digest_field(field, get_member(params, field, member))
+                });
+            quote! {
+                #(#stmts)*
+                Ok(())
+            }
+        }
+        Data::Struct(Style::Tuple, fields) | Data::Struct(Style::Newtype, fields) => {
+            let stmts = fields.iter().enumerate().map(|(i, field)| {
+                let member = Member::Unnamed(Index {
+                    index: i as u32,
+                    span: Span::call_site(),
+                });
+                digest_field(field, get_member(params, field, &member))
+            });
+            quote! {
+                #(#stmts)*
+                Ok(())
+            }
+        }
+        Data::Struct(Style::Unit, _) => quote!(Ok(())),
+    };
+
+    Some(body)
+}
+
+fn digest_field(field: &Field, field_expr: TokenStream) -> TokenStream {
+    let item = if field.is_vec_u8() {
+        quote! {
+            bucky_raw_codec::USize(#field_expr.len()).raw_digest(__writer__, __purpose__)?;
+            __writer__.write(&#field_expr);
+        }
+    } else {
+        quote! {
+            #field_expr.raw_digest(__writer__, __purpose__)?;
+        }
+    };
+
+    match field.attrs.purpose_condition() {
+        Some(cond) => quote! {
+            if #cond {
+                #item
+            }
+        },
+        None => item,
+    }
+}
+
 fn serialize_into(params: &Parameters, type_into: &syn::Type) -> Fragment {
     let self_var = &params.self_var;
     quote_block! {
@@ -263,7 +404,7 @@ fn serialize_struct_as_struct(
                 Ok(__buf__)
             },
             quote_block! {
-                Ok(#flag_measure + #(#serialize_fields1)+*)
+                Ok(#flag_measure #(+ #serialize_fields1)*)
             },
         )
     }
@@ -568,7 +709,7 @@ fn encode_enum_struct_variant<'a>(
                         Ok(__buf__)
                     }),
                     quote_block!({
-                        Ok(bucky_raw_codec::USize(#variant_index).raw_measure(__purpose__)? + #flag_measure + #(#measure_fields)+*)
+                        Ok(bucky_raw_codec::USize(#variant_index).raw_measure(__purpose__)? + #flag_measure #(+ #measure_fields)*)
                     }),
                 )
             }
@@ -786,39 +927,30 @@ fn encode_struct_flag(
     optimize_option: bool,
 ) -> (TokenStream, TokenStream) {
     let mut option_index = 0;
-    let flag_stmts: Vec<_> = if is_enum {
-        fields
-            .iter()
-            .enumerate()
-            .filter(|(_, field)| optimize_option && field.is_option())
-            .map(|(i, field)| {
-                let name = &field.member;
-                let item = quote! {
-                    if #name.is_some() {
-                        flag |= 1 << #option_index;
-                    }
-                };
-                option_index += 1;
-                item
-            })
-            .collect()
-    } else {
-        fields
-            .iter()
-            .enumerate()
-            .filter(|(_, field)| optimize_option && field.is_option())
-            .map(|(i, field)| {
-                let name = &field.member;
-                let item = quote! {
-                    if self.#name.is_some() {
-                        flag |= 1 << #option_index;
-                    }
-                };
-                option_index += 1;
-                item
-            })
-            .collect()
-    };
+    let flag_stmts: Vec<_> = fields
+        .iter()
+        .filter(|field| optimize_option && field.is_flag_option())
+        .map(|field| {
+            let member = &field.member;
+            let name = if is_enum {
+                quote!(#member)
+            } else {
+                quote!(self.#member)
+            };
+            // 受purpose控制的字段，只有实际编码时才设置标志位
+            let is_some = match field.attrs.purpose_condition() {
+                Some(cond) => quote!((#cond) && #name.is_some()),
+                None => quote!(#name.is_some()),
+            };
+            let item = quote! {
+                if #is_some {
+                    flag |= 1 << #option_index;
+                }
+            };
+            option_index += 1;
+            item
+        })
+        .collect();
 
     let option_count = flag_stmts.len();
     if option_count == 0 {
@@ -877,129 +1009,91 @@ fn encode_struct_visitor(
     optimize_option: bool,
 ) -> (TokenStream, Vec<TokenStream>, TokenStream, Vec<TokenStream>) {
     let (flag, flag_measure) = encode_struct_flag(fields, _is_enum, optimize_option);
-    if _is_enum {
-        (flag, fields
-             .iter()
-            .filter(|field| !field.attrs.skip_serializing())
-             .map(|field| {
-                 let ty = field.ty;
-                 let member = &field.member;
-                 if optimize_option && field.is_option() {
-                    quote! {
-                        let __buf__ = if #member.is_some() {
-                            #member.unwrap().raw_encode(__buf__, __purpose__)?
-                        } else {
+    let field_expr = |field: &Field| {
+        let member = &field.member;
+        if _is_enum {
+            quote!(#member)
+        } else {
+            quote!(self.#member)
+        }
+    };
+
+    (flag, fields
+        .iter()
+        .filter(|field| !field.attrs.skip_serializing())
+        .map(|field| {
+            let field_expr = field_expr(field);
+            let item = if optimize_option && field.is_flag_option() {
+                quote! {
+                    let __buf__ = if #field_expr.is_some() {
+                        #field_expr.as_ref().unwrap().raw_encode(__buf__, __purpose__)?
+                    } else {
+                        __buf__
+                    };
+                }
+            } else if field.is_vec_u8() {
+                quote! {
+                    let __buf__ = {
+                        let __buf__ = bucky_raw_codec::USize(#field_expr.len()).raw_encode(__buf__, __purpose__)?;
+                        if #field_expr.len() == 0 {
                             __buf__
-                        }
-                    }
-                 } else if field.is_vec_u8() {
-                     let item = quote! {
-                        let __buf__ = {
-                            let __buf__ = bucky_raw_codec::USize(#member.len()).raw_encode(__buf__, __purpose__)?;
-                            if #member.len() == 0 {
-                                __buf__
-                            } else {
-                                unsafe {
-                                    std::ptr::copy::<u8>(#member.as_ptr() as *mut u8, __buf__.as_mut_ptr(), #member.len());
-                                }
-                                &mut __buf__[#member.len()..]
-                            }
-                        };
-                     };
-                     // println!("{}", item.to_string());
-                     item
-                 } else {
-                     quote! {
-                        let __buf__ = #member.raw_encode(__buf__, __purpose__)?;
-                    }
-                 }
-             })
-             .collect(),
-
-         flag_measure, fields
-             .iter()
-             .filter(|field| !field.attrs.skip_serializing())
-             .map(|field| {
-                 let member = &field.member;
-
-                 if optimize_option && field.is_option() {
-                     quote! {
-                        {if #member.is_some() {
-                            #member.as_ref().unwrap().raw_measure(__purpose__)?
                         } else {
-                            0
-                        }}
-                    }
-                 } else if field.is_vec_u8() {
-                     quote!{bucky_raw_codec::USize(#member.len()).raw_measure(__purpose__)? + #member.len()}
-                 } else {
-                     quote! {
-                        #member.raw_measure(__purpose__)?
-                    }
-                 }
-             })
-             .collect())
-    } else {
-        (flag, fields
-             .iter()
-            .filter(|field| !field.attrs.skip_serializing())
-             .map(|field| {
-                 let member = &field.member;
-                 if optimize_option && field.is_option() {
-                     quote! {
-                        let __buf__ = if self.#member.is_some() {
-                            self.#member.as_ref().unwrap().raw_encode(__buf__, __purpose__)?
-                        } else {
-                            __buf__
-                        };
-                    }
-                 } else if field.is_vec_u8() {
-                     let item = quote! {
-                        let __buf__ = {
-                            let __buf__ = bucky_raw_codec::USize(self.#member.len()).raw_encode(__buf__, __purpose__)?;
-                            if self.#member.len() == 0 {
-                                __buf__
-                            } else {
-                                unsafe {
-                                    std::ptr::copy::<u8>(self.#member.as_ptr() as *mut u8, __buf__.as_mut_ptr(), self.#member.len());
-                                }
-                                &mut __buf__[self.#member.len()..]
+                            unsafe {
+                                std::ptr::copy::<u8>(#field_expr.as_ptr() as *mut u8, __buf__.as_mut_ptr(), #field_expr.len());
                             }
-                        };
+                            &mut __buf__[#field_expr.len()..]
+                        }
                     };
-                     // println!("{}", item.to_string());
-                     item
-                 } else {
-                     quote! {
-                        let __buf__ = self.#member.raw_encode(__buf__, __purpose__)?;
-                    }
-                 }
-             })
-             .collect(),
-
-         flag_measure, fields
-             .iter()
-             .filter(|field| !field.attrs.skip_serializing())
-             .map(|field| {
-                 let member = &field.member;
-                 if optimize_option && field.is_option() {
-                     quote! {
-                        {if self.#member.is_some() {
-                            self.#member.as_ref().unwrap().raw_measure(__purpose__)?
-                        } else {
-                            0
-                        }}
-                    }
-                 } else if field.is_vec_u8() {
-                     quote!{bucky_raw_codec::USize(self.#member.len()).raw_measure(__purpose__)? + self.#member.len()}
-                 } else {
-                     quote! {
-                        self.#member.raw_measure(__purpose__)?
-                     }
-                 }
-             })
-             .collect())
-    }
+                }
+            } else {
+                quote! {
+                    let __buf__ = #field_expr.raw_encode(__buf__, __purpose__)?;
+                }
+            };
+
+            match field.attrs.purpose_condition() {
+                Some(cond) => quote! {
+                    let __buf__ = if #cond {
+                        #item
+                        __buf__
+                    } else {
+                        __buf__
+                    };
+                },
+                None => item,
+            }
+        })
+        .collect(),
+
+     flag_measure, fields
+        .iter()
+        .filter(|field| !field.attrs.skip_serializing())
+        .map(|field| {
+            let field_expr = field_expr(field);
+            let item = if optimize_option && field.is_flag_option() {
+                quote! {
+                    {if #field_expr.is_some() {
+                        #field_expr.as_ref().unwrap().raw_measure(__purpose__)?
+                    } else {
+                        0
+                    }}
+                }
+            } else if field.is_vec_u8() {
+                quote!{bucky_raw_codec::USize(#field_expr.len()).raw_measure(__purpose__)? + #field_expr.len()}
+            } else {
+                quote! {
+                    #field_expr.raw_measure(__purpose__)?
+                }
+            };
+
+            match field.attrs.purpose_condition() {
+                Some(cond) => quote! {
+                    (if #cond { #item } else { 0 })
+                },
+                None => item,
+            }
+        })
+        .collect())
 }
 
 // Serialization of an empty struct results in code like:
@@ -43,7 +43,7 @@ pub struct Variant<'a> {
 pub fn get_option_count(fields: &[Field]) -> u32 {
     let mut count = 0u32;
     for field in fields {
-        if field.is_option() {
+        if field.is_flag_option() {
             count += 1;
         }
     }
@@ -66,6 +66,11 @@ impl <'a> Field<'_> {
         re.is_match(ty.as_str())
     }
 
+    /// 参与optimize_option标志位的字段：Option类型且实际会被序列化
+    pub fn is_flag_option(&self) -> bool {
+        self.is_option() && !self.attrs.skip_serializing() && !self.attrs.skip_deserializing()
+    }
+
     pub fn get_option_type(&self) -> TokenStream {
         let ty = self.ty;
         let ty = quote!(#ty).to_string();
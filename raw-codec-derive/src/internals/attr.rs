@@ -151,6 +151,7 @@ pub struct Container {
     // cyfs_path: Option<syn::Path>,
     // is_packed: bool,
     pub optimize_option: bool,
+    pub hash_id: bool,
 }
 
 /// Styles of representing an enum.
@@ -232,6 +233,7 @@ impl Container {
         let mut variant_identifier = BoolAttr::none(cx, VARIANT_IDENTIFIER);
         let mut cyfs_path = Attr::none(cx, CRATE);
         let mut optimize_option = BoolAttr::none(cx, OPTIMIZE_OPTION);
+        let mut hash_id = BoolAttr::none(cx, HASH_ID);
 
         for meta_item in item
             .attrs
@@ -401,6 +403,11 @@ impl Container {
                     optimize_option.set_true(word);
                 },
 
+                // Parse `#[bucky(hash_id)]`
+                Meta(Path(word)) if word == HASH_ID => {
+                    hash_id.set_true(word);
+                },
+
                 // Parse `#[cyfs(tag = "type")]`
                 Meta(NameValue(m)) if m.path == TAG => {
                     if let Ok(s) = get_lit_str(cx, TAG, &m.lit) {
@@ -527,7 +534,8 @@ impl Container {
         }
 
         Container {
-            optimize_option: optimize_option.get()
+            optimize_option: optimize_option.get(),
+            hash_id: hash_id.get(),
         }
     }
 
@@ -818,6 +826,7 @@ pub struct Field {
     // name: Name,
     skip_serializing: bool,
     skip_deserializing: bool,
+    purpose_filter: Option<PurposeFilter>,
     // skip_serializing_if: Option<syn::ExprPath>,
     // default: Default,
     // serialize_with: Option<syn::ExprPath>,
@@ -830,6 +839,89 @@ pub struct Field {
     // transparent: bool,
 }
 
+/// Which `RawEncodePurpose`s a field is encoded for, from
+/// `#[bucky(skip_hash)]`, `#[bucky(skip_purpose(...))]` or
+/// `#[bucky(only_purpose(...))]`.
+pub struct PurposeFilter {
+    only: bool,
+    purposes: Vec<TokenStream>,
+    with_serialize: bool,
+}
+
+impl PurposeFilter {
+    fn from_meta(cx: &Ctxt, only: bool, nested: &Punctuated<syn::NestedMeta, syn::Token![,]>) -> Option<Self> {
+        let mut purposes = Vec::new();
+        let mut with_serialize = false;
+        for item in nested {
+            match item {
+                Meta(Path(word)) if word.is_ident("Serialize") || word.is_ident("Hash") || word.is_ident("Sign") => {
+                    with_serialize |= word.is_ident("Serialize");
+                    purposes.push(quote!(bucky_raw_codec::RawEncodePurpose::#word));
+                }
+                Meta(List(m)) if m.path.is_ident("Custom") && m.nested.len() == 1 => {
+                    match m.nested.first() {
+                        Some(Lit(syn::Lit::Int(v))) => {
+                            if v.base10_parse::<u8>().is_err() {
+                                cx.error_spanned_by(v, "custom purpose must be a u8");
+                                return None;
+                            }
+                            purposes.push(quote!(bucky_raw_codec::RawEncodePurpose::Custom(#v)));
+                        }
+                        _ => {
+                            cx.error_spanned_by(m, "expected Custom(<u8>)");
+                            return None;
+                        }
+                    }
+                }
+                _ => {
+                    cx.error_spanned_by(item, "expected one of Serialize, Hash, Sign, Custom(<u8>)");
+                    return None;
+                }
+            }
+        }
+
+        if purposes.is_empty() {
+            cx.error_spanned_by(nested, "purpose list can't be empty");
+            return None;
+        }
+
+        Some(Self {
+            only,
+            purposes,
+            with_serialize,
+        })
+    }
+
+    fn skip_hash() -> Self {
+        Self {
+            only: false,
+            purposes: vec![quote!(bucky_raw_codec::RawEncodePurpose::Hash)],
+            with_serialize: false,
+        }
+    }
+
+    // 是否参与序列化编码，不参与的话解码时使用默认值
+    fn is_serialized(&self) -> bool {
+        self.only == self.with_serialize
+    }
+
+    // 生成运行时判断字段是否需要编码的表达式，purpose为None时等同于Serialize
+    fn condition(&self) -> TokenStream {
+        let purposes = &self.purposes;
+        let matched = quote! {
+            matches!(
+                __purpose__.as_ref().unwrap_or(&bucky_raw_codec::RawEncodePurpose::Serialize),
+                #(#purposes)|*
+            )
+        };
+        if self.only {
+            matched
+        } else {
+            quote!(!#matched)
+        }
+    }
+}
+
 /// Represents the default to use for a field when deserializing.
 pub enum Default {
     /// Field must always be specified because it does not have a default.
@@ -862,6 +954,7 @@ impl Field {
         // let mut de_aliases = VecAttr::none(cx, RENAME);
         let mut skip_serializing = BoolAttr::none(cx, SKIP_SERIALIZING);
         let mut skip_deserializing = BoolAttr::none(cx, SKIP_DESERIALIZING);
+        let mut purpose_filter = Attr::none(cx, SKIP_PURPOSE);
         // let mut skip_serializing_if = Attr::none(cx, SKIP_SERIALIZING_IF);
         // let mut default = Attr::none(cx, DEFAULT);
         // let mut serialize_with = Attr::none(cx, SERIALIZE_WITH);
@@ -944,6 +1037,25 @@ impl Field {
                     skip_deserializing.set_true(word);
                 }
 
+                // Parse `#[bucky(skip_hash)]`
+                Meta(Path(word)) if word == SKIP_HASH => {
+                    purpose_filter.set(word, PurposeFilter::skip_hash());
+                }
+
+                // Parse `#[bucky(skip_purpose(Hash, Custom(1)))]`
+                Meta(List(m)) if m.path == SKIP_PURPOSE => {
+                    if let Some(filter) = PurposeFilter::from_meta(cx, false, &m.nested) {
+                        purpose_filter.set(&m.path, filter);
+                    }
+                }
+
+                // Parse `#[bucky(only_purpose(Serialize, Sign))]`
+                Meta(List(m)) if m.path == ONLY_PURPOSE => {
+                    if let Some(filter) = PurposeFilter::from_meta(cx, true, &m.nested) {
+                        purpose_filter.set(&m.path, filter);
+                    }
+                }
+
                 // // Parse `#[serde(skip_serializing_if = "...")]`
                 // Meta(NameValue(m)) if m.path == SKIP_SERIALIZING_IF => {
                 //     if let Ok(path) = parse_lit_into_expr_path(cx, SKIP_SERIALIZING_IF, &m.lit) {
@@ -1118,10 +1230,22 @@ impl Field {
         //     collect_lifetimes(&field.ty, &mut borrowed_lifetimes);
         // }
 
+        let purpose_filter = purpose_filter.value;
+        if purpose_filter.is_some() && field.ident.is_none() {
+            cx.error_spanned_by(field, "purpose attributes can only be used on named fields");
+        }
+
+        // 不参与序列化编码的字段，解码时使用默认值
+        let serialized = purpose_filter
+            .as_ref()
+            .map(PurposeFilter::is_serialized)
+            .unwrap_or(true);
+
         Field {
             // name: Name::from_attrs(ident, ser_name, de_name, Some(de_aliases)),
             skip_serializing: skip_serializing.get(),
-            skip_deserializing: skip_deserializing.get(),
+            skip_deserializing: skip_deserializing.get() || !serialized,
+            purpose_filter,
             // skip_serializing_if: skip_serializing_if.get(),
             // default: default.get().unwrap_or(Default::None),
             // serialize_with: serialize_with.get(),
@@ -1159,6 +1283,10 @@ impl Field {
     pub fn skip_deserializing(&self) -> bool {
         self.skip_deserializing
     }
+
+    pub fn purpose_condition(&self) -> Option<TokenStream> {
+        self.purpose_filter.as_ref().map(PurposeFilter::condition)
+    }
     //
     // pub fn skip_serializing_if(&self) -> Option<&syn::ExprPath> {
     //     self.skip_serializing_if.as_ref()
@@ -4,7 +4,7 @@ pub mod attr;
 mod ctxt;
 pub use self::ctxt::Ctxt;
 
-mod case;
+pub mod case;
 mod symbol;
 
 use syn::Type;
@@ -37,6 +37,10 @@ pub const UNTAGGED: Symbol = Symbol("untagged");
 pub const VARIANT_IDENTIFIER: Symbol = Symbol("variant_identifier");
 pub const WITH: Symbol = Symbol("with");
 pub const OPTIMIZE_OPTION: Symbol = Symbol("optimize_option");
+pub const HASH_ID: Symbol = Symbol("hash_id");
+pub const SKIP_HASH: Symbol = Symbol("skip_hash");
+pub const SKIP_PURPOSE: Symbol = Symbol("skip_purpose");
+pub const ONLY_PURPOSE: Symbol = Symbol("only_purpose");
 
 impl PartialEq<Symbol> for Ident {
     fn eq(&self, word: &Symbol) -> bool {
@@ -37,7 +37,7 @@ pub fn derive_raw_decode_fn(input: TokenStream) -> TokenStream {
         .into()
 }
 
-#[proc_macro_derive(ProtobufTransform, attributes(bucky_protobuf_type))]
+#[proc_macro_derive(ProtobufTransform, attributes(bucky_protobuf_type, bucky_protobuf))]
 pub fn derive_protobuf_try_from_fn(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
     derive_protobuf_try_from_fn_impl(input).unwrap_or_else(|err| {
@@ -53,7 +53,7 @@ pub fn derive_protobuf_type_fn(input: TokenStream) -> TokenStream {
     })
 }
 
-#[proc_macro_derive(ProtobufEncode, attributes(bucky_protobuf_type))]
+#[proc_macro_derive(ProtobufEncode, attributes(bucky_protobuf_type, bucky_protobuf_backend, bucky_protobuf))]
 pub fn derive_proto_encode_fn(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
     derive_proto_encode_fn_impl(input).unwrap_or_else(|err| {
@@ -61,7 +61,7 @@ pub fn derive_proto_encode_fn(input: TokenStream) -> TokenStream {
     })
 }
 
-#[proc_macro_derive(ProtobufDecode, attributes(bucky_protobuf_type))]
+#[proc_macro_derive(ProtobufDecode, attributes(bucky_protobuf_type, bucky_protobuf_backend, bucky_protobuf))]
 pub fn derive_proto_decode_fn(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
     derive_proto_decode_fn_impl(input).unwrap_or_else(|err| {
@@ -69,6 +69,14 @@ pub fn derive_proto_decode_fn(input: TokenStream) -> TokenStream {
     })
 }
 
+#[proc_macro_derive(ProtobufSchema, attributes(bucky_protobuf_type, bucky_protobuf))]
+pub fn derive_protobuf_schema_fn(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    derive_protobuf_schema_fn_impl(input).unwrap_or_else(|err| {
+        err.to_compile_error().into()
+    })
+}
+
 #[proc_macro_derive(ProtobufEmptyEncode)]
 pub fn derive_proto_encode_empty_fn(input: TokenStream) -> TokenStream {
     let input = syn::parse_macro_input!(input as syn::DeriveInput);
@@ -2,7 +2,7 @@ use proc_macro2::Ident;
 use proc_macro2::{Span, TokenStream};
 use quote::{quote, ToTokens};
 use regex::Regex;
-use std::str::FromStr;
+use crate::internals::case::RenameRule;
 use syn::spanned::Spanned;
 use syn::Meta::List;
 use syn::*;
@@ -97,61 +97,61 @@ impl FieldEx for Field {
 // }
 
 pub fn get_proto_type(input: &DeriveInput) -> Result<TokenStream> {
-    for attr in input.attrs.iter() {
+    match get_attr_proto_type(&input.attrs)? {
+        Some(proto_type) => Ok(proto_type),
+        None => Err(syn::Error::new(
+            input.span(),
+            "not find bucky_protobuf_type attribute",
+        )),
+    }
+}
+
+// 读取#[bucky_protobuf_type(path)]，容器和enum成员上都可以使用
+fn get_attr_proto_type(attrs: &[Attribute]) -> Result<Option<TokenStream>> {
+    for attr in attrs.iter() {
         if !attr.path.is_ident("bucky_protobuf_type") {
             continue;
-        } else {
-            match attr.parse_meta() {
-                Ok(List(meta)) => {
-                    for nest in meta.nested.iter() {
-                        match nest {
-                            NestedMeta::Meta(meta) => {
-                                match meta {
-                                    Meta::Path(path) => {
-                                        return Ok(path.to_token_stream());
-                                    }
-                                    Meta::List(meta_list) => {
-                                        println!(
-                                            "meta_list {}",
-                                            meta_list.to_token_stream().to_string()
-                                        );
-                                    }
-                                    Meta::NameValue(value) => {
-                                        println!(
-                                            "NameValue {}",
-                                            value.to_token_stream().to_string()
-                                        );
-                                        // if value.path.is_ident("proto_type") {
-                                        //     if let Lit::Str(lit_str) = &value.lit {
-                                        //         let ty: syn::Type = syn::parse_str(lit_str.value().as_str()).unwrap();
-                                        //         return Ok(ty.to_token_stream());
-                                        //     }
-                                        // }
-                                    }
-                                }
-                            }
-                            NestedMeta::Lit(lit) => {
-                                println!("lit {}", lit.to_token_stream().to_string());
-                            }
-                        }
-                    }
-                }
-                Ok(other) => {
-                    return Err(syn::Error::new(
-                        other.span(),
-                        format!("attribute type err."),
-                    ));
-                }
-                Err(err) => {
-                    return Err(err);
-                }
-            }
         }
+
+        return match attr.parse_meta()? {
+            List(meta) if meta.nested.len() == 1 => match meta.nested.first().unwrap() {
+                NestedMeta::Meta(Meta::Path(path)) => Ok(Some(path.to_token_stream())),
+                other => Err(syn::Error::new(other.span(), "expected protobuf type path")),
+            },
+            other => Err(syn::Error::new(other.span(), "attribute type err.")),
+        };
+    }
+
+    Ok(None)
+}
+
+// #[bucky_protobuf_backend(bucky_raw_codec::RustProtobuf)]指定编解码使用的protobuf实现库
+// 必须显式指定，避免开启某个feature后其它类型的编码随之改变
+pub fn get_proto_backend(input: &DeriveInput) -> Result<TokenStream> {
+    for attr in input.attrs.iter() {
+        if !attr.path.is_ident("bucky_protobuf_backend") {
+            continue;
+        }
+
+        return match attr.parse_meta()? {
+            List(meta) if meta.nested.len() == 1 => match meta.nested.first().unwrap() {
+                NestedMeta::Meta(Meta::Path(path)) => Ok(path.to_token_stream()),
+                other => Err(syn::Error::new(
+                    other.span(),
+                    "expected backend type path, such as bucky_raw_codec::Prost",
+                )),
+            },
+            other => Err(syn::Error::new(
+                other.span(),
+                "expected #[bucky_protobuf_backend(path)]",
+            )),
+        };
     }
-    return Err(syn::Error::new(
+
+    Err(syn::Error::new(
         input.span(),
-        format!("not find bucky_protobuf_type attribute"),
-    ));
+        "not find bucky_protobuf_backend attribute, such as #[bucky_protobuf_backend(bucky_raw_codec::Prost)]",
+    ))
 }
 
 #[allow(unused)]
@@ -205,9 +205,12 @@ pub fn derive_protobuf_try_from_fn_impl(
 ) -> Result<proc_macro::TokenStream> {
     let ident = &input.ident;
     let proto_type = get_proto_type(&input)?;
-    let obj_try_from_proto_body = obj_try_from_proto_body(&proto_type, &input);
-    let proto_try_from_obj_ref_body = proto_try_from_obj_ref_body(&proto_type, &input);
+    let obj_try_from_proto_body = obj_try_from_proto_body(&proto_type, &input)?;
+    let proto_try_from_obj_ref_body = proto_try_from_obj_ref_body(&proto_type, &input)?;
+    let value_enum_i32_impls = value_enum_i32_impls(&proto_type, &input)?;
     let impl_block = quote! {
+        #value_enum_i32_impls
+
         #[automatically_derived]
         #[allow(non_snake_case)]
         impl bucky_raw_codec::ProtobufTransform<&#ident> for #proto_type {
@@ -281,35 +284,82 @@ pub fn derive_protobuf_transform_type_fn_impl(
     Ok(impl_block.into())
 }
 
+// 结构体中保存未识别字段的成员
+fn get_unknown_fields_member(input: &DeriveInput) -> Result<Option<TokenStream>> {
+    let data_st = match &input.data {
+        Data::Struct(data_st) => data_st,
+        _ => return Ok(None),
+    };
+
+    let mut ret = None;
+    for field in ProtoField::from_fields(&data_st.fields)? {
+        if !field.attrs.unknown_fields {
+            continue;
+        }
+        if ret.is_some() {
+            return Err(syn::Error::new(
+                field.field.span(),
+                "only one unknown_fields field is allowed",
+            ));
+        }
+        ret = Some(match &field.field.ident {
+            Some(ident) => ident.to_token_stream(),
+            None => Index::from(field.index).to_token_stream(),
+        });
+    }
+
+    Ok(ret)
+}
+
 pub fn derive_proto_encode_fn_impl(input: syn::DeriveInput) -> Result<proc_macro::TokenStream> {
     let ident = &input.ident;
     let proto_type = get_proto_type(&input)?;
+    let backend = get_proto_backend(&input)?;
+    let (encode_unknown, measure_unknown, hash_encode, hash_measure) = match get_unknown_fields_member(&input)? {
+        Some(member) => (
+            quote! {self.#member.encode(__buf__)},
+            quote! {Ok(__len__ + self.#member.len())},
+            quote! {
+                let __data__ = bucky_raw_codec::ProtobufMessageCodecHelper::encode_canonical_with_unknown::<#backend, #proto_type>(&proto_obj, &self.#member)?;
+                return bucky_raw_codec::ProtobufMessageCodecHelper::copy_to_buf(&__data__, __buf__);
+            },
+            quote! {
+                return Ok(bucky_raw_codec::ProtobufMessageCodecHelper::encode_canonical_with_unknown::<#backend, #proto_type>(&proto_obj, &self.#member)?.len());
+            },
+        ),
+        None => (
+            quote! {Ok(__buf__)},
+            quote! {Ok(__len__)},
+            quote! {
+                return bucky_raw_codec::ProtobufMessageCodecHelper::raw_encode::<#backend, #proto_type>(&proto_obj, __buf__, __purpose__);
+            },
+            quote! {
+                return bucky_raw_codec::ProtobufMessageCodecHelper::raw_measure::<#backend, #proto_type>(&proto_obj, __purpose__);
+            },
+        ),
+    };
     let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
     let impl_block = quote! {
         #[automatically_derived]
         #[allow(non_snake_case)]
         impl #impl_generics bucky_raw_codec::RawEncode for #ident #ty_generics #where_clause {
             fn raw_encode<'__de__>(&self, __buf__: &'__de__ mut [u8], __purpose__: &Option<bucky_raw_codec::RawEncodePurpose>) -> bucky_raw_codec::CodecResult<&'__de__ mut [u8]> {
-                use prost::Message;
-                use prost::bytes::BufMut;
                 let proto_obj: #proto_type = bucky_raw_codec::ProtobufTransform::transform(self)?;
-                let required = proto_obj.encoded_len();
-                let remaining = __buf__.len();
-                if required > remaining {
-                    let msg = format!("raw_encode failed.err {} except {}", remaining, required);
-                    log::error!("{}", msg.as_str());
-                    return Err(bucky_raw_codec::CodecError::new(bucky_raw_codec::CodecErrorCode::OutOfLimit, msg));
+                // 计算hash时使用确定性的编码，未识别的字段按编号合并到其中
+                if bucky_raw_codec::ProtobufMessageCodecHelper::is_hash_purpose(__purpose__) {
+                    #hash_encode
                 }
-
-                let mut tmp_buf = __buf__;
-                proto_obj.encode_raw(&mut tmp_buf);
-                Ok(tmp_buf)
+                let __buf__ = <#backend as bucky_raw_codec::ProtobufBackend<#proto_type>>::encode(&proto_obj, __buf__)?;
+                #encode_unknown
             }
 
             fn raw_measure(&self, __purpose__: &Option<bucky_raw_codec::RawEncodePurpose>) -> bucky_raw_codec::CodecResult<usize> {
-                use prost::Message;
                 let proto_obj: #proto_type = bucky_raw_codec::ProtobufTransform::transform(self)?;
-                Ok(proto_obj.encoded_len())
+                if bucky_raw_codec::ProtobufMessageCodecHelper::is_hash_purpose(__purpose__) {
+                    #hash_measure
+                }
+                let __len__ = <#backend as bucky_raw_codec::ProtobufBackend<#proto_type>>::encoded_len(&proto_obj)?;
+                #measure_unknown
             }
         }
     };
@@ -340,6 +390,18 @@ pub fn derive_proto_encode_empty_fn_impl(input: syn::DeriveInput) -> Result<proc
 pub fn derive_proto_decode_fn_impl(input: syn::DeriveInput) -> Result<proc_macro::TokenStream> {
     let ident = &input.ident;
     let proto_type = get_proto_type(&input)?;
+    let backend = get_proto_backend(&input)?;
+    let decode = match get_unknown_fields_member(&input)? {
+        Some(member) => quote! {
+            let (t, unknown) = <#backend as bucky_raw_codec::ProtobufBackend<#proto_type>>::decode_with_unknown(__buf__)?;
+            let mut obj: Self = bucky_raw_codec::ProtobufTransform::transform(t)?;
+            obj.#member = unknown;
+        },
+        None => quote! {
+            let t = <#backend as bucky_raw_codec::ProtobufBackend<#proto_type>>::decode(__buf__)?;
+            let obj: Self = bucky_raw_codec::ProtobufTransform::transform(t)?;
+        },
+    };
     let mut generics = input.generics.clone();
     generics.params.insert(
         0,
@@ -356,14 +418,9 @@ pub fn derive_proto_decode_fn_impl(input: syn::DeriveInput) -> Result<proc_macro
         impl #de_impl_generics bucky_raw_codec::RawDecode<'__de__> for #ident #ty_generics #where_clause {
             fn raw_decode(__buf__: &'__de__ [u8]) -> bucky_raw_codec::CodecResult<(Self, &'__de__ [u8])>
             {
-                use prost::Message;
-                let t = #proto_type::decode(&mut std::io::Cursor::new(__buf__)).map_err(|e| {
-                        let msg = format!("raw_encode failed.err {}", e);
-                        log::error!("{}", msg.as_str());
-                        bucky_raw_codec::CodecError::new(bucky_raw_codec::CodecErrorCode::Failed, msg)
-                    })?;
-                let len = t.encoded_len();
-                Ok((bucky_raw_codec::ProtobufTransform::transform(t)?, &__buf__[len..]))
+                #decode
+                // protobuf解码会消费整个buf，不能用encoded_len推算剩余数据
+                Ok((obj, &__buf__[__buf__.len()..]))
             }
         }
     };
@@ -397,106 +454,448 @@ pub fn derive_proto_decode_empty_fn_impl(input: syn::DeriveInput) -> Result<proc
     Ok(impl_block.into())
 }
 
-pub fn obj_try_from_proto_body(proto_type: &TokenStream, input: &DeriveInput) -> TokenStream {
-    let ident = &input.ident;
-    match &input.data {
-        Data::Enum(variants) => obj_try_from_proto_encode_enum(proto_type, ident, variants),
-        Data::Struct(data_st) => obj_try_from_proto_encode_struct(proto_type, ident, data_st),
-        Data::Union(data_union) => obj_try_from_proto_encode_union(data_union),
-    }
+// 字段和enum成员上的#[bucky_protobuf(...)]属性
+// rename = "name": 对应proto中的字段名或者oneof成员名
+// skip: 不参与protobuf编码，解码时使用Default填充
+// with = "module": 使用module::to_proto(&T)和module::from_proto(P)转换字段
+// raw: 使用raw codec把字段编码为bytes，支持T/Option<T>/Vec<T>，T需要实现RawEncode和RawDecode
+// unknown_fields: 类型为ProtobufUnknownFields的结构体字段，保存解码时未识别的字段，编码时原样写回
+// tag = N: 生成.proto时字段或者oneof成员的编号，同一个message中的字段都指定或者都不指定
+// value = N: 无数据枚举成员对应的整数值
+// other: 无数据枚举中未知的值转换为该成员，成员可以没有字段，或者只有一个i32字段保存原始值
+#[derive(Default)]
+struct ProtobufAttrs {
+    rename: Option<Ident>,
+    skip: bool,
+    with: Option<Path>,
+    raw: bool,
+    unknown_fields: bool,
+    tag: Option<u32>,
+    value: Option<i32>,
+    other: bool,
 }
 
-fn obj_try_from_proto_encode_enum(
-    proto_type: &TokenStream,
-    enum_name: &Ident,
-    variants: &DataEnum,
-) -> TokenStream {
-    let mut token_list: Vec<TokenStream> = variants.variants.iter().enumerate().map(|(enum_member_index, variant)| {
-        let enum_index = TokenStream::from_str(format!("{}", enum_member_index).as_str()).unwrap();
-        let var_ident = &variant.ident;
+impl ProtobufAttrs {
+    fn from_attrs(attrs: &[Attribute]) -> Result<Self> {
+        let mut ret = Self::default();
+        for attr in attrs.iter() {
+            if !attr.path.is_ident("bucky_protobuf") {
+                continue;
+            }
 
-        let stmt = if variant.fields.len() == 0 {
-            if proto_type.to_string() == "i32".to_string() {
-                quote! {
-                    #enum_index => #enum_name::#var_ident,
+            let meta = match attr.parse_meta()? {
+                List(meta) => meta,
+                other => {
+                    return Err(syn::Error::new(
+                        other.span(),
+                        "expected #[bucky_protobuf(...)]",
+                    ))
                 }
-            } else {
-                quote! {
-                    #proto_type::#var_ident => #enum_name::#var_ident,
+            };
+
+            for nest in meta.nested.iter() {
+                match nest {
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                        ret.skip = true;
+                    }
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("other") => {
+                        ret.other = true;
+                    }
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("raw") => {
+                        ret.raw = true;
+                    }
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("unknown_fields") => {
+                        // 不参与消息字段的转换，由ProtobufEncode/ProtobufDecode读写
+                        ret.unknown_fields = true;
+                        ret.skip = true;
+                    }
+                    NestedMeta::Meta(Meta::NameValue(value)) if value.path.is_ident("tag") => {
+                        ret.tag = Some(match &value.lit {
+                            Lit::Int(lit) => lit.base10_parse::<u32>()?,
+                            other => return Err(syn::Error::new(other.span(), "expected u32 literal")),
+                        });
+                    }
+                    NestedMeta::Meta(Meta::NameValue(value)) if value.path.is_ident("value") => {
+                        ret.value = Some(match &value.lit {
+                            Lit::Int(lit) => lit.base10_parse::<i32>()?,
+                            other => return Err(syn::Error::new(other.span(), "expected i32 literal")),
+                        });
+                    }
+                    NestedMeta::Meta(Meta::NameValue(value)) if value.path.is_ident("rename") => {
+                        let name = Self::get_lit_str(&value.lit)?;
+                        ret.rename = Some(syn::parse_str::<Ident>(name.value().as_str()).map_err(|_| {
+                            syn::Error::new(name.span(), format!("invalid protobuf name: {}", name.value()))
+                        })?);
+                    }
+                    NestedMeta::Meta(Meta::NameValue(value)) if value.path.is_ident("with") => {
+                        let name = Self::get_lit_str(&value.lit)?;
+                        ret.with = Some(syn::parse_str::<Path>(name.value().as_str()).map_err(|_| {
+                            syn::Error::new(name.span(), format!("invalid module path: {}", name.value()))
+                        })?);
+                    }
+                    other => {
+                        return Err(syn::Error::new(
+                            other.span(),
+                            "unknown bucky_protobuf attribute, expected rename, skip, with, raw, unknown_fields, tag, value or other",
+                        ))
+                    }
                 }
             }
+        }
+
+        Ok(ret)
+    }
+
+    fn get_lit_str(lit: &Lit) -> Result<&LitStr> {
+        match lit {
+            Lit::Str(lit) => Ok(lit),
+            other => Err(syn::Error::new(other.span(), "expected string literal")),
+        }
+    }
+
+    fn from_variant(variant: &Variant) -> Result<Self> {
+        let ret = Self::from_attrs(&variant.attrs)?;
+        if ret.skip || ret.with.is_some() || ret.raw {
+            return Err(syn::Error::new(
+                variant.span(),
+                "only rename, tag, value and other are supported on enum variant",
+            ));
+        }
+
+        Ok(ret)
+    }
+
+    fn from_field(field: &Field) -> Result<Self> {
+        let ret = Self::from_attrs(&field.attrs)?;
+        if ret.value.is_some() || ret.other {
+            return Err(syn::Error::new(
+                field.span(),
+                "value and other are only supported on enum variant",
+            ));
+        }
+        if ret.raw && ret.with.is_some() {
+            return Err(syn::Error::new(
+                field.span(),
+                "raw and with can't be used together",
+            ));
+        }
+        if ret.unknown_fields && (ret.raw || ret.with.is_some() || ret.rename.is_some() || ret.tag.is_some()) {
+            return Err(syn::Error::new(
+                field.span(),
+                "unknown_fields can't be used with other attributes",
+            ));
+        }
+
+        Ok(ret)
+    }
+}
+
+// 解析后的字段，index为字段在rust结构中的序号
+struct ProtoField<'a> {
+    field: &'a Field,
+    index: usize,
+    attrs: ProtobufAttrs,
+}
+
+impl<'a> ProtoField<'a> {
+    fn from_fields(fields: &'a Fields) -> Result<Vec<Self>> {
+        let mut list = Vec::new();
+        for (index, field) in fields.iter().enumerate() {
+            list.push(Self {
+                field,
+                index,
+                attrs: ProtobufAttrs::from_field(field)?,
+            });
+        }
+
+        Ok(list)
+    }
+
+    fn from_variant_fields(fields: &'a Fields) -> Result<Vec<Self>> {
+        let list = Self::from_fields(fields)?;
+        if let Some(field) = list.iter().find(|field| field.attrs.unknown_fields) {
+            return Err(syn::Error::new(
+                field.field.span(),
+                "unknown_fields is only supported on struct field",
+            ));
+        }
+
+        Ok(list)
+    }
+
+    // proto结构中对应的字段名，具名字段与rust字段同名，tuple字段依次为field1, field2...
+    fn proto_ident(&self) -> Ident {
+        if let Some(rename) = &self.attrs.rename {
+            return rename.clone();
+        }
+
+        match &self.field.ident {
+            Some(ident) => ident.clone(),
+            None => Ident::new(format!("field{}", self.index + 1).as_str(), Span::call_site()),
+        }
+    }
+
+    // enum成员在match中绑定的变量名
+    fn binding_ident(&self) -> Ident {
+        match &self.field.ident {
+            Some(ident) => ident.clone(),
+            None => Ident::new(format!("field{}", self.index).as_str(), Span::call_site()),
+        }
+    }
+
+    fn binding_pattern(&self) -> TokenStream {
+        let binding = self.binding_ident();
+        match (&self.field.ident, self.attrs.skip) {
+            (Some(ident), true) => quote! {#ident: _},
+            (None, true) => quote! {_},
+            _ => quote! {#binding},
+        }
+    }
+
+    // 从proto的值转换为rust字段的值
+    fn value_from_proto(&self, value: TokenStream) -> TokenStream {
+        let ty_str = self.field.ty.to_token_stream().to_string();
+        if self.attrs.skip {
+            quote! {Default::default()}
+        } else if let Some(with) = &self.attrs.with {
+            quote! {#with::from_proto(#value)?}
+        } else if self.attrs.raw {
+            quote! {bucky_raw_codec::ProtobufRawField::raw_from_proto(#value)?}
+        } else if is_vec_u8(ty_str.as_str()) {
+            quote! {#value}
         } else {
-            let params_tokens: Vec<TokenStream> = variant.fields.iter().enumerate().map(|(index, field)| {
-                let field_ident = &field.ident;
-                if field_ident.is_some() {
-                    let field_ident = field_ident.as_ref().unwrap();
-                    quote! {#field_ident,}
-                } else {
-                    let field_ident = TokenStream::from_str(format!("field{}", index).as_str()).unwrap();
-                    quote! {
-                        #field_ident,
-                    }
-                }
-            }).collect();
+            quote! {bucky_raw_codec::ProtobufTransform::transform(#value)?}
+        }
+    }
 
-            let mut is_union = false;
-            let field_tokens: Vec<TokenStream> = if variant.fields.len() == 1 {
-                variant.fields.iter().enumerate().map(|(index, field)| {
-                    let ident: &Option<Ident> = &field.ident;
-                    let ty = &field.ty;
-                    let ty_str = ty.to_token_stream().to_string();
-                    if ident.is_some() {
-                        is_union = false;
-                        let ident = ident.as_ref().unwrap();
-                        if is_vec_u8(ty_str.as_str()) {
-                            quote! {
-                                #ident,
-                            }
-                        } else {
-                            quote! {
-                                #ident: bucky_raw_codec::ProtobufTransform::transform(#ident)?,
-                            }
-                        }
-                    } else {
-                        is_union = true;
-                        let obj_field_ident = TokenStream::from_str(format!("field{}", index).as_str()).unwrap();
-                        if is_vec_u8(ty_str.as_str()) {
-                            quote! {
-                                #obj_field_ident,
-                            }
-                        } else {
-                            quote! {
-                                bucky_raw_codec::ProtobufTransform::transform(#obj_field_ident)?,
-                            }
-                        }
-                    }
-                }).collect()
+    // 从rust字段的值转换为proto的值，value_is_ref表示value是引用
+    fn value_to_proto(&self, value: TokenStream, value_is_ref: bool) -> TokenStream {
+        let ty_str = self.field.ty.to_token_stream().to_string();
+        if let Some(with) = &self.attrs.with {
+            if value_is_ref {
+                quote! {#with::to_proto(#value)?}
             } else {
-                panic!("enum {} member fields count can't more than 1", enum_name.to_string());
-            };
+                quote! {#with::to_proto(&#value)?}
+            }
+        } else if self.attrs.raw {
+            if value_is_ref {
+                quote! {bucky_raw_codec::ProtobufRawField::raw_to_proto(#value)?}
+            } else {
+                quote! {bucky_raw_codec::ProtobufRawField::raw_to_proto(&#value)?}
+            }
+        } else if is_vec_u8(ty_str.as_str()) {
+            if value_is_ref {
+                quote! {#value.clone()}
+            } else {
+                quote! {#value}
+            }
+        } else {
+            quote! {bucky_raw_codec::ProtobufTransform::transform(#value)?}
+        }
+    }
 
-            if is_union {
-                quote! {
-                    #proto_type::#var_ident(#(#params_tokens)*) => {
-                        #enum_name::#var_ident(#(#field_tokens)*)
-                    },
+    // 构造rust结构时的字段初始化
+    fn construct(&self, value: TokenStream) -> TokenStream {
+        match &self.field.ident {
+            Some(ident) => quote! {#ident: #value,},
+            None => quote! {#value,},
+        }
+    }
+}
+
+// oneof中对应的成员名
+fn variant_proto_ident(variant: &Variant) -> Result<Ident> {
+    let attrs = ProtobufAttrs::from_variant(variant)?;
+    Ok(attrs.rename.unwrap_or_else(|| variant.ident.clone()))
+}
+
+// 只有一个无名字段的enum成员直接对应oneof中的值，其它有数据的成员对应oneof中的嵌套message
+// 嵌套message的类型通过成员上的#[bucky_protobuf_type(...)]指定
+fn variant_nested_type(variant: &Variant, fields: &[ProtoField]) -> Result<Option<TokenStream>> {
+    let nested_type = get_attr_proto_type(&variant.attrs)?;
+    let is_single_unnamed = match &variant.fields {
+        Fields::Unnamed(fields) => fields.unnamed.len() == 1,
+        _ => false,
+    };
+
+    if nested_type.is_none() {
+        if !is_single_unnamed {
+            return Err(syn::Error::new(
+                variant.span(),
+                format!(
+                    "enum variant {} with named or multiple fields must set #[bucky_protobuf_type(...)] to the nested message",
+                    variant.ident
+                ),
+            ));
+        }
+
+        if fields[0].attrs.skip || fields[0].attrs.rename.is_some() || fields[0].attrs.tag.is_some() {
+            return Err(syn::Error::new(
+                fields[0].field.span(),
+                "skip, rename and tag are not supported on the oneof value field, set them on the variant",
+            ));
+        }
+    }
+
+    Ok(nested_type)
+}
+
+// 无数据枚举的成员，other成员只有一个i32字段时value为None
+struct ValueVariant<'a> {
+    variant: &'a Variant,
+    proto_ident: Ident,
+    value: Option<i32>,
+    other: bool,
+}
+
+impl ValueVariant<'_> {
+    // 成员对应的i32值，映射到i32时使用成员的值，映射到生成的枚举类型时使用该枚举值
+    fn value_expr(&self, proto_type: &TokenStream, is_i32: bool) -> TokenStream {
+        if is_i32 {
+            let value = proc_macro2::Literal::i32_unsuffixed(self.value.unwrap());
+            quote! {#value}
+        } else {
+            let proto_ident = &self.proto_ident;
+            quote! {(#proto_type::#proto_ident as i32)}
+        }
+    }
+}
+
+// 先按i64解析，-2147483648这样的字面量去掉负号后超出了i32的范围
+fn parse_discriminant(expr: &Expr) -> Result<i64> {
+    match expr {
+        Expr::Lit(ExprLit { lit: Lit::Int(lit), .. }) => lit.base10_parse::<i64>(),
+        Expr::Unary(ExprUnary { op: UnOp::Neg(_), expr, .. }) => Ok(-parse_discriminant(expr)?),
+        other => Err(syn::Error::new(
+            other.span(),
+            "enum discriminant must be an integer literal, or set #[bucky_protobuf(value = N)]",
+        )),
+    }
+}
+
+// 所有成员都没有数据(other成员除外)时返回各成员的值
+// 有#[bucky_protobuf(value = N)]时取N，否则取成员在rust中的discriminant
+// discriminant单独计算：显式指定的值，或者上一个成员的discriminant加1，value属性不影响后面的成员
+fn value_enum_variants(variants: &DataEnum) -> Result<Option<Vec<ValueVariant<'_>>>> {
+    let mut list = Vec::new();
+    let mut has_data = false;
+    let mut has_other = false;
+    // 无法解析的显式discriminant(比如常量表达式)为None，后面的成员只能使用value属性
+    let mut next_discriminant = Some(0i64);
+    for variant in variants.variants.iter() {
+        let attrs = ProtobufAttrs::from_variant(variant)?;
+        if attrs.tag.is_some() && (variant.fields.is_empty() || attrs.other) {
+            return Err(syn::Error::new(variant.span(), "tag is only supported on oneof variant, use value instead"));
+        }
+        let discriminant = match &variant.discriminant {
+            Some((_, expr)) => match parse_discriminant(expr) {
+                Ok(value) => Some(value),
+                Err(e) if attrs.value.is_none() => return Err(e),
+                Err(_) => None,
+            },
+            None => next_discriminant,
+        };
+        next_discriminant = discriminant.and_then(|value| value.checked_add(1));
+        let proto_ident = attrs.rename.clone().unwrap_or_else(|| variant.ident.clone());
+
+        if attrs.other {
+            if has_other {
+                return Err(syn::Error::new(variant.span(), "only one variant can be other"));
+            }
+            has_other = true;
+
+            let is_raw_value = match &variant.fields {
+                Fields::Unit => false,
+                Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                    let ty = &fields.unnamed[0].ty;
+                    if quote!(#ty).to_string() != "i32" {
+                        return Err(syn::Error::new(ty.span(), "other variant field must be i32"));
+                    }
+                    true
                 }
-            } else {
-                quote! {
-                    #proto_type::#var_ident(#(#params_tokens)*) => {
-                        #enum_name::#var_ident{#(#field_tokens)*}
-                    },
+                _ => {
+                    return Err(syn::Error::new(
+                        variant.span(),
+                        "other variant must be unit or have a single i32 field",
+                    ))
                 }
+            };
+
+            if is_raw_value {
+                list.push(ValueVariant { variant, proto_ident, value: None, other: true });
+                continue;
+            }
+        } else if !variant.fields.is_empty() {
+            has_data = true;
+            continue;
+        }
+
+        let value = match (attrs.value, discriminant) {
+            (Some(value), _) => value,
+            (None, Some(discriminant)) => i32::try_from(discriminant).map_err(|_| {
+                syn::Error::new(
+                    variant.span(),
+                    format!("discriminant {} is out of i32 range, set #[bucky_protobuf(value = N)]", discriminant),
+                )
+            })?,
+            (None, None) => {
+                return Err(syn::Error::new(
+                    variant.span(),
+                    "can't infer discriminant after a non-literal one, set #[bucky_protobuf(value = N)]",
+                ))
             }
         };
-        stmt
-    }).collect();
+        if let Some(prev) = list.iter().find(|v: &&ValueVariant| v.value == Some(value)) {
+            return Err(syn::Error::new(
+                variant.span(),
+                format!("value {} is already used by variant {}", value, prev.variant.ident),
+            ));
+        }
+        list.push(ValueVariant { variant, proto_ident, value: Some(value), other: attrs.other });
+    }
 
-    if proto_type.to_string() == "i32".to_string() {
-        token_list.push(quote! {
-                    _ => return Err(bucky_raw_codec::CodecError::new(bucky_raw_codec::CodecErrorCode::InvalidInput, format!("transform {} to {} failed.", _value_, stringify!(#enum_name))))
-                })
+    if has_data {
+        if has_other {
+            return Err(syn::Error::new(
+                variants.enum_token.span(),
+                "other is only supported on enum without data",
+            ));
+        }
+        return Ok(None);
     }
+
+    Ok(Some(list))
+}
+
+// i32转换为无数据枚举
+fn value_enum_from_i32(
+    proto_type: &TokenStream,
+    enum_name: &Ident,
+    variants: &[ValueVariant],
+    is_i32: bool,
+) -> TokenStream {
+    let mut token_list: Vec<TokenStream> = variants.iter().filter(|v| v.value.is_some()).map(|v| {
+        let var_ident = &v.variant.ident;
+        let value = v.value_expr(proto_type, is_i32);
+        quote! {
+            __value__ if __value__ == #value => #enum_name::#var_ident,
+        }
+    }).collect();
+
+    token_list.push(match variants.iter().find(|v| v.other) {
+        Some(v) => {
+            let var_ident = &v.variant.ident;
+            if v.value.is_some() {
+                quote! {_ => #enum_name::#var_ident,}
+            } else {
+                quote! {__value__ => #enum_name::#var_ident(__value__),}
+            }
+        }
+        None => quote! {
+            _ => return Err(bucky_raw_codec::CodecError::new(bucky_raw_codec::CodecErrorCode::InvalidInput, format!("transform {} to {} failed.", _value_, stringify!(#enum_name))))
+        },
+    });
+
     quote! {
         {
             match _value_ {
@@ -506,377 +905,531 @@ fn obj_try_from_proto_encode_enum(
     }
 }
 
-fn obj_try_from_proto_encode_struct(
-    _proto_type: &TokenStream,
-    struct_ident: &Ident,
-    data_st: &DataStruct,
+// 无数据枚举转换为i32
+fn value_enum_to_i32(
+    proto_type: &TokenStream,
+    enum_name: &Ident,
+    variants: &[ValueVariant],
+    is_i32: bool,
+    by_ref: bool,
 ) -> TokenStream {
-    let mut is_union = false;
-    let token_list: Vec<TokenStream> = data_st
-        .fields
-        .iter()
-        .enumerate()
-        .map(|(_index, field)| {
-            let ident: &Option<Ident> = &field.ident;
-            let ty = &field.ty;
-            let ty_str = ty.to_token_stream().to_string();
-            if ident.is_some() {
-                is_union = false;
-                let ident = ident.as_ref().unwrap();
-                if is_vec_u8(ty_str.as_str()) {
-                    quote! {
-                    #ident: _value_.#ident,
-                }
-                } else {
-                    quote! {
-                    #ident: bucky_raw_codec::ProtobufTransform::transform(_value_.#ident)?,
-                }
-                }
-            } else {
-                panic!("struct {} member must has token name", struct_ident.to_string());
-                // is_union = true;
-                // let ident = Ident::new(format!("field{}", index + 1).as_str(), Span::call_site());
-                // if is_vec_u8(ty_str.as_str()) {
-                //     quote! {
-                //     _value_.#ident,
-                // }
-                // } else {
-                //     quote! {
-                //     bucky_raw_codec::ProtobufTransform::transform(_value_.#ident)?,
-                // }
-                // }
+    let token_list: Vec<TokenStream> = variants.iter().map(|v| {
+        let var_ident = &v.variant.ident;
+        if v.value.is_some() {
+            let value = v.value_expr(proto_type, is_i32);
+            quote! {#enum_name::#var_ident => #value,}
+        } else if by_ref {
+            quote! {#enum_name::#var_ident(__value__) => *__value__,}
+        } else {
+            quote! {#enum_name::#var_ident(__value__) => __value__,}
+        }
+    }).collect();
+
+    quote! {
+        {
+            match _value_ {
+                #(#token_list)*
             }
-        })
-        .collect();
-    if is_union {
+        }
+    }
+}
+
+// 生成的枚举类型转换为无数据枚举，按成员名对应
+fn value_enum_from_proto_enum(
+    proto_type: &TokenStream,
+    enum_name: &Ident,
+    variants: &[ValueVariant],
+) -> TokenStream {
+    let mut token_list: Vec<TokenStream> = variants.iter().filter(|v| v.value.is_some()).map(|v| {
+        let var_ident = &v.variant.ident;
+        let proto_ident = &v.proto_ident;
         quote! {
-            Self(#(#token_list)*)
+            #proto_type::#proto_ident => #enum_name::#var_ident,
         }
-    } else {
+    }).collect();
+
+    if let Some(v) = variants.iter().find(|v| v.other) {
+        let var_ident = &v.variant.ident;
+        token_list.push(if v.value.is_some() {
+            quote! {_ => #enum_name::#var_ident,}
+        } else {
+            quote! {__value__ => #enum_name::#var_ident(__value__ as i32),}
+        });
+    }
+
+    quote! {
+        {
+            match _value_ {
+                #(#token_list)*
+            }
+        }
+    }
+}
+
+// 无数据枚举转换为生成的枚举类型，other成员保存的原始值需要是已知的枚举值
+fn value_enum_to_proto_enum(
+    proto_type: &TokenStream,
+    enum_name: &Ident,
+    variants: &[ValueVariant],
+    by_ref: bool,
+) -> TokenStream {
+    let known: Vec<TokenStream> = variants.iter().filter(|v| v.value.is_some()).map(|v| {
+        let proto_ident = &v.proto_ident;
         quote! {
-            Self{#(#token_list)*}
+            __value__ if __value__ == #proto_type::#proto_ident as i32 => #proto_type::#proto_ident,
+        }
+    }).collect();
+
+    let token_list: Vec<TokenStream> = variants.iter().map(|v| {
+        let var_ident = &v.variant.ident;
+        let proto_ident = &v.proto_ident;
+        if v.value.is_some() {
+            quote! {#enum_name::#var_ident => #proto_type::#proto_ident,}
+        } else {
+            let raw_value = if by_ref { quote! {*__raw__} } else { quote! {__raw__} };
+            quote! {
+                #enum_name::#var_ident(__raw__) => match #raw_value {
+                    #(#known)*
+                    __value__ => return Err(bucky_raw_codec::CodecError::new(bucky_raw_codec::CodecErrorCode::InvalidInput, format!("transform {} to {} failed.", __value__, stringify!(#proto_type)))),
+                },
+            }
+        }
+    }).collect();
+
+    quote! {
+        {
+            match _value_ {
+                #(#token_list)*
+            }
         }
     }
 }
 
-#[allow(unused)]
-pub fn proto_try_from_obj_body(proto_type: &TokenStream, input: &DeriveInput) -> TokenStream {
+// 映射到生成的枚举类型时，另外实现与i32的转换，prost生成的message中枚举字段是i32
+pub fn value_enum_i32_impls(proto_type: &TokenStream, input: &DeriveInput) -> Result<TokenStream> {
+    let variants = match &input.data {
+        Data::Enum(variants) => variants,
+        _ => return Ok(quote! {}),
+    };
+    if proto_type.to_string() == "i32" {
+        return Ok(quote! {});
+    }
+    let list = match value_enum_variants(variants)? {
+        Some(list) => list,
+        None => return Ok(quote! {}),
+    };
+
+    let ident = &input.ident;
+    let from_i32 = value_enum_from_i32(proto_type, ident, &list, false);
+    let to_i32 = value_enum_to_i32(proto_type, ident, &list, false, true);
+    Ok(quote! {
+        #[automatically_derived]
+        #[allow(non_snake_case)]
+        impl bucky_raw_codec::ProtobufTransform<&#ident> for i32 {
+            fn transform(_value_: &#ident) -> bucky_raw_codec::CodecResult<Self> {
+                Ok(#to_i32)
+            }
+        }
+
+        #[automatically_derived]
+        #[allow(non_snake_case)]
+        impl bucky_raw_codec::ProtobufTransform<i32> for #ident {
+            fn transform(_value_: i32) -> bucky_raw_codec::CodecResult<Self> {
+                Ok(#from_i32)
+            }
+        }
+    })
+}
+
+fn union_unsupported(input: &DeriveInput) -> syn::Error {
+    syn::Error::new(input.ident.span(), "bucky_protobuf does not support union")
+}
+
+pub fn obj_try_from_proto_body(proto_type: &TokenStream, input: &DeriveInput) -> Result<TokenStream> {
     let ident = &input.ident;
     match &input.data {
-        Data::Enum(variants) => proto_try_from_obj_encode_enum(proto_type, ident, variants),
-        Data::Struct(data_st) => proto_try_from_obj_encode_struct(proto_type, ident, data_st),
-        Data::Union(data_union) => proto_try_from_obj_encode_union(data_union),
+        Data::Enum(variants) => obj_try_from_proto_encode_enum(proto_type, ident, variants),
+        Data::Struct(data_st) => obj_try_from_proto_encode_struct(data_st),
+        Data::Union(_) => Err(union_unsupported(input)),
     }
 }
 
-#[allow(unused)]
-fn proto_try_from_obj_encode_enum(
+fn obj_try_from_proto_encode_enum(
     proto_type: &TokenStream,
     enum_name: &Ident,
     variants: &DataEnum,
-) -> TokenStream {
-    let token_list: Vec<TokenStream> = variants.variants.iter().enumerate().map(|(enum_member_index, variant)| {
-        let enum_index = TokenStream::from_str(format!("{}", enum_member_index).as_str()).unwrap();
+) -> Result<TokenStream> {
+    let is_i32 = proto_type.to_string() == "i32";
+    if let Some(list) = value_enum_variants(variants)? {
+        return Ok(if is_i32 {
+            value_enum_from_i32(proto_type, enum_name, &list, true)
+        } else {
+            value_enum_from_proto_enum(proto_type, enum_name, &list)
+        });
+    }
+    if is_i32 {
+        return Err(syn::Error::new(
+            enum_name.span(),
+            format!("enum {} with data can't map to i32", enum_name),
+        ));
+    }
+
+    let mut token_list = Vec::new();
+    for variant in variants.variants.iter() {
         let var_ident = &variant.ident;
-        if variant.fields.len() == 0 {
-            if proto_type.to_string() == "i32".to_string() {
-                quote! {
-                    #enum_name::#var_ident => {
-                        #enum_index
-                    }
-                }
-            } else {
-                quote! {
-                    #enum_name::#var_ident => {
-                        #proto_type::#var_ident
-                    }
-                }
+        let proto_var_ident = variant_proto_ident(variant)?;
+        let fields = ProtoField::from_variant_fields(&variant.fields)?;
+
+        let stmt = if fields.is_empty() {
+            quote! {
+                #proto_type::#proto_var_ident => #enum_name::#var_ident,
+            }
+        } else if variant_nested_type(variant, &fields)?.is_none() {
+            let value = fields[0].value_from_proto(quote! {field0});
+            quote! {
+                #proto_type::#proto_var_ident(field0) => {
+                    #enum_name::#var_ident(#value)
+                },
             }
         } else {
-            let param_tokens: Vec<TokenStream> = variant.fields.iter().enumerate().map(|(index, field)| {
-                let field_ident = &field.ident;
-                if field_ident.is_some() {
-                    let field_ident = field_ident.as_ref().unwrap();
-                    quote! {#field_ident,}
-                } else {
-                    let field_ident = TokenStream::from_str(format!("field{}", index).as_str()).unwrap();
-                    quote! {
-                        #field_ident,
-                    }
-                }
+            let values: Vec<TokenStream> = fields.iter().map(|field| {
+                let proto_ident = field.proto_ident();
+                field.construct(field.value_from_proto(quote! {__msg__.#proto_ident}))
             }).collect();
 
-            let mut is_union = false;
-            let field_set_tokens: Vec<TokenStream> = if variant.fields.len() == 1 {
-                variant.fields.iter().enumerate().map(|(index, field)| {
-                    let field_ident = &field.ident;
-                    let ty = &field.ty;
-                    let ty_str = ty.to_token_stream().to_string();
-                    if field_ident.is_some() {
-                        is_union = false;
-                        let field_ident = field_ident.as_ref().unwrap();
-                        if is_vec_u8(ty_str.as_str()) {
-                            quote! {
-                                #field_ident,
-                            }
-                        } else {
-                            quote! {
-                                #field_ident: bucky_raw_codec::ProtobufTransform::transform(#field_ident)?,
-                            }
-                        }
-                    } else {
-                        is_union = true;
-                        let obj_field_ident = TokenStream::from_str(format!("field{}", index).as_str()).unwrap();
-                        if is_vec_u8(ty_str.as_str()) {
-                            quote! {
-                                #obj_field_ident,
-                            }
-                        } else {
-                            quote! {
-                                bucky_raw_codec::ProtobufTransform::transform(#obj_field_ident)?,
-                            }
-                        }
-                    }
-                }).collect()
-            } else {
-                panic!("enum {} fields count can't more than 1", enum_name.to_string());
+            let construct = match &variant.fields {
+                Fields::Named(_) => quote! {#enum_name::#var_ident{#(#values)*}},
+                _ => quote! {#enum_name::#var_ident(#(#values)*)},
             };
-            if is_union {
-                quote! {
-                    #enum_name::#var_ident(#(#param_tokens)*) => {
-                        #proto_type::#var_ident(#(#field_set_tokens)*)
-                    },
-                }
-            } else {
-                quote! {
-                    #enum_name::#var_ident {#(#param_tokens)*} => {
-                        #proto_type::#var_ident{#(#field_set_tokens)*}
-                    },
-                }
+            quote! {
+                #proto_type::#proto_var_ident(__msg__) => {
+                    #construct
+                },
             }
-        }
-    }).collect();
-    quote! {
+        };
+        token_list.push(stmt);
+    }
+
+    Ok(quote! {
         {
             match _value_ {
                 #(#token_list)*
             }
         }
-    }
+    })
 }
 
-#[allow(unused)]
-fn proto_try_from_obj_encode_struct(
-    proto_type: &TokenStream,
-    _struct_ident: &Ident,
-    data_st: &DataStruct,
-) -> TokenStream {
-    let token_list: Vec<TokenStream> = data_st
-        .fields
+fn obj_try_from_proto_encode_struct(data_st: &DataStruct) -> Result<TokenStream> {
+    let fields = ProtoField::from_fields(&data_st.fields)?;
+    let token_list: Vec<TokenStream> = fields
         .iter()
-        .enumerate()
-        .map(|(index, field)| {
-            let ident: &Option<Ident> = &field.ident;
-            let ty = &field.ty;
-            let ty_str = ty.to_token_stream().to_string();
-            if ident.is_some() {
-                let ident = ident.as_ref().unwrap();
-                if is_vec_u8(ty_str.as_str()) {
-                    quote! {
-                    #ident: _value_.#ident,
-                }
-                } else {
-                    quote! {
-                    #ident: bucky_raw_codec::ProtobufTransform::transform(_value_.#ident)?,
-                }
-                }
-            } else {
-                let ident = Ident::new(format!("field{}", index + 1).as_str(), Span::call_site());
-                let obj_field_ident = TokenStream::from_str(format!("{}", index).as_str()).unwrap();
-                if is_vec_u8(ty_str.as_str()) {
-                    quote! {
-                    #ident: _value_.#obj_field_ident,
-                }
-                } else {
-                    quote! {
-                    #ident: bucky_raw_codec::ProtobufTransform::transform(_value_.#obj_field_ident)?,
-                }
-                }
-            }
+        .map(|field| {
+            let proto_ident = field.proto_ident();
+            field.construct(field.value_from_proto(quote! {_value_.#proto_ident}))
         })
         .collect();
-    quote! {
-        #proto_type{#(#token_list)*}
-    }
+
+    Ok(match &data_st.fields {
+        Fields::Named(_) => quote! {Self{#(#token_list)*}},
+        Fields::Unnamed(_) => quote! {Self(#(#token_list)*)},
+        Fields::Unit => quote! {Self},
+    })
 }
 
 #[allow(unused)]
-fn obj_try_from_proto_encode_union(_data_union: &DataUnion) -> TokenStream {
-    panic!("unsupport union");
+pub fn proto_try_from_obj_body(proto_type: &TokenStream, input: &DeriveInput) -> Result<TokenStream> {
+    proto_try_from_obj_body_impl(proto_type, input, false)
 }
 
-fn proto_try_from_obj_encode_union(_data_union: &DataUnion) -> TokenStream {
-    panic!("unsupport union");
+pub fn proto_try_from_obj_ref_body(proto_type: &TokenStream, input: &DeriveInput) -> Result<TokenStream> {
+    proto_try_from_obj_body_impl(proto_type, input, true)
 }
 
-pub fn proto_try_from_obj_ref_body(proto_type: &TokenStream, input: &DeriveInput) -> TokenStream {
+fn proto_try_from_obj_body_impl(
+    proto_type: &TokenStream,
+    input: &DeriveInput,
+    by_ref: bool,
+) -> Result<TokenStream> {
     let ident = &input.ident;
     match &input.data {
-        Data::Enum(variants) => proto_try_from_obj_ref_encode_enum(proto_type, ident, variants),
-        Data::Struct(data_st) => proto_try_from_obj_ref_encode_struct(proto_type, ident, data_st),
-        Data::Union(data_union) => proto_try_from_obj_ref_encode_union(data_union),
+        Data::Enum(variants) => proto_try_from_obj_encode_enum(proto_type, ident, variants, by_ref),
+        Data::Struct(data_st) => proto_try_from_obj_encode_struct(proto_type, data_st, by_ref),
+        Data::Union(_) => Err(union_unsupported(input)),
     }
 }
 
-fn proto_try_from_obj_ref_encode_enum(
+fn proto_try_from_obj_encode_enum(
     proto_type: &TokenStream,
     enum_name: &Ident,
     variants: &DataEnum,
-) -> TokenStream {
-    let token_list: Vec<TokenStream> = variants.variants.iter().enumerate().map(|(enum_member_index, variant)| {
-        let enum_index = TokenStream::from_str(format!("{}", enum_member_index).as_str()).unwrap();
-        let var_ident = &variant.ident;
-        if variant.fields.len() == 0 {
-            if proto_type.to_string() == "i32".to_string() {
-                quote! {
-                    #enum_name::#var_ident => {
-                        #enum_index
-                    }
-                }
-            } else {
-                quote! {
-                    #enum_name::#var_ident => {
-                        #proto_type::#var_ident
-                    }
-                }
-            }
+    by_ref: bool,
+) -> Result<TokenStream> {
+    let is_i32 = proto_type.to_string() == "i32";
+    if let Some(list) = value_enum_variants(variants)? {
+        return Ok(if is_i32 {
+            value_enum_to_i32(proto_type, enum_name, &list, true, by_ref)
         } else {
-            let param_tokens: Vec<TokenStream> = variant.fields.iter().enumerate().map(|(index, field)| {
-                let field_ident = &field.ident;
-                if field_ident.is_some() {
-                    let field_ident = field_ident.as_ref().unwrap();
-                    quote! {#field_ident,}
-                } else {
-                    let param_ident = TokenStream::from_str(format!("field{}", index).as_str()).unwrap();
-                    quote! {
-                        #param_ident,
-                    }
-                }
-            }).collect();
+            value_enum_to_proto_enum(proto_type, enum_name, &list, by_ref)
+        });
+    }
+    if is_i32 {
+        return Err(syn::Error::new(
+            enum_name.span(),
+            format!("enum {} with data can't map to i32", enum_name),
+        ));
+    }
 
-            let mut is_union = false;
-            let field_set_tokens: Vec<TokenStream> = if variant.fields.len() == 1 {
-                variant.fields.iter().enumerate().map(|(index, field)| {
-                    let field_ident = &field.ident;
-                    let ty = &field.ty;
-                    let ty_str = ty.to_token_stream().to_string();
-                    if field_ident.is_some() {
-                        is_union = false;
-                        let field_ident = field_ident.as_ref().unwrap();
-                        if is_vec_u8(ty_str.as_str()) {
-                            quote! {
-                                #field_ident,
-                            }
-                        } else {
-                            quote! {
-                                #field_ident: bucky_raw_codec::ProtobufTransform::transform(#field_ident)?,
-                            }
-                        }
-                    } else {
-                        is_union = true;
-                        let obj_field_ident = TokenStream::from_str(format!("field{}", index).as_str()).unwrap();
-                        if is_vec_u8(ty_str.as_str()) {
-                            quote! {
-                                #obj_field_ident,
-                            }
-                        } else {
-                            quote! {
-                                bucky_raw_codec::ProtobufTransform::transform(#obj_field_ident)?,
-                            }
-                        }
-                    }
-                }).collect()
-            } else {
-                panic!("enum {} fields count can't more than 1", enum_name.to_string());
-            };
-            if is_union {
-                quote! {
-                    #enum_name::#var_ident(#(#param_tokens)*) => {
-                        #proto_type::#var_ident(#(#field_set_tokens)*)
-                    },
-                }
-            } else {
-                quote! {
-                    #enum_name::#var_ident {#(#param_tokens)*} => {
-                        #proto_type::#var_ident{#(#field_set_tokens)*}
-                    },
+    let mut token_list = Vec::new();
+    for variant in variants.variants.iter() {
+        let var_ident = &variant.ident;
+        let proto_var_ident = variant_proto_ident(variant)?;
+        let fields = ProtoField::from_variant_fields(&variant.fields)?;
+        if fields.is_empty() {
+            token_list.push(quote! {
+                #enum_name::#var_ident => {
+                    #proto_type::#proto_var_ident
                 }
-            }
+            });
+            continue;
         }
-    }).collect();
-    if token_list.len() == 0 {
-        quote! {
-        {
-            match _value_ {
-                _ => {}
+
+        let bindings: Vec<TokenStream> = fields.iter().map(|field| field.binding_pattern()).collect();
+        let pattern = match &variant.fields {
+            Fields::Named(_) => quote! {#enum_name::#var_ident {#(#bindings,)*}},
+            _ => quote! {#enum_name::#var_ident(#(#bindings,)*)},
+        };
+
+        let value = match variant_nested_type(variant, &fields)? {
+            None => {
+                let binding = fields[0].binding_ident();
+                let value = fields[0].value_to_proto(quote! {#binding}, by_ref);
+                quote! {#proto_type::#proto_var_ident(#value)}
             }
-        }
+            Some(nested_type) => {
+                let values: Vec<TokenStream> = fields.iter().filter(|field| !field.attrs.skip).map(|field| {
+                    let proto_ident = field.proto_ident();
+                    let binding = field.binding_ident();
+                    let value = field.value_to_proto(quote! {#binding}, by_ref);
+                    quote! {#proto_ident: #value,}
+                }).collect();
+                quote! {#proto_type::#proto_var_ident(#nested_type{#(#values)* ..Default::default()})}
+            }
+        };
+
+        token_list.push(quote! {
+            #pattern => {
+                #value
+            },
+        });
     }
-    } else {
-        quote! {
+
+    if token_list.is_empty() {
+        return Err(syn::Error::new(
+            enum_name.span(),
+            format!("enum {} has no variant", enum_name),
+        ));
+    }
+
+    Ok(quote! {
         {
             match _value_ {
                 #(#token_list)*
             }
         }
-    }
-    }
+    })
 }
 
-fn proto_try_from_obj_ref_encode_struct(
+fn proto_try_from_obj_encode_struct(
     proto_type: &TokenStream,
-    _struct_ident: &Ident,
     data_st: &DataStruct,
-) -> TokenStream {
-    let token_list: Vec<TokenStream> = data_st
-        .fields
+    by_ref: bool,
+) -> Result<TokenStream> {
+    let fields = ProtoField::from_fields(&data_st.fields)?;
+    let token_list: Vec<TokenStream> = fields
         .iter()
-        .enumerate()
-        .map(|(index, field)| {
-            let ident: &Option<Ident> = &field.ident;
-            let ty = &field.ty;
-            let ty_str = ty.to_token_stream().to_string();
-            if ident.is_some() {
-                let ident = ident.as_ref().unwrap();
-                if is_vec_u8(ty_str.as_str()) {
-                    quote! {
-                    #ident: _value_.#ident.clone(),
-                }
-                } else {
-                    quote! {
-                    #ident: bucky_raw_codec::ProtobufTransform::transform(&_value_.#ident)?,
-                }
-                }
+        .filter(|field| !field.attrs.skip)
+        .map(|field| {
+            let proto_ident = field.proto_ident();
+            let obj_member = match &field.field.ident {
+                Some(ident) => ident.to_token_stream(),
+                None => Index::from(field.index).to_token_stream(),
+            };
+            let value = if by_ref {
+                field.value_to_proto(quote! {(&_value_.#obj_member)}, true)
             } else {
-                let ident = Ident::new(format!("field{}", index + 1).as_str(), Span::call_site());
-                let obj_field_ident = TokenStream::from_str(format!("{}", index).as_str()).unwrap();
-                if is_vec_u8(ty_str.as_str()) {
-                    quote! {
-                    #ident: _value_.#obj_field_ident.clone(),
-                }
-                } else {
-                    quote! {
-                    #ident: bucky_raw_codec::ProtobufTransform::transform(&_value_.#obj_field_ident)?,
-                }
-                }
+                field.value_to_proto(quote! {_value_.#obj_member}, false)
+            };
+            quote! {
+                #proto_ident: #value,
             }
         })
         .collect();
+    // rust-protobuf生成的结构还有special_fields等成员，其余成员使用默认值
+    Ok(quote! {
+        #proto_type{#(#token_list)* ..Default::default()}
+    })
+}
+
+// proto中的名字，去掉raw标识符的前缀
+fn proto_name(ident: &Ident) -> String {
+    let name = ident.to_string();
+    name.strip_prefix("r#").map(|v| v.to_owned()).unwrap_or(name)
+}
+
+// proto类型路径的最后一段作为message或者enum的名字
+fn proto_type_name(proto_type: &TokenStream) -> Result<String> {
+    let path: Path = syn::parse2(proto_type.clone())?;
+    Ok(proto_name(&path.segments.last().unwrap().ident))
+}
+
+fn schema_field_type(field: &ProtoField) -> TokenStream {
+    let ty = &field.field.ty;
+    if field.attrs.raw {
+        // raw字段对应bytes，Option/Vec对应optional/repeated bytes
+        let bytes = quote! {bucky_raw_codec::ProtobufFieldType::Scalar("bytes")};
+        let ty_str = ty.to_token_stream().to_string();
+        return if field.field.is_option() {
+            quote! {bucky_raw_codec::ProtobufFieldType::Optional(Box::new(#bytes))}
+        } else if Regex::new(r"^Vec[ ]*<").unwrap().is_match(ty_str.as_str()) {
+            quote! {bucky_raw_codec::ProtobufFieldType::Repeated(Box::new(#bytes))}
+        } else {
+            bytes
+        };
+    }
+
+    match &field.attrs.with {
+        Some(with) => quote! {#with::proto_field_type()},
+        None => quote! {<#ty as bucky_raw_codec::ProtobufSchema>::proto_field_type()},
+    }
+}
+
+fn schema_field_collect(field: &ProtoField) -> TokenStream {
+    let ty = &field.field.ty;
+    if field.attrs.raw {
+        return quote! {};
+    }
+
+    match &field.attrs.with {
+        Some(_) => quote! {},
+        None => quote! {<#ty as bucky_raw_codec::ProtobufSchema>::proto_collect(__builder__);},
+    }
+}
+
+// 把字段加入message定义，并收集字段类型依赖的定义
+fn schema_tag(tag: Option<u32>) -> TokenStream {
+    match tag {
+        Some(tag) => quote! {Some(#tag)},
+        None => quote! {None},
+    }
+}
+
+// owner为生成该定义的rust类型，不同类型使用同一个名字时build返回错误
+fn schema_message_definition(name: &str, owner: TokenStream, fields: &[ProtoField]) -> TokenStream {
+    let stmts: Vec<TokenStream> = fields.iter().filter(|field| !field.attrs.skip).map(|field| {
+        let field_name = proto_name(&field.proto_ident());
+        let tag = schema_tag(field.attrs.tag);
+        let field_type = schema_field_type(field);
+        let collect = schema_field_collect(field);
+        quote! {
+            __msg__.field(#field_name, #tag, #field_type)?;
+            #collect
+        }
+    }).collect();
+
     quote! {
-        #proto_type{#(#token_list)*}
+        __builder__.add_definition(#name, #owner, |__builder__| {
+            let mut __msg__ = bucky_raw_codec::ProtobufMessageSchema::new(#name);
+            #(#stmts)*
+            Ok(__msg__.build())
+        });
     }
 }
 
-fn proto_try_from_obj_ref_encode_union(_data_union: &DataUnion) -> TokenStream {
-    panic!("unsupport union");
+fn schema_enum_body(proto_type: &TokenStream, variants: &DataEnum) -> Result<(TokenStream, TokenStream)> {
+    let is_i32 = proto_type.to_string() == "i32";
+    if let Some(list) = value_enum_variants(variants)? {
+        if is_i32 {
+            return Ok((quote! {bucky_raw_codec::ProtobufFieldType::Scalar("int32")}, quote! {}));
+        }
+
+        // 枚举值在proto中与枚举同级，加上枚举名的前缀避免不同枚举的同名值冲突，prost生成代码时会去掉该前缀
+        let name = proto_type_name(proto_type)?;
+        let prefix = RenameRule::ScreamingSnakeCase.apply_to_variant(&name);
+        let values: Vec<TokenStream> = list.iter().filter(|v| v.value.is_some()).map(|v| {
+            let value_name = format!(
+                "{}_{}",
+                prefix,
+                RenameRule::ScreamingSnakeCase.apply_to_variant(&proto_name(&v.proto_ident))
+            );
+            let value = proc_macro2::Literal::i32_unsuffixed(v.value.unwrap());
+            quote! {(#value_name, #value)}
+        }).collect();
+        return Ok((
+            quote! {bucky_raw_codec::ProtobufFieldType::Named(#name.to_owned())},
+            quote! {__builder__.add_enum(#name, std::any::type_name::<Self>(), &[#(#values,)*]);},
+        ));
+    }
+
+    let mut members = Vec::new();
+    let mut collects = Vec::new();
+    for variant in variants.variants.iter() {
+        let fields = ProtoField::from_variant_fields(&variant.fields)?;
+        if fields.is_empty() {
+            continue;
+        }
+
+        let member_name = RenameRule::SnakeCase.apply_to_variant(&proto_name(&variant_proto_ident(variant)?));
+        let tag = schema_tag(ProtobufAttrs::from_variant(variant)?.tag);
+        match variant_nested_type(variant, &fields)? {
+            None => {
+                let field_type = schema_field_type(&fields[0]);
+                members.push(quote! {(#member_name.to_owned(), #tag, #field_type)});
+                collects.push(schema_field_collect(&fields[0]));
+            }
+            Some(nested_type) => {
+                let name = proto_type_name(&nested_type)?;
+                let variant_name = variant.ident.to_string();
+                let owner = quote! {&format!("{}::{}", std::any::type_name::<Self>(), #variant_name)};
+                members.push(quote! {(#member_name.to_owned(), #tag, bucky_raw_codec::ProtobufFieldType::Named(#name.to_owned()))});
+                collects.push(schema_message_definition(&name, owner, &fields));
+            }
+        }
+    }
+
+    Ok((
+        quote! {bucky_raw_codec::ProtobufFieldType::Oneof(vec![#(#members,)*])},
+        quote! {#(#collects)*},
+    ))
+}
+
+pub fn derive_protobuf_schema_fn_impl(input: syn::DeriveInput) -> Result<proc_macro::TokenStream> {
+    let ident = &input.ident;
+    let proto_type = get_proto_type(&input)?;
+    let (field_type, collect) = match &input.data {
+        Data::Struct(data_st) => {
+            let name = proto_type_name(&proto_type)?;
+            let fields = ProtoField::from_fields(&data_st.fields)?;
+            (
+                quote! {bucky_raw_codec::ProtobufFieldType::Named(#name.to_owned())},
+                schema_message_definition(&name, quote! {std::any::type_name::<Self>()}, &fields),
+            )
+        }
+        Data::Enum(variants) => schema_enum_body(&proto_type, variants)?,
+        Data::Union(_) => return Err(union_unsupported(&input)),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let impl_block = quote! {
+        #[automatically_derived]
+        impl #impl_generics bucky_raw_codec::ProtobufSchema for #ident #ty_generics #where_clause {
+            fn proto_field_type() -> bucky_raw_codec::ProtobufFieldType {
+                #field_type
+            }
+
+            fn proto_collect(__builder__: &mut bucky_raw_codec::ProtobufSchemaBuilder) {
+                #collect
+            }
+        }
+    };
+    Ok(impl_block.into())
 }
@@ -11,6 +11,13 @@ pub use protobuf::*;
 pub use raw::*;
 pub use error::*;
 
+// Prost实现库基于的prost版本(0.12)
+// prost-build生成的代码和#[derive(prost::Message)]引用的是::prost，使用的crate仍然需要直接依赖同一版本的prost
+#[cfg(feature = "prost")]
+pub use prost;
+#[cfg(feature = "prost")]
+pub use prost_types;
+
 #[macro_use]
 extern crate log;
 
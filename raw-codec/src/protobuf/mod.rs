@@ -1,7 +1,11 @@
+pub mod protobuf_backend;
 pub mod protobuf_helper;
+pub mod protobuf_schema;
 pub(crate) mod protos {
     include!(concat!(env!("OUT_DIR"), "/mod.rs"));
 }
 
+pub use protobuf_backend::*;
 pub use protobuf_helper::*;
+pub use protobuf_schema::*;
 pub use protos::EmptyContent;
@@ -0,0 +1,584 @@
+use crate::*;
+
+use std::collections::HashSet;
+
+// protobuf的底层实现库，helper和derive宏生成的代码都通过该trait读写protobuf消息
+// 不同的实现库使用不同的标记类型：RustProtobuf(rust-protobuf 2)，RustProtobuf3(rust-protobuf 3，开启protobuf3 feature)
+// 以及Prost(开启prost feature)，每个类型单独指定，不受开启了哪些feature的影响
+pub trait ProtobufBackend<M> {
+    fn encoded_len(msg: &M) -> CodecResult<usize>;
+
+    // 编码到buf的开头，返回剩余的buf
+    fn encode<'a>(msg: &M, buf: &'a mut [u8]) -> CodecResult<&'a mut [u8]>;
+
+    // 需要使用精确长度的buf来decode，整个buf都会被消费
+    fn decode(buf: &[u8]) -> CodecResult<M>;
+
+    // decode并且返回消息中未识别的字段，不支持的实现库返回空
+    fn decode_with_unknown(buf: &[u8]) -> CodecResult<(M, ProtobufUnknownFields)> {
+        Ok((Self::decode(buf)?, ProtobufUnknownFields::default()))
+    }
+
+    // 计算hash时使用的确定性编码，不包含未识别的字段
+    // 默认只按字段编号排序，实现库通过反射、描述符等提供消息的结构，递归整理嵌套message和map字段
+    fn encode_canonical(msg: &M) -> CodecResult<Vec<u8>> {
+        let mut buf = vec![0u8; Self::encoded_len(msg)?];
+        let remain = Self::encode(msg, &mut buf)?.len();
+        buf.truncate(buf.len() - remain);
+
+        ProtobufMessageCodecHelper::canonicalize(&buf, &ProtobufPlainSchema)
+    }
+}
+
+// 整理确定性编码时需要区分的字段类型
+#[derive(Clone, Copy)]
+pub(crate) enum ProtobufCanonicalField {
+    // 标量、字符串、bytes和枚举，原样输出
+    Plain,
+    // 嵌套message(包括oneof中的message)，递归整理
+    Message,
+    // map字段，条目整理后按编码排序，value_message表示value是否为message
+    Map { value_message: bool },
+}
+
+// 消息的结构信息，用来递归整理嵌套message和map字段
+pub(crate) trait ProtobufCanonicalSchema: Sized {
+    // 字段的类型，未识别的字段返回None，整理时会被丢弃
+    fn field(&self, number: u32) -> Option<ProtobufCanonicalField>;
+
+    // 字段中count个嵌套message(map字段对应条目的value)的结构，按出现的顺序返回
+    fn messages(&self, number: u32, count: usize) -> CodecResult<Vec<Self>>;
+}
+
+// 没有结构信息时所有字段都当作普通字段
+pub(crate) struct ProtobufPlainSchema;
+
+impl ProtobufCanonicalSchema for ProtobufPlainSchema {
+    fn field(&self, _number: u32) -> Option<ProtobufCanonicalField> {
+        Some(ProtobufCanonicalField::Plain)
+    }
+
+    fn messages(&self, _number: u32, _count: usize) -> CodecResult<Vec<Self>> {
+        Ok(Vec::new())
+    }
+}
+
+// 解码时未识别的字段，按原始顺序保存wire格式的数据，编码时原样追加在消息的后面
+// 新版本增加的字段一般使用更大的编号，追加在后面可以保证经过旧版本转发的对象编码不变
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProtobufUnknownFields(Vec<u8>);
+
+impl ProtobufUnknownFields {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.0
+    }
+
+    // 编码到buf的开头，返回剩余的buf
+    pub fn encode<'a>(&self, buf: &'a mut [u8]) -> CodecResult<&'a mut [u8]> {
+        check_encode_buf(self.0.len(), buf.len())?;
+        buf[..self.0.len()].copy_from_slice(&self.0);
+        Ok(&mut buf[self.0.len()..])
+    }
+
+    // 遍历buf中的所有字段，返回(字段编号, 字段的原始数据)
+    pub(crate) fn fields(buf: &[u8]) -> CodecResult<Vec<(u32, &[u8])>> {
+        let mut list = Vec::new();
+        let mut stream = ::protobuf::CodedInputStream::from_bytes(buf);
+        let map_err = |e: ::protobuf::ProtobufError| {
+            let msg = format!("read protobuf field error! {}", e);
+            error!("{}", msg);
+
+            CodecError::new(CodecErrorCode::InvalidFormat, msg)
+        };
+
+        while !stream.eof().map_err(map_err)? {
+            let begin = stream.pos() as usize;
+            let (number, wire_type) = stream.read_tag_unpack().map_err(map_err)?;
+            stream.skip_field(wire_type).map_err(map_err)?;
+            list.push((number, &buf[begin..stream.pos() as usize]));
+        }
+
+        Ok(list)
+    }
+
+    fn collect(buf: &[u8], is_unknown: impl Fn(u32) -> bool) -> CodecResult<Self> {
+        let mut ret = Vec::new();
+        for (number, data) in Self::fields(buf)? {
+            if is_unknown(number) {
+                ret.extend_from_slice(data);
+            }
+        }
+
+        Ok(Self(ret))
+    }
+}
+
+fn check_encode_buf(required: usize, remaining: usize) -> CodecResult<()> {
+    if required > remaining {
+        let msg = format!(
+            "encode protobuf message but buf not enough! required={}, remaining={}",
+            required, remaining
+        );
+        error!("{}", msg);
+
+        return Err(CodecError::new(CodecErrorCode::OutOfLimit, msg));
+    }
+
+    Ok(())
+}
+
+pub struct RustProtobuf;
+
+impl<M: ::protobuf::Message> ProtobufBackend<M> for RustProtobuf {
+    fn encoded_len(msg: &M) -> CodecResult<usize> {
+        Ok(msg.compute_size() as usize)
+    }
+
+    fn encode<'a>(msg: &M, buf: &'a mut [u8]) -> CodecResult<&'a mut [u8]> {
+        let size = msg.compute_size() as usize;
+        check_encode_buf(size, buf.len())?;
+
+        let mut stream = ::protobuf::CodedOutputStream::bytes(&mut buf[..size]);
+        msg.write_to(&mut stream).map_err(|e| {
+            let msg = format!("encode protobuf::Message to stream error! {}", e);
+            error!("{}", msg);
+
+            CodecError::new(CodecErrorCode::OutOfLimit, msg)
+        })?;
+
+        Ok(&mut buf[size..])
+    }
+
+    fn decode(buf: &[u8]) -> CodecResult<M> {
+        let mut stream = ::protobuf::CodedInputStream::from_bytes(buf);
+        let value = M::parse_from(&mut stream).map_err(|e| {
+            let msg = format!("decode protobuf::Message from stream error! {}", e);
+            error!("{}", msg);
+
+            CodecError::new(CodecErrorCode::InvalidFormat, msg)
+        })?;
+
+        if stream.pos() as usize != buf.len() {
+            let msg = format!(
+                "decode protobuf::Message but not consume all buf! pos={}, len={}",
+                stream.pos(),
+                buf.len()
+            );
+            error!("{}", msg);
+
+            return Err(CodecError::new(CodecErrorCode::InvalidFormat, msg));
+        }
+
+        Ok(value)
+    }
+
+    // rust-protobuf会把未识别的字段保存在消息里，但是按字段编号无序存放，这里从原始数据中按顺序提取
+    fn decode_with_unknown(buf: &[u8]) -> CodecResult<(M, ProtobufUnknownFields)> {
+        let value: M = Self::decode(buf)?;
+        if value.get_unknown_fields().iter().next().is_none() {
+            return Ok((value, ProtobufUnknownFields::default()));
+        }
+
+        let numbers: HashSet<u32> = value.get_unknown_fields().iter().map(|(number, _)| number).collect();
+        let unknown = ProtobufUnknownFields::collect(buf, |number| numbers.contains(&number))?;
+        Ok((value, unknown))
+    }
+
+    fn encode_canonical(msg: &M) -> CodecResult<Vec<u8>> {
+        let data = ProtobufMessageCodecHelper::encode_to_vec_with::<Self, M>(msg)?;
+        ProtobufMessageCodecHelper::canonicalize(&data, &RustProtobufSchema(msg))
+    }
+}
+
+// rust-protobuf 2的字段描述符无法得到嵌套message的描述符，所以跟着消息的值一起遍历
+// repeated字段按顺序编码，map字段按HashMap的遍历顺序编码，和反射遍历的顺序一致
+struct RustProtobufSchema<'a>(&'a dyn ::protobuf::Message);
+
+impl<'a> ProtobufCanonicalSchema for RustProtobufSchema<'a> {
+    fn field(&self, number: u32) -> Option<ProtobufCanonicalField> {
+        use ::protobuf::reflect::{ReflectFieldRef, ReflectValueRef};
+
+        let msg: &'a dyn ::protobuf::Message = self.0;
+        let field = msg.descriptor().get_field_by_number(number)?;
+        let ret = match field.get_reflect(msg) {
+            ReflectFieldRef::Optional(Some(ReflectValueRef::Message(_))) => ProtobufCanonicalField::Message,
+            ReflectFieldRef::Repeated(list)
+                if list.len() > 0 && matches!(list.get(0).as_ref(), ReflectValueRef::Message(_)) =>
+            {
+                ProtobufCanonicalField::Message
+            }
+            ReflectFieldRef::Map(map) => ProtobufCanonicalField::Map {
+                value_message: map
+                    .reflect_iter()
+                    .next()
+                    .map_or(false, |(_, value)| matches!(value.as_ref(), ReflectValueRef::Message(_))),
+            },
+            _ => ProtobufCanonicalField::Plain,
+        };
+
+        Some(ret)
+    }
+
+    fn messages(&self, number: u32, count: usize) -> CodecResult<Vec<Self>> {
+        use ::protobuf::reflect::{ReflectFieldRef, ReflectValueRef};
+
+        let msg: &'a dyn ::protobuf::Message = self.0;
+        let mut list = Vec::with_capacity(count);
+        if let Some(field) = msg.descriptor().get_field_by_number(number) {
+            let values: Vec<ReflectValueRef<'a>> = match field.get_reflect(msg) {
+                ReflectFieldRef::Optional(value) => value.into_iter().collect(),
+                ReflectFieldRef::Repeated(repeated) => (0..repeated.len()).map(|i| repeated.get(i).as_ref()).collect(),
+                ReflectFieldRef::Map(map) => map.reflect_iter().map(|(_, value)| value.as_ref()).collect(),
+            };
+            for value in values {
+                if let ReflectValueRef::Message(value) = value {
+                    list.push(Self(value));
+                }
+            }
+        }
+
+        if list.len() != count {
+            let msg = format!(
+                "protobuf message fields not match the value! number={}, fields={}, values={}",
+                number,
+                count,
+                list.len()
+            );
+            error!("{}", msg);
+
+            return Err(CodecError::new(CodecErrorCode::InvalidFormat, msg));
+        }
+
+        Ok(list)
+    }
+}
+
+#[cfg(feature = "protobuf3")]
+pub struct RustProtobuf3;
+
+#[cfg(feature = "protobuf3")]
+impl<M: ::protobuf3::MessageFull> ProtobufBackend<M> for RustProtobuf3 {
+    fn encoded_len(msg: &M) -> CodecResult<usize> {
+        Ok(msg.compute_size() as usize)
+    }
+
+    fn encode<'a>(msg: &M, buf: &'a mut [u8]) -> CodecResult<&'a mut [u8]> {
+        let size = msg.compute_size() as usize;
+        check_encode_buf(size, buf.len())?;
+
+        let mut stream = ::protobuf3::CodedOutputStream::bytes(&mut buf[..size]);
+        msg.write_to_with_cached_sizes(&mut stream)
+            .and_then(|_| stream.flush())
+            .map_err(|e| {
+                let msg = format!("encode protobuf3::Message to stream error! {}", e);
+                error!("{}", msg);
+
+                CodecError::new(CodecErrorCode::OutOfLimit, msg)
+            })?;
+        drop(stream);
+
+        Ok(&mut buf[size..])
+    }
+
+    // parse_from_bytes会检查是否消费了整个buf
+    fn decode(buf: &[u8]) -> CodecResult<M> {
+        M::parse_from_bytes(buf).map_err(|e| {
+            let msg = format!("decode protobuf3::Message error! {}", e);
+            error!("{}", msg);
+
+            CodecError::new(CodecErrorCode::InvalidFormat, msg)
+        })
+    }
+
+    fn decode_with_unknown(buf: &[u8]) -> CodecResult<(M, ProtobufUnknownFields)> {
+        let value: M = Self::decode(buf)?;
+        if value.unknown_fields().iter().next().is_none() {
+            return Ok((value, ProtobufUnknownFields::default()));
+        }
+
+        let numbers: HashSet<u32> = value.unknown_fields().iter().map(|(number, _)| number).collect();
+        let unknown = ProtobufUnknownFields::collect(buf, |number| numbers.contains(&number))?;
+        Ok((value, unknown))
+    }
+
+    fn encode_canonical(msg: &M) -> CodecResult<Vec<u8>> {
+        let data = ProtobufMessageCodecHelper::encode_to_vec_with::<Self, M>(msg)?;
+        ProtobufMessageCodecHelper::canonicalize(&data, &M::descriptor())
+    }
+}
+
+// rust-protobuf 3可以从字段描述符直接得到嵌套message的描述符
+#[cfg(feature = "protobuf3")]
+impl ProtobufCanonicalSchema for ::protobuf3::reflect::MessageDescriptor {
+    fn field(&self, number: u32) -> Option<ProtobufCanonicalField> {
+        use ::protobuf3::reflect::{RuntimeFieldType, RuntimeType};
+
+        let ret = match self.field_by_number(number)?.runtime_field_type() {
+            RuntimeFieldType::Singular(RuntimeType::Message(_))
+            | RuntimeFieldType::Repeated(RuntimeType::Message(_)) => ProtobufCanonicalField::Message,
+            RuntimeFieldType::Map(_, value) => ProtobufCanonicalField::Map {
+                value_message: matches!(value, RuntimeType::Message(_)),
+            },
+            _ => ProtobufCanonicalField::Plain,
+        };
+
+        Some(ret)
+    }
+
+    fn messages(&self, number: u32, count: usize) -> CodecResult<Vec<Self>> {
+        use ::protobuf3::reflect::{RuntimeFieldType, RuntimeType};
+
+        match self.field_by_number(number).map(|field| field.runtime_field_type()) {
+            Some(RuntimeFieldType::Singular(RuntimeType::Message(desc)))
+            | Some(RuntimeFieldType::Repeated(RuntimeType::Message(desc)))
+            | Some(RuntimeFieldType::Map(_, RuntimeType::Message(desc))) => Ok(vec![desc; count]),
+            _ => {
+                let msg = format!("protobuf field is not message! message={}, number={}", self.full_name(), number);
+                error!("{}", msg);
+
+                Err(CodecError::new(CodecErrorCode::InvalidFormat, msg))
+            }
+        }
+    }
+}
+
+// 探测的结果，key为(path, 字段编号)，同一次编码中嵌套的schema共享
+#[cfg(feature = "prost")]
+type ProstFieldCache = std::rc::Rc<std::cell::RefCell<std::collections::HashMap<(Vec<u32>, u32), Option<ProtobufCanonicalField>>>>;
+
+// prost没有反射，通过解码构造的数据来探测字段的类型，path是从M到当前嵌套message经过的字段编号
+// 每次探测都要解码整个M，同一个字段只探测一次，结果保存在cache中
+#[cfg(feature = "prost")]
+struct ProstSchema<M> {
+    path: Vec<u32>,
+    cache: ProstFieldCache,
+    _phantom: std::marker::PhantomData<fn() -> M>,
+}
+
+// 手动实现，避免derive要求M: Clone
+#[cfg(feature = "prost")]
+impl<M> Clone for ProstSchema<M> {
+    fn clone(&self) -> Self {
+        Self {
+            path: self.path.clone(),
+            cache: self.cache.clone(),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+}
+
+#[cfg(feature = "prost")]
+impl<M: ::prost::Message + Default> ProstSchema<M> {
+    fn new() -> Self {
+        Self {
+            path: Vec::new(),
+            cache: ProstFieldCache::default(),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    fn nested(&self, number: u32) -> Self {
+        let mut path = self.path.clone();
+        path.push(number);
+
+        Self {
+            path,
+            cache: self.cache.clone(),
+            _phantom: std::marker::PhantomData,
+        }
+    }
+
+    // 把当前嵌套message中的字段数据按path逐层包装，然后作为M解码
+    fn probe(&self, data: Vec<u8>) -> Option<M> {
+        use ::prost::encoding::{encode_key, encode_varint, WireType};
+
+        let mut data = data;
+        for number in self.path.iter().rev() {
+            let mut buf = Vec::new();
+            encode_key(*number, WireType::LengthDelimited, &mut buf);
+            encode_varint(data.len() as u64, &mut buf);
+            buf.extend_from_slice(&data);
+            data = buf;
+        }
+
+        M::decode(data.as_slice()).ok()
+    }
+
+    fn accept(&self, number: u32, wire_type: ::prost::encoding::WireType, value: &[u8]) -> bool {
+        let mut data = Vec::new();
+        ::prost::encoding::encode_key(number, wire_type, &mut data);
+        if wire_type == ::prost::encoding::WireType::LengthDelimited {
+            ::prost::encoding::encode_varint(value.len() as u64, &mut data);
+        }
+        data.extend_from_slice(value);
+
+        self.probe(data).is_some()
+    }
+
+    // prost生成的代码解码已知字段时会检查wire类型，任何字段类型都至少会拒绝varint和fixed32中的一种
+    // (packed的repeated字段还接受length delimited)，而未知字段使用任何wire类型都会被跳过
+    // 所以只有两种wire类型都能解码成功的编号才是未知字段，与字段的值是否为默认值无关
+    // map字段解码时不检查wire类型，会把varint当作条目的长度，所以varint使用1，长度为1的条目缺少数据无法解码
+    fn is_unknown(&self, number: u32) -> bool {
+        use ::prost::encoding::WireType;
+
+        self.accept(number, WireType::Varint, &[1]) && self.accept(number, WireType::ThirtyTwoBit, &[0; 4])
+    }
+
+    // 解码出现了若干次number字段的数据，重新编码后number字段出现的次数
+    fn count(&self, number: u32, values: &[&[u8]]) -> usize {
+        use ::prost::encoding::{encode_key, encode_varint, WireType};
+
+        let mut data = Vec::new();
+        for value in values {
+            encode_key(number, WireType::LengthDelimited, &mut data);
+            encode_varint(value.len() as u64, &mut data);
+            data.extend_from_slice(value);
+        }
+        let msg = match self.probe(data) {
+            Some(msg) => msg,
+            None => return 0,
+        };
+
+        let mut buf = msg.encode_to_vec();
+        for number in self.path.iter() {
+            let field = ProtobufUnknownFields::fields(&buf)
+                .ok()
+                .and_then(|fields| fields.into_iter().find(|(n, _)| n == number))
+                .and_then(|(_, data)| ProtobufMessageCodecHelper::field_payload(data).ok());
+            match field {
+                Some(field) => buf = field.to_vec(),
+                None => return 0,
+            }
+        }
+
+        ProtobufUnknownFields::fields(&buf)
+            .map(|fields| fields.iter().filter(|(n, _)| *n == number).count())
+            .unwrap_or(0)
+    }
+
+    // map和message都只接受length delimited，并且不是任意数据都能解码
+    // 两个空条目：repeated message保留两个，单个message合并为一个，map的key相同也合并为一个
+    // 再加上一个不同key的条目：map保留两个，单个message仍然合并为一个，key依次尝试各种wire类型
+    fn is_map(&self, number: u32) -> bool {
+        if self.count(number, &[&[], &[]]) != 1 {
+            return false;
+        }
+
+        let keys: [&[u8]; 4] = [
+            &[0x08, 0x01],
+            &[0x0d, 0x01, 0x00, 0x00, 0x00],
+            &[0x09, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            &[0x0a, 0x01, 0x61],
+        ];
+        keys.iter().any(|key| self.count(number, &[&[], key]) == 2)
+    }
+
+    fn classify(&self, number: u32) -> Option<ProtobufCanonicalField> {
+        use ::prost::encoding::WireType;
+
+        if self.is_unknown(number) {
+            return None;
+        }
+
+        // 截断的数据对string和bytes是合法的值，对message和map则无法解码
+        if self.accept(number, WireType::Varint, &[1])
+            || self.accept(number, WireType::ThirtyTwoBit, &[0; 4])
+            || self.accept(number, WireType::SixtyFourBit, &[0; 8])
+            || self.accept(number, WireType::LengthDelimited, &[0x08])
+        {
+            return Some(ProtobufCanonicalField::Plain);
+        }
+
+        if self.is_map(number) {
+            let value_message = matches!(self.nested(number).field(2), Some(ProtobufCanonicalField::Message));
+            return Some(ProtobufCanonicalField::Map { value_message });
+        }
+
+        Some(ProtobufCanonicalField::Message)
+    }
+}
+
+#[cfg(feature = "prost")]
+impl<M: ::prost::Message + Default> ProtobufCanonicalSchema for ProstSchema<M> {
+    fn field(&self, number: u32) -> Option<ProtobufCanonicalField> {
+        let key = (self.path.clone(), number);
+        if let Some(field) = self.cache.borrow().get(&key) {
+            return *field;
+        }
+
+        let field = self.classify(number);
+        self.cache.borrow_mut().insert(key, field);
+        field
+    }
+
+    fn messages(&self, number: u32, count: usize) -> CodecResult<Vec<Self>> {
+        // map条目中的value是条目的2号字段
+        let schema = match self.field(number) {
+            Some(ProtobufCanonicalField::Map { .. }) => self.nested(number).nested(2),
+            _ => self.nested(number),
+        };
+
+        Ok(vec![schema; count])
+    }
+}
+
+#[cfg(feature = "prost")]
+pub struct Prost;
+
+#[cfg(feature = "prost")]
+impl<M: ::prost::Message + Default> ProtobufBackend<M> for Prost {
+    fn encoded_len(msg: &M) -> CodecResult<usize> {
+        Ok(msg.encoded_len())
+    }
+
+    fn encode<'a>(msg: &M, buf: &'a mut [u8]) -> CodecResult<&'a mut [u8]> {
+        check_encode_buf(msg.encoded_len(), buf.len())?;
+
+        // &mut [u8]实现了BufMut，写入后会自动前移
+        let mut buf = buf;
+        msg.encode_raw(&mut buf);
+        Ok(buf)
+    }
+
+    fn decode(buf: &[u8]) -> CodecResult<M> {
+        M::decode(buf).map_err(|e| {
+            let msg = format!("decode prost::Message error! {}", e);
+            error!("{}", msg);
+
+            CodecError::new(CodecErrorCode::InvalidFormat, msg)
+        })
+    }
+
+    // prost会丢弃未识别的字段，也不提供字段编号，对buf中出现的每个编号单独探测是否为已知字段
+    fn decode_with_unknown(buf: &[u8]) -> CodecResult<(M, ProtobufUnknownFields)> {
+        let value: M = Self::decode(buf)?;
+        let schema = ProstSchema::<M>::new();
+        let numbers: HashSet<u32> = ProtobufUnknownFields::fields(buf)?
+            .into_iter()
+            .map(|(number, _)| number)
+            .collect::<HashSet<u32>>()
+            .into_iter()
+            .filter(|number| schema.is_unknown(*number))
+            .collect();
+        if numbers.is_empty() {
+            return Ok((value, ProtobufUnknownFields::default()));
+        }
+
+        let unknown = ProtobufUnknownFields::collect(buf, |number| numbers.contains(&number))?;
+        Ok((value, unknown))
+    }
+
+    fn encode_canonical(msg: &M) -> CodecResult<Vec<u8>> {
+        let data = ProtobufMessageCodecHelper::encode_to_vec_with::<Self, M>(msg)?;
+        ProtobufMessageCodecHelper::canonicalize(&data, &ProstSchema::<M>::new())
+    }
+}
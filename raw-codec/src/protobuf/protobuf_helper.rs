@@ -1,7 +1,11 @@
 use crate::*;
 
 use std::{convert::TryFrom, str::FromStr};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::Hash;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::time::{Duration, SystemTime};
+use primitive_types::H256;
 
 // 为了读取CodedOutputStream里面的position字段
 // TODO 确保protobuf的版本，如果此结构体发生变化，需要同步，否则会导致读取错误
@@ -24,68 +28,222 @@ mod stream_pos_retreve_helper {
 pub struct ProtobufMessageCodecHelper {}
 
 impl ProtobufMessageCodecHelper {
-    pub fn raw_measure(
-        value: impl ::protobuf::Message,
-        _purpose: &Option<RawEncodePurpose>,
-    ) -> CodecResult<usize> {
-        let size = value.compute_size() as usize;
+    // 使用指定的protobuf实现库编解码，计算hash时使用确定性的编码
+    pub fn raw_measure<B, M>(value: &M, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize>
+    where
+        B: ProtobufBackend<M>,
+    {
+        if Self::is_hash_purpose(purpose) {
+            return Ok(B::encode_canonical(value)?.len());
+        }
 
-        Ok(size)
+        B::encoded_len(value)
     }
 
-    pub fn raw_encode<'a>(
-        value: impl ::protobuf::Message,
+    pub fn raw_encode<'a, B, M>(
+        value: &M,
         buf: &'a mut [u8],
-        _purpose: &Option<RawEncodePurpose>,
-    ) -> CodecResult<&'a mut [u8]> {
-        let size = value.compute_size() as usize;
-        let mut stream = ::protobuf::CodedOutputStream::bytes(buf);
-        value.write_to(&mut stream).map_err(|e| {
-            let msg = format!("encode protobuf::Message to stream error! {}", e);
-            error!("{}", msg);
-
-            CodecError::new(CodecErrorCode::OutOfLimit, msg)
-        })?;
-
-        //let stream_exposed: stream_pos_retreve_helper::CodedOutputStream =
-        //    unsafe { std::mem::transmute(stream) };
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]>
+    where
+        B: ProtobufBackend<M>,
+    {
+        if Self::is_hash_purpose(purpose) {
+            let data = B::encode_canonical(value)?;
+            return Self::copy_to_buf(&data, buf);
+        }
 
-        Ok(&mut buf[size..])
+        B::encode(value, buf)
     }
 
     // 需要使用精确长度的buf来decode
-    pub fn raw_decode<'de, T>(buf: &'de [u8]) -> CodecResult<(T, &'de [u8])>
+    pub fn raw_decode<'de, B, M>(buf: &'de [u8]) -> CodecResult<(M, &'de [u8])>
     where
-        T: ::protobuf::Message,
+        B: ProtobufBackend<M>,
     {
         // buffer的size就是整个body_content的长度
         let size = buf.len();
+        let value = B::decode(buf)?;
+
+        Ok((value, &buf[size..]))
+    }
+
+    // 计算hash时的编码，保留的未识别字段原样按编号合并到确定性的编码中
+    // 新字段本身是确定性编码时，使用旧版本schema的节点与使用新版本的节点得到相同的结果
+    pub fn encode_canonical_with_unknown<B, M>(value: &M, unknown: &ProtobufUnknownFields) -> CodecResult<Vec<u8>>
+    where
+        B: ProtobufBackend<M>,
+    {
+        let data = B::encode_canonical(value)?;
+        if unknown.is_empty() {
+            return Ok(data);
+        }
+
+        // 未识别字段的编号不会与schema中的字段相同，稳定排序保持同一编号的字段原有的顺序
+        let mut fields = ProtobufUnknownFields::fields(&data)?;
+        fields.extend(ProtobufUnknownFields::fields(unknown.as_slice())?);
+        fields.sort_by_key(|(number, _)| *number);
+
+        let mut ret = Vec::with_capacity(data.len() + unknown.len());
+        for (_, field) in fields {
+            ret.extend_from_slice(field);
+        }
+        Ok(ret)
+    }
+
+    pub fn is_hash_purpose(purpose: &Option<RawEncodePurpose>) -> bool {
+        purpose == &Some(RawEncodePurpose::Hash)
+    }
+
+    pub fn encode_to_vec_with<B, M>(value: &M) -> CodecResult<Vec<u8>>
+    where
+        B: ProtobufBackend<M>,
+    {
+        let mut buf = vec![0u8; B::encoded_len(value)?];
+        let remain = B::encode(value, &mut buf)?.len();
+        if remain != 0 {
+            let msg = format!(
+                "encode protobuf message but size not match the encoded len! len={}, remaining={}",
+                buf.len(),
+                remain
+            );
+            error!("{}", msg);
+
+            return Err(CodecError::new(CodecErrorCode::InvalidData, msg));
+        }
+
+        Ok(buf)
+    }
 
-        // 必须截取精确大小的buffer
-        let mut stream = ::protobuf::CodedInputStream::from_bytes(buf);
-        let value = T::parse_from(&mut stream).map_err(|e| {
-            let msg = format!("decode protobuf::Message from stream error! {}", e);
+    pub fn copy_to_buf<'a>(data: &[u8], buf: &'a mut [u8]) -> CodecResult<&'a mut [u8]> {
+        if data.len() > buf.len() {
+            let msg = format!(
+                "encode protobuf message but buf not enough! required={}, remaining={}",
+                data.len(),
+                buf.len()
+            );
+            error!("{}", msg);
+
+            return Err(CodecError::new(CodecErrorCode::OutOfLimit, msg));
+        }
+
+        buf[..data.len()].copy_from_slice(data);
+        Ok(&mut buf[data.len()..])
+    }
+
+    // 把编码整理为确定性的编码，用于计算hash
+    // 字段按编号排序(同一编号的repeated字段保持原有顺序)，丢弃schema中没有的字段
+    // 嵌套message递归整理，map字段的条目整理后按编码排序
+    pub(crate) fn canonicalize<S: ProtobufCanonicalSchema>(buf: &[u8], schema: &S) -> CodecResult<Vec<u8>> {
+        let mut fields = ProtobufUnknownFields::fields(buf)?;
+        fields.sort_by_key(|(number, _)| *number);
+
+        let mut ret = Vec::with_capacity(buf.len());
+        let mut begin = 0;
+        while begin < fields.len() {
+            let number = fields[begin].0;
+            let end = begin + fields[begin..].iter().take_while(|(n, _)| *n == number).count();
+            let group = &fields[begin..end];
+            begin = end;
+
+            match schema.field(number) {
+                None => {}
+                Some(ProtobufCanonicalField::Plain) => {
+                    for (_, data) in group {
+                        ret.extend_from_slice(data);
+                    }
+                }
+                Some(ProtobufCanonicalField::Message) => {
+                    for ((_, data), nested) in group.iter().zip(schema.messages(number, group.len())?) {
+                        let body = Self::canonicalize(Self::field_payload(data)?, &nested)?;
+                        Self::write_len_field(number, &body, &mut ret)?;
+                    }
+                }
+                Some(ProtobufCanonicalField::Map { value_message }) => {
+                    let values: Vec<Option<S>> = if value_message {
+                        schema.messages(number, group.len())?.into_iter().map(Some).collect()
+                    } else {
+                        group.iter().map(|_| None).collect()
+                    };
+
+                    let mut entries = Vec::with_capacity(group.len());
+                    for ((_, data), value) in group.iter().zip(values) {
+                        entries.push(Self::canonicalize_map_entry(Self::field_payload(data)?, value.as_ref())?);
+                    }
+                    entries.sort();
+                    for entry in entries {
+                        Self::write_len_field(number, &entry, &mut ret)?;
+                    }
+                }
+            }
+        }
+
+        Ok(ret)
+    }
+
+    // map条目只有key(编号1)和value(编号2)两个字段
+    fn canonicalize_map_entry<S: ProtobufCanonicalSchema>(buf: &[u8], value: Option<&S>) -> CodecResult<Vec<u8>> {
+        let mut fields = ProtobufUnknownFields::fields(buf)?;
+        fields.sort_by_key(|(number, _)| *number);
+
+        let mut ret = Vec::with_capacity(buf.len());
+        for (number, data) in fields {
+            match (number, value) {
+                (1, _) | (2, None) => ret.extend_from_slice(data),
+                (2, Some(value)) => {
+                    let body = Self::canonicalize(Self::field_payload(data)?, value)?;
+                    Self::write_len_field(2, &body, &mut ret)?;
+                }
+                _ => {}
+            }
+        }
+
+        Ok(ret)
+    }
+
+    // length delimited字段的内容，data是包含tag的完整字段
+    pub(crate) fn field_payload(data: &[u8]) -> CodecResult<&[u8]> {
+        let mut stream = ::protobuf::CodedInputStream::from_bytes(data);
+        let map_err = |e: ::protobuf::ProtobufError| {
+            let msg = format!("read protobuf field error! {}", e);
             error!("{}", msg);
 
             CodecError::new(CodecErrorCode::InvalidFormat, msg)
-        })?;
+        };
 
-        assert_eq!(stream.pos() as usize, size);
+        let (number, wire_type) = stream.read_tag_unpack().map_err(map_err)?;
+        if wire_type != ::protobuf::wire_format::WireTypeLengthDelimited {
+            let msg = format!("protobuf field is not length delimited! number={}, wire_type={:?}", number, wire_type);
+            error!("{}", msg);
 
-        Ok((value, &buf[size..]))
+            return Err(CodecError::new(CodecErrorCode::InvalidFormat, msg));
+        }
+        stream.read_raw_varint64().map_err(map_err)?;
+
+        Ok(&data[stream.pos() as usize..])
+    }
+
+    fn write_len_field(number: u32, body: &[u8], buf: &mut Vec<u8>) -> CodecResult<()> {
+        let mut stream = ::protobuf::CodedOutputStream::vec(buf);
+        stream.write_bytes(number, body).and_then(|_| stream.flush()).map_err(|e| {
+            let msg = format!("write protobuf field error! number={}, {}", number, e);
+            error!("{}", msg);
+
+            CodecError::new(CodecErrorCode::InvalidData, msg)
+        })
     }
 }
 
 pub struct ProtobufCodecHelper {}
 
 impl ProtobufCodecHelper {
-    pub fn raw_measure<'a, T, P>(
+    pub fn raw_measure<'a, B, T, P>(
         value: &'a T,
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<usize>
     where
+        B: ProtobufBackend<P>,
         P: TryFrom<&'a T>,
-        P: ::protobuf::Message,
         <P as TryFrom<&'a T>>::Error: std::fmt::Display,
     {
         let value: P = P::try_from(value).map_err(|e: <P as TryFrom<&'a T>>::Error| {
@@ -95,17 +253,17 @@ impl ProtobufCodecHelper {
             CodecError::new(CodecErrorCode::InvalidFormat, msg)
         })?;
 
-        ProtobufMessageCodecHelper::raw_measure(value, purpose)
+        ProtobufMessageCodecHelper::raw_measure::<B, P>(&value, purpose)
     }
 
-    pub fn raw_encode<'a, 'b, T, P>(
+    pub fn raw_encode<'a, 'b, B, T, P>(
         value: &'b T,
         buf: &'a mut [u8],
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]>
     where
+        B: ProtobufBackend<P>,
         P: TryFrom<&'b T>,
-        P: ::protobuf::Message,
         <P as TryFrom<&'b T>>::Error: std::fmt::Display,
     {
         let value: P = P::try_from(value).map_err(|e: <P as TryFrom<&'b T>>::Error| {
@@ -115,16 +273,16 @@ impl ProtobufCodecHelper {
             CodecError::new(CodecErrorCode::InvalidFormat, msg)
         })?;
 
-        ProtobufMessageCodecHelper::raw_encode(value, buf, purpose)
+        ProtobufMessageCodecHelper::raw_encode::<B, P>(&value, buf, purpose)
     }
 
-    pub fn raw_decode<'de, T, P>(buf: &'de [u8]) -> CodecResult<(T, &'de [u8])>
+    pub fn raw_decode<'de, B, T, P>(buf: &'de [u8]) -> CodecResult<(T, &'de [u8])>
     where
+        B: ProtobufBackend<P>,
         T: TryFrom<P>,
-        P: ::protobuf::Message,
         <T as TryFrom<P>>::Error: std::fmt::Display,
     {
-        let (msg, buf) = ProtobufMessageCodecHelper::raw_decode::<P>(buf)?;
+        let (msg, buf) = ProtobufMessageCodecHelper::raw_decode::<B, P>(buf)?;
         let value: T = T::try_from(msg).map_err(|e: <T as TryFrom<P>>::Error| {
             let msg = format!("convert protobuf message to type error! {}", e);
             error!("{}", msg);
@@ -160,9 +318,11 @@ impl ProtobufCodecHelper {
         Ok(result)
     }
 
-    pub fn encode_string_list<T>(list: &[T]) -> CodecResult<::protobuf::RepeatedField<String>>
+    // 返回值可以是Vec或者rust-protobuf 2的RepeatedField
+    pub fn encode_string_list<T, R>(list: &[T]) -> CodecResult<R>
     where
         T: ToString,
+        R: From<Vec<String>>,
     {
         let mut result = Vec::with_capacity(list.len());
         for item in list.iter() {
@@ -189,9 +349,10 @@ impl ProtobufCodecHelper {
         Ok(result)
     }
 
-    pub fn encode_buf_list<T>(list: &[T]) -> CodecResult<::protobuf::RepeatedField<Vec<u8>>>
+    pub fn encode_buf_list<T, R>(list: &[T]) -> CodecResult<R>
     where
         T: RawEncode,
+        R: From<Vec<Vec<u8>>>,
     {
         let mut result = Vec::with_capacity(list.len());
         for item in list.iter() {
@@ -245,11 +406,10 @@ impl ProtobufCodecHelper {
         Ok(result)
     }
 
-    pub fn encode_nested_list<'a, T, P>(
-        list: &'a Vec<T>,
-    ) -> CodecResult<::protobuf::RepeatedField<P>>
+    pub fn encode_nested_list<'a, T, P, R>(list: &'a Vec<T>) -> CodecResult<R>
     where
         T: 'a,
+        R: From<Vec<P>>,
         P: TryFrom<&'a T>,
         <P as TryFrom<&'a T>>::Error: std::fmt::Display,
         CodecError: From<<P as TryFrom<&'a T>>::Error>,
@@ -382,6 +542,44 @@ impl<'a, T: 'a, U: ProtobufTransform<&'a T>> ProtobufTransform<&'a Option<T>> fo
     }
 }
 
+// rust-protobuf生成的message字段为SingularPtrField(2.x)或者MessageField(3.x)，repeated字段为RepeatedField(2.x)
+// 通过Option和Vec转换，derive生成的代码不需要区分具体使用的protobuf库
+macro_rules! protobuf_transform_field_wrapper {
+    ($wrapper:ident, $inner:ident, $into_inner:ident) => {
+        impl<'a, T: 'a, U> ProtobufTransform<&'a T> for $wrapper<U>
+        where
+            $inner<U>: ProtobufTransform<&'a T>,
+        {
+            fn transform(value: &'a T) -> CodecResult<Self> {
+                let value: $inner<U> = ProtobufTransform::transform(value)?;
+                Ok(value.into())
+            }
+        }
+
+        impl<T: ProtobufTransform<$inner<U>>, U> ProtobufTransform<$wrapper<U>> for T {
+            fn transform(value: $wrapper<U>) -> CodecResult<Self> {
+                ProtobufTransform::transform(value.$into_inner())
+            }
+        }
+    };
+}
+
+mod field_wrapper_impl {
+    use super::*;
+    use ::protobuf::{RepeatedField, SingularPtrField};
+
+    protobuf_transform_field_wrapper!(SingularPtrField, Option, into_option);
+    protobuf_transform_field_wrapper!(RepeatedField, Vec, into_vec);
+}
+
+#[cfg(feature = "protobuf3")]
+mod field_wrapper_impl3 {
+    use super::*;
+    use ::protobuf3::MessageField;
+
+    protobuf_transform_field_wrapper!(MessageField, Option, into_option);
+}
+
 impl ProtobufTransform<i32> for i8 {
     fn transform(value: i32) -> CodecResult<Self> {
         Ok(value as i8)
@@ -622,16 +820,410 @@ impl ProtobufTransform<&u16> for u16 {
     }
 }
 
+impl<K, T, Y: ProtobufTransform<K> + Ord, U: ProtobufTransform<T>> ProtobufTransform<BTreeMap<K, T>> for BTreeMap<Y, U> {
+    fn transform(value: BTreeMap<K, T>) -> CodecResult<Self> {
+        let mut list = BTreeMap::new();
+        for (k, t) in value.into_iter() {
+            list.insert(ProtobufTransform::transform(k)?, ProtobufTransform::transform(t)?);
+        }
+        Ok(list)
+    }
+}
+
+impl<'a, K: 'a, T: 'a, Y: ProtobufTransform<&'a K> + Ord, U: ProtobufTransform<&'a T>> ProtobufTransform<&'a BTreeMap<K, T>> for BTreeMap<Y, U> {
+    fn transform(value: &'a BTreeMap<K, T>) -> CodecResult<Self> {
+        let mut list = BTreeMap::new();
+        for (k, t) in value.iter() {
+            list.insert(ProtobufTransform::transform(k)?, ProtobufTransform::transform(t)?);
+        }
+        Ok(list)
+    }
+}
+
+// prost默认把map生成为HashMap
+impl<K, T, Y: ProtobufTransform<K> + Ord, U: ProtobufTransform<T>> ProtobufTransform<HashMap<K, T>> for BTreeMap<Y, U> {
+    fn transform(value: HashMap<K, T>) -> CodecResult<Self> {
+        let mut list = BTreeMap::new();
+        for (k, t) in value.into_iter() {
+            list.insert(ProtobufTransform::transform(k)?, ProtobufTransform::transform(t)?);
+        }
+        Ok(list)
+    }
+}
+
+impl<'a, K: 'a, T: 'a, Y: ProtobufTransform<&'a K> + Eq + Hash, U: ProtobufTransform<&'a T>> ProtobufTransform<&'a BTreeMap<K, T>> for HashMap<Y, U> {
+    fn transform(value: &'a BTreeMap<K, T>) -> CodecResult<Self> {
+        let mut list = HashMap::new();
+        for (k, t) in value.iter() {
+            list.insert(ProtobufTransform::transform(k)?, ProtobufTransform::transform(t)?);
+        }
+        Ok(list)
+    }
+}
+
+// set对应repeated字段
+impl<T, U: ProtobufTransform<T> + Eq + Hash> ProtobufTransform<Vec<T>> for HashSet<U> {
+    fn transform(value: Vec<T>) -> CodecResult<Self> {
+        let mut list = HashSet::new();
+        for item in value.into_iter() {
+            list.insert(ProtobufTransform::transform(item)?);
+        }
+        Ok(list)
+    }
+}
+
+// HashSet的遍历顺序不确定，按转换后的protobuf值排序输出，保证同一个值的编码是确定的
+impl<'a, T: 'a, U: ProtobufTransform<&'a T> + Ord> ProtobufTransform<&'a HashSet<T>> for Vec<U> {
+    fn transform(value: &'a HashSet<T>) -> CodecResult<Self> {
+        let mut list = Vec::with_capacity(value.len());
+        for item in value.iter() {
+            list.push(ProtobufTransform::transform(item)?);
+        }
+        list.sort();
+        Ok(list)
+    }
+}
+
+impl<T, U: ProtobufTransform<T> + Ord> ProtobufTransform<Vec<T>> for BTreeSet<U> {
+    fn transform(value: Vec<T>) -> CodecResult<Self> {
+        let mut list = BTreeSet::new();
+        for item in value.into_iter() {
+            list.insert(ProtobufTransform::transform(item)?);
+        }
+        Ok(list)
+    }
+}
+
+impl<'a, T: 'a, U: ProtobufTransform<&'a T>> ProtobufTransform<&'a BTreeSet<T>> for Vec<U> {
+    fn transform(value: &'a BTreeSet<T>) -> CodecResult<Self> {
+        let mut list = Vec::with_capacity(value.len());
+        for item in value.iter() {
+            list.push(ProtobufTransform::transform(item)?);
+        }
+        Ok(list)
+    }
+}
+
+fn invalid_proto_bytes(name: &str, len: usize, expect: &str) -> CodecError {
+    let msg = format!("transform bytes to {} failed! len={}, expect {}", name, len, expect);
+    error!("{}", msg);
+    CodecError::new(CodecErrorCode::InvalidFormat, msg)
+}
+
+// i128/u128/H256对应定长的big endian bytes
+macro_rules! protobuf_transform_fixed_bytes {
+    ($ty:ty, $len:expr, $to:expr, $from:expr) => {
+        impl ProtobufTransform<Vec<u8>> for $ty {
+            fn transform(value: Vec<u8>) -> CodecResult<Self> {
+                let buf: [u8; $len] = value.as_slice().try_into().map_err(|_| {
+                    invalid_proto_bytes(stringify!($ty), value.len(), stringify!($len))
+                })?;
+                Ok($from(buf))
+            }
+        }
+
+        impl ProtobufTransform<&$ty> for Vec<u8> {
+            fn transform(value: &$ty) -> CodecResult<Self> {
+                Ok(Vec::from($to(value)))
+            }
+        }
+    };
+}
+
+protobuf_transform_fixed_bytes!(i128, 16, |v: &i128| v.to_be_bytes(), i128::from_be_bytes);
+protobuf_transform_fixed_bytes!(u128, 16, |v: &u128| v.to_be_bytes(), u128::from_be_bytes);
+protobuf_transform_fixed_bytes!(H256, 32, |v: &H256| v.to_fixed_bytes(), H256::from);
+
+// 地址类型对应string时使用标准的文本格式
+macro_rules! protobuf_transform_str {
+    ($($ty:ty),*) => {
+        $(
+        impl ProtobufTransform<String> for $ty {
+            fn transform(value: String) -> CodecResult<Self> {
+                ProtobufCodecHelper::decode_str_value(&value)
+            }
+        }
+
+        impl ProtobufTransform<&$ty> for String {
+            fn transform(value: &$ty) -> CodecResult<Self> {
+                Ok(value.to_string())
+            }
+        }
+        )*
+    };
+}
+
+protobuf_transform_str!(IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6);
+
+// IpAddr对应bytes时为4或者16个字节的地址
+impl ProtobufTransform<Vec<u8>> for IpAddr {
+    fn transform(value: Vec<u8>) -> CodecResult<Self> {
+        if let Ok(buf) = <[u8; 4]>::try_from(value.as_slice()) {
+            Ok(IpAddr::from(buf))
+        } else if let Ok(buf) = <[u8; 16]>::try_from(value.as_slice()) {
+            Ok(IpAddr::from(buf))
+        } else {
+            Err(invalid_proto_bytes("IpAddr", value.len(), "4 or 16"))
+        }
+    }
+}
+
+impl ProtobufTransform<&IpAddr> for Vec<u8> {
+    fn transform(value: &IpAddr) -> CodecResult<Self> {
+        Ok(match value {
+            IpAddr::V4(ip) => Vec::from(ip.octets()),
+            IpAddr::V6(ip) => Vec::from(ip.octets()),
+        })
+    }
+}
+
+// SocketAddr对应bytes时为地址 + big endian的u16端口
+impl ProtobufTransform<Vec<u8>> for SocketAddr {
+    fn transform(value: Vec<u8>) -> CodecResult<Self> {
+        if value.len() != 6 && value.len() != 18 {
+            return Err(invalid_proto_bytes("SocketAddr", value.len(), "6 or 18"));
+        }
+
+        let (ip, port) = value.split_at(value.len() - 2);
+        let ip: IpAddr = ProtobufTransform::transform(ip.to_owned())?;
+        let port = u16::from_be_bytes([port[0], port[1]]);
+        Ok(SocketAddr::new(ip, port))
+    }
+}
+
+impl ProtobufTransform<&SocketAddr> for Vec<u8> {
+    fn transform(value: &SocketAddr) -> CodecResult<Self> {
+        let mut buf: Vec<u8> = ProtobufTransform::transform(&value.ip())?;
+        buf.extend_from_slice(&value.port().to_be_bytes());
+        Ok(buf)
+    }
+}
+
+fn duration_from_proto(seconds: i64, nanos: i32) -> CodecResult<Duration> {
+    if seconds < 0 || nanos < 0 || nanos as u32 >= NANOS_PER_SEC {
+        let msg = format!("invalid protobuf Duration: seconds={}, nanos={}", seconds, nanos);
+        error!("{}", msg);
+        return Err(CodecError::new(CodecErrorCode::InvalidFormat, msg));
+    }
+
+    Ok(Duration::new(seconds as u64, nanos as u32))
+}
+
+fn duration_to_proto(value: &Duration) -> CodecResult<(i64, i32)> {
+    let seconds = i64::try_from(value.as_secs()).map_err(|_| {
+        let msg = format!("Duration out of protobuf range: {:?}", value);
+        error!("{}", msg);
+        CodecError::new(CodecErrorCode::OutOfLimit, msg)
+    })?;
+
+    Ok((seconds, value.subsec_nanos() as i32))
+}
+
+fn system_time_from_proto(seconds: i64, nanos: i32) -> CodecResult<SystemTime> {
+    if nanos < 0 || nanos as u32 >= NANOS_PER_SEC {
+        let msg = format!("invalid protobuf Timestamp: seconds={}, nanos={}", seconds, nanos);
+        error!("{}", msg);
+        return Err(CodecError::new(CodecErrorCode::InvalidFormat, msg));
+    }
+
+    system_time_from_timestamp(seconds, nanos as u32)
+}
+
+fn system_time_to_proto(value: &SystemTime) -> CodecResult<(i64, i32)> {
+    let (seconds, nanos) = system_time_to_timestamp(value)?;
+    Ok((seconds, nanos as i32))
+}
+
+// Duration/SystemTime对应google.protobuf.Duration/Timestamp
+macro_rules! protobuf_transform_well_known_time {
+    ($duration:ty, $timestamp:ty, $new_duration:expr, $new_timestamp:expr) => {
+        impl ProtobufTransform<$duration> for Duration {
+            fn transform(value: $duration) -> CodecResult<Self> {
+                duration_from_proto(value.seconds, value.nanos)
+            }
+        }
+
+        impl ProtobufTransform<&Duration> for $duration {
+            fn transform(value: &Duration) -> CodecResult<Self> {
+                let (seconds, nanos) = duration_to_proto(value)?;
+                Ok($new_duration(seconds, nanos))
+            }
+        }
+
+        impl ProtobufTransform<&Duration> for Option<$duration> {
+            fn transform(value: &Duration) -> CodecResult<Self> {
+                Ok(Some(ProtobufTransform::transform(value)?))
+            }
+        }
+
+        impl ProtobufTransform<Option<$duration>> for Duration {
+            fn transform(value: Option<$duration>) -> CodecResult<Self> {
+                match value {
+                    Some(value) => ProtobufTransform::transform(value),
+                    None => {
+                        let msg = format!("transform {} failed. value can't None", stringify!(Duration));
+                        error!("{}", msg);
+                        Err(CodecError::new(CodecErrorCode::InvalidInput, msg))
+                    }
+                }
+            }
+        }
+
+        impl ProtobufTransform<$timestamp> for SystemTime {
+            fn transform(value: $timestamp) -> CodecResult<Self> {
+                system_time_from_proto(value.seconds, value.nanos)
+            }
+        }
+
+        impl ProtobufTransform<&SystemTime> for $timestamp {
+            fn transform(value: &SystemTime) -> CodecResult<Self> {
+                let (seconds, nanos) = system_time_to_proto(value)?;
+                Ok($new_timestamp(seconds, nanos))
+            }
+        }
+
+        impl ProtobufTransform<&SystemTime> for Option<$timestamp> {
+            fn transform(value: &SystemTime) -> CodecResult<Self> {
+                Ok(Some(ProtobufTransform::transform(value)?))
+            }
+        }
+
+        impl ProtobufTransform<Option<$timestamp>> for SystemTime {
+            fn transform(value: Option<$timestamp>) -> CodecResult<Self> {
+                match value {
+                    Some(value) => ProtobufTransform::transform(value),
+                    None => {
+                        let msg = format!("transform {} failed. value can't None", stringify!(SystemTime));
+                        error!("{}", msg);
+                        Err(CodecError::new(CodecErrorCode::InvalidInput, msg))
+                    }
+                }
+            }
+        }
+    };
+}
+
+protobuf_transform_well_known_time!(
+    ::protobuf::well_known_types::Duration,
+    ::protobuf::well_known_types::Timestamp,
+    |seconds, nanos| {
+        let mut ret = ::protobuf::well_known_types::Duration::new();
+        ret.seconds = seconds;
+        ret.nanos = nanos;
+        ret
+    },
+    |seconds, nanos| {
+        let mut ret = ::protobuf::well_known_types::Timestamp::new();
+        ret.seconds = seconds;
+        ret.nanos = nanos;
+        ret
+    }
+);
+
+#[cfg(feature = "protobuf3")]
+protobuf_transform_well_known_time!(
+    ::protobuf3::well_known_types::duration::Duration,
+    ::protobuf3::well_known_types::timestamp::Timestamp,
+    |seconds, nanos| ::protobuf3::well_known_types::duration::Duration {
+        seconds,
+        nanos,
+        ..Default::default()
+    },
+    |seconds, nanos| ::protobuf3::well_known_types::timestamp::Timestamp {
+        seconds,
+        nanos,
+        ..Default::default()
+    }
+);
+
+#[cfg(feature = "prost")]
+protobuf_transform_well_known_time!(
+    ::prost_types::Duration,
+    ::prost_types::Timestamp,
+    |seconds, nanos| ::prost_types::Duration { seconds, nanos },
+    |seconds, nanos| ::prost_types::Timestamp { seconds, nanos }
+);
+
+// #[bucky_protobuf(raw)]字段的转换，使用raw codec编码为bytes
+// T对应bytes，Option<T>对应optional bytes，Vec<T>对应repeated bytes
+pub trait ProtobufRawField<P>: Sized {
+    fn raw_to_proto(&self) -> CodecResult<P>;
+    fn raw_from_proto(value: P) -> CodecResult<Self>;
+}
+
+impl<T> ProtobufRawField<Vec<u8>> for T
+where
+    T: RawEncode + for<'de> RawDecode<'de>,
+{
+    fn raw_to_proto(&self) -> CodecResult<Vec<u8>> {
+        self.to_vec()
+    }
+
+    fn raw_from_proto(value: Vec<u8>) -> CodecResult<Self> {
+        T::decode_exact(&value)
+    }
+}
+
+impl<T> ProtobufRawField<Option<Vec<u8>>> for Option<T>
+where
+    T: RawEncode + for<'de> RawDecode<'de>,
+{
+    fn raw_to_proto(&self) -> CodecResult<Option<Vec<u8>>> {
+        match self {
+            Some(value) => Ok(Some(value.to_vec()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn raw_from_proto(value: Option<Vec<u8>>) -> CodecResult<Self> {
+        match value {
+            Some(value) => Ok(Some(T::decode_exact(&value)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl<T> ProtobufRawField<Vec<Vec<u8>>> for Vec<T>
+where
+    T: RawEncode + for<'de> RawDecode<'de>,
+{
+    fn raw_to_proto(&self) -> CodecResult<Vec<Vec<u8>>> {
+        ProtobufCodecHelper::encode_buf_list(self)
+    }
+
+    fn raw_from_proto(value: Vec<Vec<u8>>) -> CodecResult<Self> {
+        let mut list = Vec::with_capacity(value.len());
+        for buf in value {
+            list.push(T::decode_exact(&buf)?);
+        }
+        Ok(list)
+    }
+}
+
+// rust-protobuf生成的repeated字段为RepeatedField
+impl<T> ProtobufRawField<::protobuf::RepeatedField<Vec<u8>>> for Vec<T>
+where
+    T: RawEncode + for<'de> RawDecode<'de>,
+{
+    fn raw_to_proto(&self) -> CodecResult<::protobuf::RepeatedField<Vec<u8>>> {
+        ProtobufCodecHelper::encode_buf_list(self)
+    }
+
+    fn raw_from_proto(value: ::protobuf::RepeatedField<Vec<u8>>) -> CodecResult<Self> {
+        ProtobufRawField::raw_from_proto(value.into_vec())
+    }
+}
+
 #[macro_export]
 macro_rules! impl_default_protobuf_raw_codec {
-    ($name:ty, $proto_name:ty) => {
+    ($name:ty, $proto_name:ty, $backend:ty) => {
         impl bucky_raw_codec::RawEncode for $name {
             fn raw_measure(
                 &self,
                 purpose: &Option<bucky_raw_codec::RawEncodePurpose>,
             ) -> bucky_raw_codec::CodecResult<usize> {
                 // info!("desc content measure");
-                bucky_raw_codec::ProtobufCodecHelper::raw_measure::<$name, $proto_name>(&self, purpose)
+                bucky_raw_codec::ProtobufCodecHelper::raw_measure::<$backend, $name, $proto_name>(&self, purpose)
             }
             fn raw_encode<'a>(
                 &self,
@@ -639,17 +1231,22 @@ macro_rules! impl_default_protobuf_raw_codec {
                 purpose: &Option<bucky_raw_codec::RawEncodePurpose>,
             ) -> bucky_raw_codec::CodecResult<&'a mut [u8]> {
                 // info!("desc content encode");
-                bucky_raw_codec::ProtobufCodecHelper::raw_encode::<$name, $proto_name>(self, buf, purpose)
+                bucky_raw_codec::ProtobufCodecHelper::raw_encode::<$backend, $name, $proto_name>(self, buf, purpose)
             }
         }
         impl<'de> bucky_raw_codec::RawDecode<'de> for $name {
             fn raw_decode(buf: &'de [u8]) -> bucky_raw_codec::CodecResult<(Self, &'de [u8])> {
                 // info!("desc content decode");
-                bucky_raw_codec::ProtobufCodecHelper::raw_decode::<$name, $proto_name>(buf)
+                bucky_raw_codec::ProtobufCodecHelper::raw_decode::<$backend, $name, $proto_name>(buf)
             }
         }
     };
 
+    // 没有指定实现库时使用rust-protobuf 2
+    ($name:ty, $proto_name:ty) => {
+        bucky_raw_codec::impl_default_protobuf_raw_codec!($name, $proto_name, bucky_raw_codec::RustProtobuf);
+    };
+
     ($name:ident) => {
         bucky_raw_codec::impl_default_protobuf_raw_codec!($name, protos::$name);
     };
@@ -658,14 +1255,14 @@ macro_rules! impl_default_protobuf_raw_codec {
 // cyfs_base工程内部使用
 #[macro_export]
 macro_rules! inner_impl_default_protobuf_raw_codec {
-    ($name:ty, $proto_name:ty) => {
+    ($name:ty, $proto_name:ty, $backend:ty) => {
         impl crate::RawEncode for $name {
             fn raw_measure(
                 &self,
                 purpose: &Option<crate::RawEncodePurpose>,
             ) -> crate::CodecResult<usize> {
                 // info!("desc content measure");
-                crate::ProtobufCodecHelper::raw_measure::<$name, $proto_name>(&self, purpose)
+                crate::ProtobufCodecHelper::raw_measure::<$backend, $name, $proto_name>(&self, purpose)
             }
             fn raw_encode<'a>(
                 &self,
@@ -673,17 +1270,21 @@ macro_rules! inner_impl_default_protobuf_raw_codec {
                 purpose: &Option<crate::RawEncodePurpose>,
             ) -> crate::CodecResult<&'a mut [u8]> {
                 // info!("desc content encode");
-                crate::ProtobufCodecHelper::raw_encode::<$name, $proto_name>(self, buf, purpose)
+                crate::ProtobufCodecHelper::raw_encode::<$backend, $name, $proto_name>(self, buf, purpose)
             }
         }
         impl<'de> crate::RawDecode<'de> for $name {
             fn raw_decode(buf: &'de [u8]) -> crate::CodecResult<(Self, &'de [u8])> {
                 // info!("desc content decode");
-                crate::ProtobufCodecHelper::raw_decode::<$name, $proto_name>(buf)
+                crate::ProtobufCodecHelper::raw_decode::<$backend, $name, $proto_name>(buf)
             }
         }
     };
 
+    ($name:ty, $proto_name:ty) => {
+        crate::inner_impl_default_protobuf_raw_codec!($name, $proto_name, crate::RustProtobuf);
+    };
+
     ($name:ident) => {
         crate::inner_impl_default_protobuf_raw_codec!($name, protos::$name);
     };
@@ -712,14 +1313,14 @@ macro_rules! mod_impl_empty_protobuf_raw_codec {
             fn raw_decode(buf: &'de [u8]) -> $m::CodecResult<(Self, &'de [u8])> {
                 // info!("desc content decode");
 
-                let (msg, buf) = $m::ProtobufMessageCodecHelper::raw_decode::<$proto_name>(buf)?;
+                let (_msg, unknown) =
+                    <$m::RustProtobuf as $m::ProtobufBackend<$proto_name>>::decode_with_unknown(buf)?;
 
                 // 如果存在unknown fields，那么打印
-                use ::protobuf::Message;
-                if let Some(list) = &msg.get_unknown_fields().fields {
-                    warn!("got unknown fields! count={}", list.len());
+                if !unknown.is_empty() {
+                    warn!("got unknown fields! len={}", unknown.len());
                 }
-                Ok((Self {}, buf))
+                Ok((Self {}, &buf[buf.len()..]))
             }
         }
     };
@@ -755,6 +1356,7 @@ macro_rules! inner_impl_empty_protobuf_raw_codec {
 mod test {
     use crate::*;
     use crate as bucky_raw_codec;
+    use bucky_raw_codec_derive::*;
     use ::protobuf::Message;
     use std::convert::TryFrom;
 
@@ -831,6 +1433,1214 @@ mod test {
         assert_eq!(buf.len(), 0);
     }
 
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode)]
+    #[bucky_protobuf_type(protos::EmptyContentV1)]
+    #[bucky_protobuf_backend(RustProtobuf)]
+    struct NameContent {
+        name: Option<String>,
+    }
+
+    impl ProtobufTransform<&NameContent> for protos::EmptyContentV1 {
+        fn transform(value: &NameContent) -> CodecResult<Self> {
+            let mut ret = Self::new();
+            if let Some(name) = &value.name {
+                ret.set_name(name.to_owned());
+            }
+
+            Ok(ret)
+        }
+    }
+
+    impl ProtobufTransform<protos::EmptyContentV1> for NameContent {
+        fn transform(mut value: protos::EmptyContentV1) -> CodecResult<Self> {
+            let name = if value.has_name() {
+                Some(value.take_name())
+            } else {
+                None
+            };
+
+            Ok(Self { name })
+        }
+    }
+
+    #[test]
+    fn test_backend() {
+        let content = NameContent {
+            name: Some("xxx".to_owned()),
+        };
+        let buf = content.to_vec().unwrap();
+        assert_eq!(buf.len(), content.raw_measure(&None).unwrap());
+
+        // 与直接使用helper编码一致
+        let v1 = EmptyContentV1 {
+            name: Some("xxx".to_owned()),
+        };
+        assert_eq!(buf, v1.to_vec().unwrap());
+
+        let (content2, left_buf) = NameContent::raw_decode(&buf).unwrap();
+        assert_eq!(content, content2);
+        assert!(left_buf.is_empty());
+
+        // buf不足
+        let mut small = vec![0u8; buf.len() - 1];
+        let err = content.raw_encode(&mut small, &None).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::OutOfLimit);
+
+        let err = NameContent::raw_decode(&[0xff]).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+    }
+
+    #[cfg(feature = "protobuf3")]
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode)]
+    #[bucky_protobuf_type(::protobuf3::well_known_types::duration::Duration)]
+    #[bucky_protobuf_backend(RustProtobuf3)]
+    struct Span {
+        duration: std::time::Duration,
+        #[bucky_protobuf(unknown_fields)]
+        unknown: ProtobufUnknownFields,
+    }
+
+    #[cfg(feature = "protobuf3")]
+    impl ProtobufTransform<&Span> for ::protobuf3::well_known_types::duration::Duration {
+        fn transform(value: &Span) -> CodecResult<Self> {
+            ProtobufTransform::transform(&value.duration)
+        }
+    }
+
+    #[cfg(feature = "protobuf3")]
+    impl ProtobufTransform<::protobuf3::well_known_types::duration::Duration> for Span {
+        fn transform(value: ::protobuf3::well_known_types::duration::Duration) -> CodecResult<Self> {
+            Ok(Self {
+                duration: ProtobufTransform::transform(value)?,
+                unknown: ProtobufUnknownFields::default(),
+            })
+        }
+    }
+
+    #[cfg(feature = "protobuf3")]
+    struct Props {
+        values: std::collections::HashMap<String, f64>,
+    }
+
+    #[cfg(feature = "protobuf3")]
+    impl TryFrom<&Props> for ::protobuf3::well_known_types::struct_::Struct {
+        type Error = CodecError;
+        fn try_from(value: &Props) -> CodecResult<Self> {
+            let mut ret = Self::new();
+            for (key, value) in &value.values {
+                let mut item = ::protobuf3::well_known_types::struct_::Value::new();
+                item.set_number_value(*value);
+                ret.fields.insert(key.clone(), item);
+            }
+            Ok(ret)
+        }
+    }
+
+    #[cfg(feature = "protobuf3")]
+    impl TryFrom<::protobuf3::well_known_types::struct_::Struct> for Props {
+        type Error = CodecError;
+        fn try_from(value: ::protobuf3::well_known_types::struct_::Struct) -> CodecResult<Self> {
+            let values = value
+                .fields
+                .into_iter()
+                .map(|(key, value)| (key, value.number_value()))
+                .collect();
+            Ok(Self { values })
+        }
+    }
+
+    #[cfg(feature = "protobuf3")]
+    inner_impl_default_protobuf_raw_codec!(
+        Props,
+        ::protobuf3::well_known_types::struct_::Struct,
+        RustProtobuf3
+    );
+
+    #[cfg(feature = "protobuf3")]
+    #[test]
+    fn test_protobuf3_backend() {
+        use ::protobuf3::Message;
+
+        let span = Span {
+            duration: std::time::Duration::new(10, 500),
+            unknown: ProtobufUnknownFields::default(),
+        };
+        let buf = span.to_vec().unwrap();
+        assert_eq!(buf.len(), span.raw_measure(&None).unwrap());
+        let proto = ::protobuf3::well_known_types::duration::Duration::parse_from_bytes(&buf).unwrap();
+        assert_eq!((proto.seconds, proto.nanos), (10, 500));
+
+        // 未识别的字段按原样保留，计算hash时同样包含
+        let mut buf2 = buf.clone();
+        buf2.extend_from_slice(&[0x18, 0x01]);
+        let (span2, _) = Span::raw_decode(&buf2).unwrap();
+        assert_eq!(span2.duration, span.duration);
+        assert_eq!(span2.unknown.as_slice(), &[0x18, 0x01]);
+        assert_eq!(span2.to_vec().unwrap(), buf2);
+        assert_eq!(span2.raw_hash_encode().unwrap(), buf2);
+
+        let err = Span::raw_decode(&[0x08]).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+
+        // helper使用同一个实现库，map字段计算hash时排序
+        let new_props = |reverse: bool| {
+            let mut list: Vec<u32> = (0..32).collect();
+            if reverse {
+                list.reverse();
+            }
+            Props {
+                values: list.iter().map(|i| (format!("key{}", i), *i as f64)).collect(),
+            }
+        };
+        let props = new_props(false);
+        let hash_buf = props.raw_hash_encode().unwrap();
+        assert_eq!(new_props(true).raw_hash_encode().unwrap(), hash_buf);
+        let (props2, _) = Props::raw_decode(&hash_buf).unwrap();
+        assert_eq!(props2.values, props.values);
+
+        // 通过描述符找到嵌套的message，其中的map和未识别的字段同样处理
+        use ::protobuf3::well_known_types::struct_::{Struct, Value};
+        let new_struct = |reverse: bool| {
+            let mut list: Vec<u32> = (0..16).collect();
+            if reverse {
+                list.reverse();
+            }
+            let mut inner = Struct::new();
+            for i in list.iter() {
+                let mut value = Value::new();
+                value.set_number_value(*i as f64);
+                inner.fields.insert(format!("inner{}", i), value);
+            }
+            let mut outer = Struct::new();
+            for i in list.iter() {
+                let mut value = Value::new();
+                value.set_struct_value(inner.clone());
+                outer.fields.insert(format!("outer{}", i), value);
+            }
+            outer
+        };
+        let value = new_struct(false);
+        let hash_buf = <RustProtobuf3 as ProtobufBackend<Struct>>::encode_canonical(&value).unwrap();
+        for i in 0..4 {
+            let value2 = new_struct(i % 2 == 1);
+            assert_eq!(<RustProtobuf3 as ProtobufBackend<Struct>>::encode_canonical(&value2).unwrap(), hash_buf);
+        }
+        let value2 = <RustProtobuf3 as ProtobufBackend<Struct>>::decode(&hash_buf).unwrap();
+        assert_eq!(value2, value);
+
+        let buf = [0x0a, 0x09, 0x0a, 0x01, 0x61, 0x12, 0x04, 0x2a, 0x02, 0x48, 0x01];
+        let value = <RustProtobuf3 as ProtobufBackend<Struct>>::decode(&buf).unwrap();
+        let data = <RustProtobuf3 as ProtobufBackend<Struct>>::encode_canonical(&value).unwrap();
+        assert_eq!(data, [0x0a, 0x07, 0x0a, 0x01, 0x61, 0x12, 0x02, 0x2a, 0x00]);
+    }
+
+    // rust-protobuf生成的结构带有unknown_fields等成员，message字段为SingularPtrField，repeated字段为RepeatedField
+    #[derive(Debug, PartialEq, ProtobufTransform)]
+    #[bucky_protobuf_type(::protobuf::well_known_types::Any)]
+    struct AnyValue {
+        type_url: String,
+        value: Vec<u8>,
+    }
+
+    #[derive(Debug, PartialEq, ProtobufTransform)]
+    #[bucky_protobuf_type(::protobuf::well_known_types::Option)]
+    struct TypeOption {
+        name: String,
+        value: Option<AnyValue>,
+    }
+
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode, ProtobufTransform)]
+    #[bucky_protobuf_type(::protobuf::well_known_types::Type)]
+    #[bucky_protobuf_backend(RustProtobuf)]
+    struct TypeInfo {
+        name: String,
+        oneofs: Vec<String>,
+        options: Vec<TypeOption>,
+    }
+
+    #[test]
+    fn test_rust_protobuf_derive() {
+        let info = TypeInfo {
+            name: "type".to_owned(),
+            oneofs: vec!["kind".to_owned()],
+            options: vec![
+                TypeOption {
+                    name: "empty".to_owned(),
+                    value: None,
+                },
+                TypeOption {
+                    name: "any".to_owned(),
+                    value: Some(AnyValue {
+                        type_url: "url".to_owned(),
+                        value: vec![1, 2, 3],
+                    }),
+                },
+            ],
+        };
+        let buf = info.to_vec().unwrap();
+        assert_eq!(buf.len(), info.raw_measure(&None).unwrap());
+        let (info2, remain) = TypeInfo::raw_decode(&buf).unwrap();
+        assert!(remain.is_empty());
+        assert_eq!(info2, info);
+
+        let proto = <::protobuf::well_known_types::Type as ::protobuf::Message>::parse_from_bytes(&buf).unwrap();
+        assert_eq!(proto.oneofs.to_vec(), vec!["kind".to_owned()]);
+        assert_eq!(proto.options.len(), 2);
+        assert!(!proto.options[0].has_value());
+        assert_eq!(proto.options[1].get_value().value, vec![1, 2, 3]);
+    }
+
+    #[cfg(feature = "protobuf3")]
+    #[derive(Debug, PartialEq, ProtobufTransform)]
+    #[bucky_protobuf_type(::protobuf3::well_known_types::any::Any)]
+    struct AnyValue3 {
+        type_url: String,
+        value: Vec<u8>,
+    }
+
+    // message字段为MessageField，必需的字段对应非Option的成员
+    #[cfg(feature = "protobuf3")]
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode, ProtobufTransform)]
+    #[bucky_protobuf_type(::protobuf3::well_known_types::type_::Option)]
+    #[bucky_protobuf_backend(RustProtobuf3)]
+    struct TypeOption3 {
+        name: String,
+        value: AnyValue3,
+        #[bucky_protobuf(unknown_fields)]
+        unknown: ProtobufUnknownFields,
+    }
+
+    #[cfg(feature = "protobuf3")]
+    #[test]
+    fn test_protobuf3_derive() {
+        use ::protobuf3::Message;
+
+        let option = TypeOption3 {
+            name: "any".to_owned(),
+            value: AnyValue3 {
+                type_url: "url".to_owned(),
+                value: vec![1, 2, 3],
+            },
+            unknown: ProtobufUnknownFields::default(),
+        };
+        let buf = option.to_vec().unwrap();
+        assert_eq!(buf.len(), option.raw_measure(&None).unwrap());
+        let (option2, remain) = TypeOption3::raw_decode(&buf).unwrap();
+        assert!(remain.is_empty());
+        assert_eq!(option2, option);
+
+        let proto = ::protobuf3::well_known_types::type_::Option::parse_from_bytes(&buf).unwrap();
+        assert_eq!(proto.name, "any");
+        assert_eq!(proto.value.type_url, "url");
+
+        // 未识别的字段按原样保留
+        let mut buf2 = buf.clone();
+        buf2.extend_from_slice(&[0x18, 0x01]);
+        let (option3, _) = TypeOption3::raw_decode(&buf2).unwrap();
+        assert_eq!(option3.unknown.as_slice(), &[0x18, 0x01]);
+        assert_eq!(option3.to_vec().unwrap(), buf2);
+
+        // 缺少必需的message字段
+        assert!(TypeOption3::raw_decode(&[0x0a, 0x01, 0x61]).is_err());
+    }
+
+    #[cfg(feature = "prost")]
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    struct ProstName {
+        #[prost(string, optional, tag = "1")]
+        name: Option<String>,
+    }
+
+    #[cfg(feature = "prost")]
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode)]
+    #[bucky_protobuf_type(ProstName)]
+    #[bucky_protobuf_backend(Prost)]
+    struct ProstContent {
+        name: Option<String>,
+    }
+
+    #[cfg(feature = "prost")]
+    impl ProtobufTransform<&ProstContent> for ProstName {
+        fn transform(value: &ProstContent) -> CodecResult<Self> {
+            Ok(Self {
+                name: value.name.clone(),
+            })
+        }
+    }
+
+    #[cfg(feature = "prost")]
+    impl ProtobufTransform<ProstName> for ProstContent {
+        fn transform(value: ProstName) -> CodecResult<Self> {
+            Ok(Self { name: value.name })
+        }
+    }
+
+    #[cfg(feature = "prost")]
+    #[test]
+    fn test_prost_backend() {
+        let content = ProstContent {
+            name: Some("xxx".to_owned()),
+        };
+        let buf = content.to_vec().unwrap();
+        assert_eq!(buf.len(), content.raw_measure(&None).unwrap());
+
+        // 两种实现库的编码结果一致
+        let rust_protobuf = NameContent {
+            name: Some("xxx".to_owned()),
+        };
+        assert_eq!(buf, rust_protobuf.to_vec().unwrap());
+
+        let (content2, left_buf) = ProstContent::raw_decode(&buf).unwrap();
+        assert_eq!(content, content2);
+        assert!(left_buf.is_empty());
+
+        let (content3, _) = NameContent::raw_decode(&buf).unwrap();
+        assert_eq!(content3, rust_protobuf);
+    }
+
+    #[cfg(feature = "prost")]
+    mod prost_shape {
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct Rect {
+            #[prost(uint32, tag = "1")]
+            pub field1: u32,
+            #[prost(uint32, tag = "2")]
+            pub field2: u32,
+        }
+
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct Label {
+            #[prost(string, tag = "1")]
+            pub name: String,
+            #[prost(bytes = "vec", tag = "2")]
+            pub data: Vec<u8>,
+        }
+
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Shape {
+            #[prost(uint32, tag = "1")]
+            Circle(u32),
+            #[prost(message, tag = "2")]
+            Rect(Rect),
+            #[prost(message, tag = "3")]
+            Label(Label),
+        }
+
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct ShapeHolder {
+            #[prost(oneof = "Shape", tags = "1, 2, 3")]
+            pub shape: Option<Shape>,
+            #[prost(message, optional, tag = "4")]
+            pub origin: Option<Rect>,
+        }
+    }
+
+    #[cfg(feature = "prost")]
+    #[derive(Debug, PartialEq, ProtobufTransform)]
+    #[bucky_protobuf_type(prost_shape::Shape)]
+    enum Shape {
+        Circle(u32),
+        #[bucky_protobuf_type(prost_shape::Rect)]
+        Rect(u32, u32),
+        #[bucky_protobuf_type(prost_shape::Label)]
+        #[bucky_protobuf(rename = "Label")]
+        Text { name: String, data: Vec<u8> },
+    }
+
+    #[cfg(feature = "prost")]
+    #[derive(Debug, PartialEq, ProtobufTransform)]
+    #[bucky_protobuf_type(prost_shape::Rect)]
+    struct Point(u32, u32);
+
+    #[cfg(feature = "prost")]
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode, ProtobufTransform)]
+    #[bucky_protobuf_type(prost_shape::ShapeHolder)]
+    #[bucky_protobuf_backend(Prost)]
+    struct ShapeHolder {
+        shape: Shape,
+        origin: Option<Point>,
+    }
+
+    #[cfg(feature = "prost")]
+    #[test]
+    fn test_prost_multi_field() {
+        let list = vec![
+            ShapeHolder {
+                shape: Shape::Circle(3),
+                origin: None,
+            },
+            ShapeHolder {
+                shape: Shape::Rect(4, 5),
+                origin: Some(Point(1, 2)),
+            },
+            ShapeHolder {
+                shape: Shape::Text {
+                    name: "label".to_owned(),
+                    data: vec![1, 2, 3],
+                },
+                origin: Some(Point(0, 0)),
+            },
+        ];
+
+        for holder in list {
+            let buf = holder.to_vec().unwrap();
+            let (holder2, _) = ShapeHolder::raw_decode(&buf).unwrap();
+            assert_eq!(holder, holder2);
+        }
+
+        // 多字段成员对应oneof中的嵌套message，tuple字段依次对应field1, field2
+        let proto: prost_shape::ShapeHolder = ProtobufTransform::transform(&ShapeHolder {
+            shape: Shape::Rect(4, 5),
+            origin: Some(Point(1, 2)),
+        })
+        .unwrap();
+        assert_eq!(
+            proto.shape,
+            Some(prost_shape::Shape::Rect(prost_shape::Rect {
+                field1: 4,
+                field2: 5
+            }))
+        );
+        assert_eq!(proto.origin.unwrap().field2, 2);
+    }
+
+    #[cfg(feature = "prost")]
+    mod u32_bytes {
+        use crate::*;
+
+        pub fn to_proto(value: &u32) -> CodecResult<Vec<u8>> {
+            Ok(Vec::from(value.to_be_bytes()))
+        }
+
+        pub fn from_proto(value: Vec<u8>) -> CodecResult<u32> {
+            let buf: [u8; 4] = value.try_into().map_err(|_| {
+                CodecError::new(CodecErrorCode::InvalidFormat, "invalid u32 bytes")
+            })?;
+            Ok(u32::from_be_bytes(buf))
+        }
+    }
+
+    #[cfg(feature = "prost")]
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode, ProtobufTransform)]
+    #[bucky_protobuf_type(prost_shape::Label)]
+    #[bucky_protobuf_backend(Prost)]
+    struct Tag {
+        #[bucky_protobuf(rename = "name")]
+        title: String,
+        #[bucky_protobuf(rename = "data", with = "u32_bytes")]
+        value: u32,
+        #[bucky_protobuf(skip)]
+        cache: Option<String>,
+    }
+
+    #[cfg(feature = "prost")]
+    mod prost_color {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+        #[repr(i32)]
+        pub enum Color {
+            Unspecified = 0,
+            Red = 1,
+            Green = 2,
+            Blue = 3,
+        }
+
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct Paint {
+            #[prost(enumeration = "Color", tag = "1")]
+            pub color: i32,
+        }
+    }
+
+    #[cfg(feature = "prost")]
+    #[derive(Debug, PartialEq, ProtobufTransform)]
+    #[bucky_protobuf_type(prost_color::Color)]
+    enum Color {
+        #[bucky_protobuf(rename = "Red")]
+        Crimson,
+        Green,
+        #[bucky_protobuf(other)]
+        Unspecified,
+    }
+
+    #[cfg(feature = "prost")]
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode, ProtobufTransform)]
+    #[bucky_protobuf_type(prost_color::Paint)]
+    #[bucky_protobuf_backend(Prost)]
+    struct Paint {
+        color: Color,
+    }
+
+    #[cfg(feature = "prost")]
+    #[test]
+    fn test_prost_enum() {
+        let color: prost_color::Color = ProtobufTransform::transform(&Color::Crimson).unwrap();
+        assert_eq!(color, prost_color::Color::Red);
+        let color: Color = ProtobufTransform::transform(prost_color::Color::Blue).unwrap();
+        assert_eq!(color, Color::Unspecified);
+
+        // prost生成的message中枚举字段是i32
+        let paint = Paint { color: Color::Green };
+        let proto: prost_color::Paint = ProtobufTransform::transform(&paint).unwrap();
+        assert_eq!(proto.color, prost_color::Color::Green as i32);
+
+        let buf = paint.to_vec().unwrap();
+        let (paint2, _) = Paint::raw_decode(&buf).unwrap();
+        assert_eq!(paint, paint2);
+
+        let paint: Paint = ProtobufTransform::transform(prost_color::Paint { color: 100 }).unwrap();
+        assert_eq!(paint.color, Color::Unspecified);
+    }
+
+    #[cfg(feature = "prost")]
+    #[test]
+    fn test_prost_field_attrs() {
+        let tag = Tag {
+            title: "tag".to_owned(),
+            value: 0x01020304,
+            cache: Some("cache".to_owned()),
+        };
+
+        let proto: prost_shape::Label = ProtobufTransform::transform(&tag).unwrap();
+        assert_eq!(proto.name, "tag");
+        assert_eq!(proto.data, vec![1, 2, 3, 4]);
+
+        // skip的字段解码时使用Default
+        let buf = tag.to_vec().unwrap();
+        let (tag2, _) = Tag::raw_decode(&buf).unwrap();
+        assert_eq!(tag2.title, tag.title);
+        assert_eq!(tag2.value, tag.value);
+        assert!(tag2.cache.is_none());
+
+        let proto = prost_shape::Label {
+            name: "tag".to_owned(),
+            data: vec![1, 2, 3],
+        };
+        let ret: CodecResult<Tag> = ProtobufTransform::transform(proto);
+        assert!(ret.is_err());
+    }
+
+    #[derive(Debug, PartialEq, ProtobufTransform)]
+    #[bucky_protobuf_type(i32)]
+    #[repr(i32)]
+    enum Level {
+        Low = 1,
+        Middle,
+        #[bucky_protobuf(value = 10)]
+        High,
+        // value只改变High的值，后面的成员仍然按rust的discriminant
+        Higher,
+        Top = -1,
+        Min = -2147483648,
+        #[bucky_protobuf(other)]
+        Unknown(i32),
+    }
+
+    #[derive(Debug, PartialEq, ProtobufTransform)]
+    #[bucky_protobuf_type(i32)]
+    enum Strict {
+        A,
+        B,
+    }
+
+    #[test]
+    fn test_enum_value() {
+        let list = [
+            (Level::Low, 1),
+            (Level::Middle, 2),
+            (Level::High, 10),
+            (Level::Higher, 4),
+            (Level::Top, -1),
+            (Level::Min, i32::MIN),
+            (Level::Unknown(100), 100),
+        ];
+        for (level, value) in list {
+            let v: i32 = ProtobufTransform::transform(&level).unwrap();
+            assert_eq!(v, value);
+            let level2: Level = ProtobufTransform::transform(value).unwrap();
+            assert_eq!(level, level2);
+        }
+
+        // 没有other成员时未知的值返回错误
+        let v: i32 = ProtobufTransform::transform(&Strict::B).unwrap();
+        assert_eq!(v, 1);
+        let ret: CodecResult<Strict> = ProtobufTransform::transform(2i32);
+        assert_eq!(ret.unwrap_err().code(), CodecErrorCode::InvalidInput);
+    }
+
+    #[test]
+    fn test_transform_ext() {
+        use primitive_types::H256;
+        use std::collections::{BTreeSet, HashSet};
+        use std::net::{IpAddr, SocketAddr};
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let v: Vec<u8> = ProtobufTransform::transform(&-2i128).unwrap();
+        assert_eq!(v.len(), 16);
+        let v2: i128 = ProtobufTransform::transform(v).unwrap();
+        assert_eq!(v2, -2);
+        let ret: CodecResult<u128> = ProtobufTransform::transform(vec![1u8; 15]);
+        assert_eq!(ret.unwrap_err().code(), CodecErrorCode::InvalidFormat);
+
+        let hash = H256::random();
+        let v: Vec<u8> = ProtobufTransform::transform(&hash).unwrap();
+        let hash2: H256 = ProtobufTransform::transform(v).unwrap();
+        assert_eq!(hash, hash2);
+
+        let addr: SocketAddr = "[::1]:8080".parse().unwrap();
+        let s: String = ProtobufTransform::transform(&addr).unwrap();
+        assert_eq!(s, "[::1]:8080");
+        let v: Vec<u8> = ProtobufTransform::transform(&addr).unwrap();
+        assert_eq!(v.len(), 18);
+        let addr2: SocketAddr = ProtobufTransform::transform(v).unwrap();
+        assert_eq!(addr, addr2);
+        let ip: IpAddr = ProtobufTransform::transform(vec![127u8, 0, 0, 1]).unwrap();
+        assert_eq!(ip.to_string(), "127.0.0.1");
+
+        let set: HashSet<u16> = [1, 2, 3].into_iter().collect();
+        let list: Vec<u32> = ProtobufTransform::transform(&set).unwrap();
+        assert_eq!(list, vec![1, 2, 3]);
+        let set2: BTreeSet<u16> = ProtobufTransform::transform(list).unwrap();
+        assert_eq!(set2.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        let duration = Duration::new(10, 500);
+        let proto: ::protobuf::well_known_types::Duration =
+            ProtobufTransform::transform(&duration).unwrap();
+        assert_eq!((proto.seconds, proto.nanos), (10, 500));
+        let duration2: Duration = ProtobufTransform::transform(proto).unwrap();
+        assert_eq!(duration, duration2);
+
+        // 1970年之前的时间使用负的seconds
+        let time = UNIX_EPOCH - Duration::new(1, 0) + Duration::from_nanos(1);
+        let proto: ::protobuf::well_known_types::Timestamp =
+            ProtobufTransform::transform(&time).unwrap();
+        assert_eq!((proto.seconds, proto.nanos), (-1, 1));
+        let time2: std::time::SystemTime = ProtobufTransform::transform(proto).unwrap();
+        assert_eq!(time, time2);
+
+        let mut proto = ::protobuf::well_known_types::Duration::new();
+        proto.seconds = -1;
+        let ret: CodecResult<Duration> = ProtobufTransform::transform(proto);
+        assert_eq!(ret.unwrap_err().code(), CodecErrorCode::InvalidFormat);
+    }
+
+    #[cfg(feature = "prost")]
+    mod prost_record {
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct Record {
+            #[prost(btree_map = "string, uint64", tag = "1")]
+            pub counts: ::std::collections::BTreeMap<String, u64>,
+            #[prost(string, repeated, tag = "2")]
+            pub tags: Vec<String>,
+            #[prost(bytes = "vec", tag = "3")]
+            pub amount: Vec<u8>,
+            #[prost(string, tag = "4")]
+            pub addr: String,
+            #[prost(message, optional, tag = "5")]
+            pub create_time: Option<::prost_types::Timestamp>,
+            #[prost(message, optional, tag = "6")]
+            pub ttl: Option<::prost_types::Duration>,
+        }
+    }
+
+    #[cfg(feature = "prost")]
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode, ProtobufTransform)]
+    #[bucky_protobuf_type(prost_record::Record)]
+    #[bucky_protobuf_backend(Prost)]
+    struct Record {
+        counts: std::collections::BTreeMap<String, u64>,
+        tags: std::collections::BTreeSet<String>,
+        amount: u128,
+        addr: std::net::SocketAddr,
+        create_time: std::time::SystemTime,
+        ttl: Option<std::time::Duration>,
+    }
+
+    #[cfg(feature = "prost")]
+    #[test]
+    fn test_prost_transform_ext() {
+        let record = Record {
+            counts: [("a".to_owned(), 1), ("b".to_owned(), 2)].into_iter().collect(),
+            tags: ["x".to_owned(), "y".to_owned()].into_iter().collect(),
+            amount: u128::MAX - 1,
+            addr: "127.0.0.1:80".parse().unwrap(),
+            create_time: std::time::UNIX_EPOCH + std::time::Duration::new(100, 1),
+            ttl: Some(std::time::Duration::from_secs(60)),
+        };
+
+        let buf = record.to_vec().unwrap();
+        let (record2, _) = Record::raw_decode(&buf).unwrap();
+        assert_eq!(record, record2);
+
+        let proto: prost_record::Record = ProtobufTransform::transform(&record).unwrap();
+        assert_eq!(proto.addr, "127.0.0.1:80");
+        assert_eq!(
+            proto.create_time,
+            Some(::prost_types::Timestamp {
+                seconds: 100,
+                nanos: 1
+            })
+        );
+    }
+
+    // 只有raw codec的类型
+    #[derive(Debug, Clone, PartialEq, RawEncode, RawDecode)]
+    struct RawId {
+        kind: u8,
+        value: u64,
+    }
+
+    #[cfg(feature = "prost")]
+    mod prost_raw {
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct RawHolder {
+            #[prost(bytes = "vec", tag = "1")]
+            pub id: Vec<u8>,
+            #[prost(bytes = "vec", optional, tag = "2")]
+            pub owner: Option<Vec<u8>>,
+            #[prost(bytes = "vec", repeated, tag = "3")]
+            pub refs: Vec<Vec<u8>>,
+        }
+    }
+
+    #[cfg(feature = "prost")]
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode, ProtobufTransform)]
+    #[bucky_protobuf_type(prost_raw::RawHolder)]
+    #[bucky_protobuf_backend(Prost)]
+    struct RawHolder {
+        #[bucky_protobuf(raw)]
+        id: RawId,
+        #[bucky_protobuf(raw)]
+        owner: Option<RawId>,
+        #[bucky_protobuf(raw)]
+        refs: Vec<RawId>,
+    }
+
+    #[cfg(feature = "prost")]
+    #[test]
+    fn test_prost_raw_field() {
+        let id = RawId { kind: 1, value: 100 };
+        let holder = RawHolder {
+            id: id.clone(),
+            owner: None,
+            refs: vec![RawId { kind: 2, value: 200 }, RawId { kind: 3, value: 300 }],
+        };
+
+        let buf = holder.to_vec().unwrap();
+        let (holder2, _) = RawHolder::raw_decode(&buf).unwrap();
+        assert_eq!(holder, holder2);
+
+        let proto: prost_raw::RawHolder = ProtobufTransform::transform(&holder).unwrap();
+        assert_eq!(proto.id, id.to_vec().unwrap());
+        assert!(proto.owner.is_none());
+        assert_eq!(proto.refs.len(), 2);
+
+        let ret: CodecResult<RawHolder> = ProtobufTransform::transform(prost_raw::RawHolder {
+            id: vec![1],
+            owner: None,
+            refs: vec![],
+        });
+        assert!(ret.is_err());
+    }
+
+    #[test]
+    fn test_raw_field() {
+        let list = vec![RawId { kind: 1, value: 1 }];
+        let proto: ::protobuf::RepeatedField<Vec<u8>> = ProtobufRawField::raw_to_proto(&list).unwrap();
+        let list2: Vec<RawId> = ProtobufRawField::raw_from_proto(proto).unwrap();
+        assert_eq!(list, list2);
+
+        // bytes中多余的数据返回错误，不会被忽略
+        let mut buf = list[0].to_vec().unwrap();
+        buf.push(0);
+        let ret: CodecResult<RawId> = ProtobufRawField::raw_from_proto(buf.clone());
+        assert_eq!(ret.unwrap_err().code(), CodecErrorCode::InvalidFormat);
+        let ret: CodecResult<Option<RawId>> = ProtobufRawField::raw_from_proto(Some(buf.clone()));
+        assert_eq!(ret.unwrap_err().code(), CodecErrorCode::InvalidFormat);
+        let ret: CodecResult<Vec<RawId>> = ProtobufRawField::raw_from_proto(vec![buf]);
+        assert_eq!(ret.unwrap_err().code(), CodecErrorCode::InvalidFormat);
+    }
+
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode)]
+    #[bucky_protobuf_type(protos::EmptyContent)]
+    #[bucky_protobuf_backend(RustProtobuf)]
+    struct OldContent {
+        #[bucky_protobuf(unknown_fields)]
+        unknown: ProtobufUnknownFields,
+    }
+
+    impl ProtobufTransform<&OldContent> for protos::EmptyContent {
+        fn transform(_value: &OldContent) -> CodecResult<Self> {
+            Ok(Self::new())
+        }
+    }
+
+    impl ProtobufTransform<protos::EmptyContent> for OldContent {
+        fn transform(_value: protos::EmptyContent) -> CodecResult<Self> {
+            Ok(Self {
+                unknown: ProtobufUnknownFields::default(),
+            })
+        }
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        // name = "xxx"，以及两个新版本的字段: 3(varint)和2(bytes)
+        let mut buf = NameContent {
+            name: Some("xxx".to_owned()),
+        }
+        .to_vec()
+        .unwrap();
+        buf.extend_from_slice(&[0x18, 0x96, 0x01, 0x12, 0x02, 0x01, 0x02]);
+
+        let (content, _) = OldContent::raw_decode(&buf).unwrap();
+        assert_eq!(content.unknown.as_slice(), buf.as_slice());
+        assert_eq!(content.to_vec().unwrap(), buf);
+
+        let err = OldContent::raw_decode(&[0x0a, 0x05, 0x01]).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+    }
+
+    #[cfg(feature = "prost")]
+    mod prost_unknown {
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct RectV2 {
+            #[prost(uint32, tag = "1")]
+            pub field1: u32,
+            #[prost(uint32, tag = "2")]
+            pub field2: u32,
+            #[prost(string, tag = "3")]
+            pub color: String,
+            #[prost(uint32, repeated, tag = "4")]
+            pub marks: Vec<u32>,
+        }
+    }
+
+    #[cfg(feature = "prost")]
+    #[derive(Debug, PartialEq, ProtobufEncode, ProtobufDecode, ProtobufTransform)]
+    #[bucky_protobuf_type(prost_shape::Rect)]
+    #[bucky_protobuf_backend(Prost)]
+    struct OldRect {
+        field1: u32,
+        field2: u32,
+        #[bucky_protobuf(unknown_fields)]
+        unknown: ProtobufUnknownFields,
+    }
+
+    #[cfg(feature = "prost")]
+    #[test]
+    fn test_prost_unknown_fields() {
+        use ::prost::Message;
+
+        let rect = prost_unknown::RectV2 {
+            field1: 1,
+            field2: 0,
+            color: "red".to_owned(),
+            marks: vec![1, 2],
+        };
+        let buf = rect.encode_to_vec();
+
+        // 旧版本的结构转发后编码不变
+        let (old, _) = OldRect::raw_decode(&buf).unwrap();
+        assert_eq!((old.field1, old.field2), (1, 0));
+        assert!(!old.unknown.is_empty());
+        let buf2 = old.to_vec().unwrap();
+        assert_eq!(buf, buf2);
+        assert_eq!(prost_unknown::RectV2::decode(buf2.as_slice()).unwrap(), rect);
+
+        // 没有未知字段
+        let old = OldRect {
+            field1: 1,
+            field2: 2,
+            unknown: ProtobufUnknownFields::default(),
+        };
+        let (old2, _) = OldRect::raw_decode(&old.to_vec().unwrap()).unwrap();
+        assert_eq!(old, old2);
+
+        // 显式编码了默认值的已知字段不是未知字段，修改后重新编码不会被旧值覆盖
+        // field1 = 1, field2 = 0, color = "red"
+        let buf = [0x08, 0x01, 0x10, 0x00, 0x1a, 0x03, b'r', b'e', b'd'];
+        let (mut old, _) = OldRect::raw_decode(&buf).unwrap();
+        assert_eq!((old.field1, old.field2), (1, 0));
+        assert_eq!(old.unknown.as_slice(), &buf[4..]);
+
+        old.field2 = 5;
+        let buf2 = old.to_vec().unwrap();
+        let (old2, _) = OldRect::raw_decode(&buf2).unwrap();
+        assert_eq!((old2.field1, old2.field2), (1, 5));
+        assert_eq!(old2.unknown, old.unknown);
+        let rect = prost_unknown::RectV2::decode(buf2.as_slice()).unwrap();
+        assert_eq!((rect.field2, rect.color.as_str()), (5, "red"));
+
+        // 各种类型的已知字段: string、map、packed repeated，以及oneof成员和嵌套message
+        let buf = [0x0a, 0x00, 0x12, 0x00, 0x1a, 0x00, 0x28, 0x01];
+        let (_, unknown) = <Prost as ProtobufBackend<prost_hash::Counter>>::decode_with_unknown(&buf).unwrap();
+        assert_eq!(unknown.as_slice(), &[0x28, 0x01]);
+        let buf = [0x08, 0x00, 0x22, 0x00, 0x28, 0x01];
+        let (_, unknown) = <Prost as ProtobufBackend<prost_shape::ShapeHolder>>::decode_with_unknown(&buf).unwrap();
+        assert_eq!(unknown.as_slice(), &[0x28, 0x01]);
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        use ::protobuf::well_known_types::{Struct, Value};
+
+        // 没有结构信息时只按编号排序，同一编号的字段保持原有顺序
+        let buf = [0x18, 0x01, 0x12, 0x02, 0x08, 0x02, 0x12, 0x02, 0x08, 0x01, 0x08, 0x05];
+        let data = ProtobufMessageCodecHelper::canonicalize(&buf, &ProtobufPlainSchema).unwrap();
+        assert_eq!(data, [0x08, 0x05, 0x12, 0x02, 0x08, 0x02, 0x12, 0x02, 0x08, 0x01, 0x18, 0x01]);
+
+        // map的value中的message，以及oneof中的message，它们包含的map也会排序
+        let new_struct = |reverse: bool| {
+            let mut list: Vec<u32> = (0..16).collect();
+            if reverse {
+                list.reverse();
+            }
+            let mut inner = Struct::new();
+            for i in list.iter() {
+                let mut value = Value::new();
+                value.set_number_value(*i as f64);
+                inner.fields.insert(format!("inner{}", i), value);
+            }
+            let mut outer = Struct::new();
+            for i in list.iter() {
+                let mut value = Value::new();
+                value.set_struct_value(inner.clone());
+                outer.fields.insert(format!("outer{}", i), value);
+            }
+            outer
+        };
+        let value = new_struct(false);
+        let hash_buf = <RustProtobuf as ProtobufBackend<Struct>>::encode_canonical(&value).unwrap();
+        for i in 0..4 {
+            let value2 = new_struct(i % 2 == 1);
+            assert_eq!(<RustProtobuf as ProtobufBackend<Struct>>::encode_canonical(&value2).unwrap(), hash_buf);
+        }
+        let value2 = <RustProtobuf as ProtobufBackend<Struct>>::decode(&hash_buf).unwrap();
+        assert_eq!(value2, value);
+
+        // 嵌套message中未识别的字段也不参与hash
+        let buf = [0x0a, 0x09, 0x0a, 0x01, 0x61, 0x12, 0x04, 0x2a, 0x02, 0x48, 0x01];
+        let value = <RustProtobuf as ProtobufBackend<Struct>>::decode(&buf).unwrap();
+        let data = <RustProtobuf as ProtobufBackend<Struct>>::encode_canonical(&value).unwrap();
+        assert_eq!(data, [0x0a, 0x07, 0x0a, 0x01, 0x61, 0x12, 0x02, 0x2a, 0x00]);
+
+        // rust-protobuf的消息计算hash时不包含未识别的字段
+        let mut buf = EmptyContentV1 {
+            name: Some("xxx".to_owned()),
+        }
+        .to_vec()
+        .unwrap();
+        let len = buf.len();
+        buf.extend_from_slice(&[0x18, 0x01]);
+        let (msg, _) =
+            ProtobufMessageCodecHelper::raw_decode::<RustProtobuf, protos::EmptyContentV1>(&buf).unwrap();
+        let purpose = Some(RawEncodePurpose::Hash);
+        let size = ProtobufMessageCodecHelper::raw_measure::<RustProtobuf, _>(&msg, &purpose).unwrap();
+        assert_eq!(size, len);
+        let mut hash_buf = vec![0u8; size];
+        ProtobufMessageCodecHelper::raw_encode::<RustProtobuf, _>(&msg, &mut hash_buf, &purpose).unwrap();
+        assert_eq!(hash_buf, buf[..len]);
+    }
+
+    #[cfg(feature = "prost")]
+    mod prost_hash {
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct Counter {
+            #[prost(string, tag = "1")]
+            pub name: String,
+            #[prost(map = "string, uint64", tag = "2")]
+            pub counts: ::std::collections::HashMap<String, u64>,
+            #[prost(uint32, repeated, tag = "3")]
+            pub ids: Vec<u32>,
+        }
+
+        // 新版本的schema增加了字段
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct CounterV2 {
+            #[prost(string, tag = "1")]
+            pub name: String,
+            #[prost(map = "string, uint64", tag = "2")]
+            pub counts: ::std::collections::HashMap<String, u64>,
+            #[prost(uint32, repeated, tag = "3")]
+            pub ids: Vec<u32>,
+            #[prost(uint32, tag = "4")]
+            pub level: u32,
+            #[prost(string, tag = "5")]
+            pub label: String,
+        }
+    }
+
+    #[cfg(feature = "prost")]
+    type CounterMap = std::collections::HashMap<String, u64>;
+
+    #[cfg(feature = "prost")]
+    #[derive(Debug, Clone, PartialEq, ProtobufEncode, ProtobufDecode, ProtobufTransform)]
+    #[bucky_protobuf_type(prost_hash::Counter)]
+    #[bucky_protobuf_backend(Prost)]
+    struct Counter {
+        name: String,
+        counts: CounterMap,
+        ids: std::collections::HashSet<u32>,
+        #[bucky_protobuf(unknown_fields)]
+        unknown: ProtobufUnknownFields,
+    }
+
+    #[cfg(feature = "prost")]
+    #[test]
+    fn test_prost_hash_encode() {
+        let new_counter = |reverse: bool| {
+            let mut list: Vec<u32> = (0..64).collect();
+            if reverse {
+                list.reverse();
+            }
+            Counter {
+                name: "counter".to_owned(),
+                counts: list.iter().map(|i| (format!("key{}", i), *i as u64)).collect(),
+                ids: list.iter().cloned().collect(),
+                unknown: ProtobufUnknownFields::default(),
+            }
+        };
+
+        // 同一个值多次编码的结果完全一致
+        let counter = new_counter(false);
+        let hash_buf = counter.raw_hash_encode().unwrap();
+        for i in 0..8 {
+            let counter2 = new_counter(i % 2 == 1);
+            assert_eq!(counter, counter2);
+            assert_eq!(counter2.raw_hash_encode().unwrap(), hash_buf);
+        }
+        assert_eq!(hash_buf.len(), counter.raw_measure(&Some(RawEncodePurpose::Hash)).unwrap());
+
+        // hash编码仍然是合法的protobuf编码
+        let (counter2, _) = Counter::raw_decode(&hash_buf).unwrap();
+        assert_eq!(counter, counter2);
+
+        // 未识别的字段按编号合并到hash编码中，与使用新版本schema的节点得到相同的结果
+        let mut buf = counter.to_vec().unwrap();
+        buf.extend_from_slice(&[0x2a, 0x01, b'x', 0x20, 0x01]);
+        let (counter3, _) = Counter::raw_decode(&buf).unwrap();
+        assert_eq!(counter3.unknown.as_slice(), &[0x2a, 0x01, b'x', 0x20, 0x01]);
+        let hash_buf3 = counter3.raw_hash_encode().unwrap();
+        assert_ne!(hash_buf3, hash_buf);
+        assert_eq!(hash_buf3.len(), counter3.raw_measure(&Some(RawEncodePurpose::Hash)).unwrap());
+        assert_eq!(&hash_buf3[hash_buf.len()..], &[0x20, 0x01, 0x2a, 0x01, b'x']);
+
+        let counter_v2 = <Prost as ProtobufBackend<prost_hash::CounterV2>>::decode(&buf).unwrap();
+        assert_eq!((counter_v2.level, counter_v2.label.as_str()), (1, "x"));
+        let hash_buf_v2 = <Prost as ProtobufBackend<prost_hash::CounterV2>>::encode_canonical(&counter_v2).unwrap();
+        assert_eq!(hash_buf3, hash_buf_v2);
+    }
+
+    #[cfg(feature = "prost")]
+    mod prost_nested {
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct Inner {
+            #[prost(map = "string, uint32", tag = "1")]
+            pub counts: ::std::collections::HashMap<String, u32>,
+        }
+
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct Outer {
+            #[prost(message, optional, tag = "1")]
+            pub inner: Option<Inner>,
+            #[prost(map = "uint32, message", tag = "2")]
+            pub nested: ::std::collections::HashMap<u32, Inner>,
+            #[prost(message, repeated, tag = "3")]
+            pub list: Vec<Inner>,
+            #[prost(oneof = "Choice", tags = "4, 5")]
+            pub choice: Option<Choice>,
+        }
+
+        #[derive(Clone, PartialEq, ::prost::Oneof)]
+        pub enum Choice {
+            #[prost(message, tag = "4")]
+            Inner(Inner),
+            #[prost(string, tag = "5")]
+            Name(String),
+        }
+    }
+
+    #[cfg(feature = "prost")]
+    #[test]
+    fn test_prost_nested_hash() {
+        use prost_nested::*;
+
+        let new_inner = |reverse: bool, base: u32| {
+            let mut list: Vec<u32> = (base..base + 16).collect();
+            if reverse {
+                list.reverse();
+            }
+            Inner {
+                counts: list.iter().map(|i| (format!("key{}", i), *i)).collect(),
+            }
+        };
+        let new_outer = |reverse: bool| {
+            let mut keys: Vec<u32> = (0..8).collect();
+            if reverse {
+                keys.reverse();
+            }
+            Outer {
+                inner: Some(new_inner(reverse, 0)),
+                nested: keys.iter().map(|i| (*i, new_inner(reverse, *i * 100))).collect(),
+                list: vec![new_inner(reverse, 1000), new_inner(reverse, 2000)],
+                choice: Some(Choice::Inner(new_inner(reverse, 3000))),
+            }
+        };
+
+        // 嵌套message、map的value以及oneof中的map都会排序
+        let outer = new_outer(false);
+        let hash_buf = <Prost as ProtobufBackend<Outer>>::encode_canonical(&outer).unwrap();
+        for i in 0..4 {
+            let outer2 = new_outer(i % 2 == 1);
+            assert_eq!(<Prost as ProtobufBackend<Outer>>::encode_canonical(&outer2).unwrap(), hash_buf);
+        }
+        let outer2 = <Prost as ProtobufBackend<Outer>>::decode(&hash_buf).unwrap();
+        assert!(outer2 == outer);
+
+        // repeated message保持原有顺序
+        let mut outer3 = outer.clone();
+        outer3.list.reverse();
+        assert_ne!(<Prost as ProtobufBackend<Outer>>::encode_canonical(&outer3).unwrap(), hash_buf);
+
+        // oneof中的字符串原样输出
+        outer3.choice = Some(Choice::Name("name".to_owned()));
+        let data = <Prost as ProtobufBackend<Outer>>::encode_canonical(&outer3).unwrap();
+        let outer4 = <Prost as ProtobufBackend<Outer>>::decode(&data).unwrap();
+        assert!(outer4 == outer3);
+    }
+
+    // protobuf编码的字段放在raw结构体的中间
+    #[derive(Debug, PartialEq, RawEncode, RawDecode)]
+    struct MixedContent {
+        version: u8,
+        content: SizePrefixed<NameContent>,
+        empty: SizePrefixed<NameContent>,
+        tail: Vec<u16>,
+    }
+
+    #[test]
+    fn test_size_prefixed() {
+        let mixed = MixedContent {
+            version: 1,
+            content: SizePrefixed(NameContent {
+                name: Some("xxx".to_owned()),
+            }),
+            empty: SizePrefixed(NameContent { name: None }),
+            tail: vec![1, 2, 3],
+        };
+
+        let buf = mixed.to_vec().unwrap();
+        assert_eq!(buf.len(), mixed.raw_measure(&None).unwrap());
+        let (mixed2, remain) = MixedContent::raw_decode(&buf).unwrap();
+        assert!(remain.is_empty());
+        assert_eq!(mixed, mixed2);
+
+        // protobuf数据前面是长度前缀
+        let content = mixed.content.to_vec().unwrap();
+        assert_eq!(content[0] as usize, content.len() - 1);
+        assert_eq!(&content[1..], mixed.content.0.to_vec().unwrap().as_slice());
+    }
+
     #[test]
     fn test_helper() {
         let mut source: u32 = u32::MAX;
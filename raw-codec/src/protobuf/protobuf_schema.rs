@@ -0,0 +1,816 @@
+use crate::*;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::time::{Duration, SystemTime};
+use primitive_types::H256;
+
+// 字段在proto中的类型
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProtobufFieldType {
+    // uint32, string, bytes等基础类型
+    Scalar(&'static str),
+    // message或者enum的名字
+    Named(String),
+    Optional(Box<ProtobufFieldType>),
+    Repeated(Box<ProtobufFieldType>),
+    Map(Box<ProtobufFieldType>, Box<ProtobufFieldType>),
+    // 有数据的枚举，在所属的message中展开为以字段名命名的oneof，成员为(名字, 编号, 类型)
+    Oneof(Vec<(String, Option<u32>, ProtobufFieldType)>),
+}
+
+impl ProtobufFieldType {
+    fn type_name(&self) -> String {
+        match self {
+            Self::Scalar(name) => name.to_string(),
+            Self::Named(name) => name.clone(),
+            // Option<Vec<T>>和Option<HashMap>直接对应repeated和map
+            Self::Optional(inner) | Self::Repeated(inner) => inner.type_name(),
+            Self::Map(key, value) => format!("map<{}, {}>", key.type_name(), value.type_name()),
+            // 生成前已经检查过，oneof只能展开在message中
+            Self::Oneof(_) => unreachable!(),
+        }
+    }
+}
+
+// proto3中map的key只能是整数、bool和string
+const PROTOBUF_MAP_KEY_TYPES: &[&str] = &[
+    "int32", "int64", "uint32", "uint64", "sint32", "sint64", "fixed32", "fixed64", "sfixed32",
+    "sfixed64", "bool", "string",
+];
+
+// 字段编号的范围，19000-19999是protobuf保留的编号
+const PROTOBUF_MAX_TAG: u32 = (1 << 29) - 1;
+const PROTOBUF_RESERVED_TAGS: std::ops::RangeInclusive<u32> = 19000..=19999;
+
+fn schema_error(code: CodecErrorCode, msg: String) -> CodecError {
+    error!("{}", msg);
+    CodecError::new(code, msg)
+}
+
+// 从rust类型生成proto定义，derive(ProtobufSchema)为结构体和枚举生成实现
+pub trait ProtobufSchema {
+    fn proto_field_type() -> ProtobufFieldType;
+
+    // 把该类型依赖的message和enum定义加入builder
+    fn proto_collect(_builder: &mut ProtobufSchemaBuilder) {}
+
+    // Vec<Self>对应的类型，u8重载为bytes
+    #[doc(hidden)]
+    fn proto_repeated_type() -> ProtobufFieldType
+    where
+        Self: Sized,
+    {
+        ProtobufFieldType::Repeated(Box::new(Self::proto_field_type()))
+    }
+}
+
+// 一个message的定义
+// 字段用#[bucky_protobuf(tag = N)]指定编号，都没有指定时按加入的顺序从1开始编号，同一个message中不能混用
+pub struct ProtobufMessageSchema {
+    name: String,
+    lines: Vec<String>,
+    tags: Vec<u32>,
+    explicit_tag: Option<bool>,
+}
+
+impl ProtobufMessageSchema {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            lines: Vec::new(),
+            tags: Vec::new(),
+            explicit_tag: None,
+        }
+    }
+
+    fn next_tag(&mut self, name: &str, tag: Option<u32>) -> CodecResult<u32> {
+        let explicit = tag.is_some();
+        if *self.explicit_tag.get_or_insert(explicit) != explicit {
+            let msg = format!(
+                "field {}.{}: tag must be set on all fields of the message or on none",
+                self.name, name
+            );
+            return Err(schema_error(CodecErrorCode::InvalidParam, msg));
+        }
+
+        let tag = match tag {
+            Some(tag) => tag,
+            None => self.tags.last().map(|tag| tag + 1).unwrap_or(1),
+        };
+        if tag == 0 || tag > PROTOBUF_MAX_TAG || PROTOBUF_RESERVED_TAGS.contains(&tag) {
+            let msg = format!("field {}.{}: invalid tag {}", self.name, name, tag);
+            return Err(schema_error(CodecErrorCode::InvalidParam, msg));
+        }
+        if self.tags.contains(&tag) {
+            let msg = format!("field {}.{}: tag {} is already used", self.name, name, tag);
+            return Err(schema_error(CodecErrorCode::InvalidParam, msg));
+        }
+
+        self.tags.push(tag);
+        Ok(tag)
+    }
+
+    // 检查类型能否在proto3中表示，不能表示的返回NotSupport，避免生成无效的.proto
+    fn check_field_type(&self, name: &str, ty: &ProtobufFieldType, in_oneof: bool) -> CodecResult<()> {
+        let unsupported = |reason: &str| {
+            let msg = format!("field {}.{}: {}", self.name, name, reason);
+            Err(schema_error(CodecErrorCode::NotSupport, msg))
+        };
+        let is_element = |ty: &ProtobufFieldType| {
+            matches!(ty, ProtobufFieldType::Scalar(_) | ProtobufFieldType::Named(_))
+        };
+
+        match ty {
+            ProtobufFieldType::Scalar(_) | ProtobufFieldType::Named(_) => Ok(()),
+            ProtobufFieldType::Optional(_) | ProtobufFieldType::Repeated(_) | ProtobufFieldType::Map(..)
+                if in_oneof =>
+            {
+                unsupported("optional, repeated and map fields are not allowed in a oneof")
+            }
+            ProtobufFieldType::Optional(inner) => match inner.as_ref() {
+                ProtobufFieldType::Oneof(_) => unsupported("enum with data can't be optional"),
+                ProtobufFieldType::Optional(_) => unsupported("nested optional is not supported"),
+                ProtobufFieldType::Repeated(_) | ProtobufFieldType::Map(..) => {
+                    self.check_field_type(name, inner, in_oneof)
+                }
+                _ => Ok(()),
+            },
+            ProtobufFieldType::Repeated(inner) => match inner.as_ref() {
+                ProtobufFieldType::Oneof(_) => unsupported("enum with data can't be repeated"),
+                inner if is_element(inner) => Ok(()),
+                _ => unsupported("repeated field must be a scalar or a message"),
+            },
+            ProtobufFieldType::Map(key, value) => {
+                match key.as_ref() {
+                    ProtobufFieldType::Scalar(key) if PROTOBUF_MAP_KEY_TYPES.contains(key) => {}
+                    _ => return unsupported("map key must be an integer, bool or string"),
+                }
+                if !is_element(value) {
+                    return unsupported("map value must be a scalar or a message");
+                }
+                Ok(())
+            }
+            ProtobufFieldType::Oneof(_) if in_oneof => unsupported("nested oneof is not supported"),
+            ProtobufFieldType::Oneof(_) => Ok(()),
+        }
+    }
+
+    fn field_line(&mut self, name: &str, tag: Option<u32>, ty: &ProtobufFieldType, in_oneof: bool) -> CodecResult<String> {
+        self.check_field_type(name, ty, in_oneof)?;
+        let tag = self.next_tag(name, tag)?;
+        let label = match ty {
+            ProtobufFieldType::Optional(inner) => match inner.as_ref() {
+                ProtobufFieldType::Repeated(_) => "repeated ",
+                ProtobufFieldType::Map(..) => "",
+                _ => "optional ",
+            },
+            ProtobufFieldType::Repeated(_) => "repeated ",
+            _ => "",
+        };
+
+        Ok(format!("{}{} {} = {};", label, ty.type_name(), name, tag))
+    }
+
+    // tag为None时按顺序编号，oneof字段的编号在各个成员上指定
+    pub fn field(&mut self, name: &str, tag: Option<u32>, ty: ProtobufFieldType) -> CodecResult<&mut Self> {
+        if let ProtobufFieldType::Oneof(members) = &ty {
+            if tag.is_some() {
+                let msg = format!("field {}.{}: set tag on the variants of the oneof", self.name, name);
+                return Err(schema_error(CodecErrorCode::InvalidParam, msg));
+            }
+
+            self.lines.push(format!("  oneof {} {{", name));
+            for (member, tag, ty) in members {
+                let line = self.field_line(member, *tag, ty, true)?;
+                self.lines.push(format!("    {}", line));
+            }
+            self.lines.push("  }".to_owned());
+        } else {
+            let line = self.field_line(name, tag, &ty, false)?;
+            self.lines.push(format!("  {}", line));
+        }
+
+        Ok(self)
+    }
+
+    pub fn build(&self) -> String {
+        let mut ret = format!("message {} {{\n", self.name);
+        for line in &self.lines {
+            ret.push_str(line);
+            ret.push('\n');
+        }
+        ret.push_str("}\n");
+        ret
+    }
+}
+
+// 收集所有用到的message和enum，生成完整的.proto文件
+// 生成定义时的错误保存下来，在build时返回
+pub struct ProtobufSchemaBuilder {
+    package: Option<String>,
+    imports: Vec<String>,
+    // (名字, 生成该定义的rust类型, 定义)
+    definitions: Vec<(String, String, String)>,
+    error: Option<CodecError>,
+}
+
+impl Default for ProtobufSchemaBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProtobufSchemaBuilder {
+    pub fn new() -> Self {
+        Self {
+            package: None,
+            imports: Vec::new(),
+            definitions: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn package(&mut self, package: &str) -> &mut Self {
+        self.package = Some(package.to_owned());
+        self
+    }
+
+    pub fn add<T: ProtobufSchema>(&mut self) -> &mut Self {
+        T::proto_collect(self);
+        self
+    }
+
+    // 引用外部的proto文件，比如google/protobuf/timestamp.proto
+    pub fn add_import(&mut self, path: &str) -> &mut Self {
+        if !self.imports.iter().any(|p| p == path) {
+            self.imports.push(path.to_owned());
+        }
+        self
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.definitions.iter().any(|(n, _, _)| n == name)
+    }
+
+    // 同一个类型的定义只会加入一次，先占位再生成，支持递归引用
+    // owner为生成该定义的rust类型(std::any::type_name)，不同的类型使用同一个名字时返回InvalidParam
+    pub fn add_definition(&mut self, name: &str, owner: &str, gen: impl FnOnce(&mut Self) -> CodecResult<String>) {
+        if let Some((_, exists, _)) = self.definitions.iter().find(|(n, _, _)| n == name) {
+            if exists != owner {
+                let msg = format!("definition {} is used by both {} and {}", name, exists, owner);
+                self.set_error(schema_error(CodecErrorCode::InvalidParam, msg));
+            }
+            return;
+        }
+
+        let index = self.definitions.len();
+        self.definitions.push((name.to_owned(), owner.to_owned(), String::new()));
+        match gen(self) {
+            Ok(def) => self.definitions[index].2 = def,
+            Err(e) => self.set_error(e),
+        }
+    }
+
+    fn set_error(&mut self, e: CodecError) {
+        if self.error.is_none() {
+            self.error = Some(e);
+        }
+    }
+
+    // proto3要求第一个枚举值为0，有0值时放在最前面
+    pub fn add_enum(&mut self, name: &str, owner: &str, values: &[(&str, i32)]) {
+        self.add_definition(name, owner, |_| {
+            let zero = match values.iter().find(|(_, value)| *value == 0) {
+                Some(zero) => zero,
+                None => {
+                    let msg = format!("enum {}: proto3 enum must have a value of 0", name);
+                    return Err(schema_error(CodecErrorCode::NotSupport, msg));
+                }
+            };
+
+            let mut ret = format!("enum {} {{\n", name);
+            for (value_name, value) in std::iter::once(zero).chain(values.iter().filter(|(_, value)| *value != 0)) {
+                ret.push_str(&format!("  {} = {};\n", value_name, value));
+            }
+            ret.push_str("}\n");
+            Ok(ret)
+        });
+    }
+
+    pub fn build(&self) -> CodecResult<String> {
+        if let Some(e) = &self.error {
+            return Err(e.clone());
+        }
+
+        let mut ret = "syntax = \"proto3\";\n".to_owned();
+        if let Some(package) = &self.package {
+            ret.push_str(&format!("\npackage {};\n", package));
+        }
+        if !self.imports.is_empty() {
+            ret.push('\n');
+            for path in &self.imports {
+                ret.push_str(&format!("import \"{}\";\n", path));
+            }
+        }
+        for (_, _, def) in &self.definitions {
+            ret.push('\n');
+            ret.push_str(def);
+        }
+        Ok(ret)
+    }
+
+    pub fn write_to(&self, path: impl AsRef<std::path::Path>) -> CodecResult<()> {
+        let path = path.as_ref();
+        std::fs::write(path, self.build()?).map_err(|e| {
+            let msg = format!("write proto file {} error! {}", path.display(), e);
+            error!("{}", msg);
+
+            CodecError::new(CodecErrorCode::IoError, msg)
+        })
+    }
+}
+
+macro_rules! protobuf_schema_scalar {
+    ($ty:ty, $name:expr) => {
+        impl ProtobufSchema for $ty {
+            fn proto_field_type() -> ProtobufFieldType {
+                ProtobufFieldType::Scalar($name)
+            }
+        }
+    };
+}
+
+protobuf_schema_scalar!(i8, "int32");
+protobuf_schema_scalar!(i16, "int32");
+protobuf_schema_scalar!(i32, "int32");
+protobuf_schema_scalar!(i64, "int64");
+protobuf_schema_scalar!(u16, "uint32");
+protobuf_schema_scalar!(u32, "uint32");
+protobuf_schema_scalar!(u64, "uint64");
+protobuf_schema_scalar!(f32, "float");
+protobuf_schema_scalar!(f64, "double");
+protobuf_schema_scalar!(bool, "bool");
+protobuf_schema_scalar!(String, "string");
+
+impl ProtobufSchema for u8 {
+    fn proto_field_type() -> ProtobufFieldType {
+        ProtobufFieldType::Scalar("uint32")
+    }
+
+    fn proto_repeated_type() -> ProtobufFieldType {
+        ProtobufFieldType::Scalar("bytes")
+    }
+}
+
+impl<T: ProtobufSchema> ProtobufSchema for Vec<T> {
+    fn proto_field_type() -> ProtobufFieldType {
+        T::proto_repeated_type()
+    }
+
+    fn proto_collect(builder: &mut ProtobufSchemaBuilder) {
+        T::proto_collect(builder)
+    }
+}
+
+impl<T: ProtobufSchema> ProtobufSchema for Option<T> {
+    fn proto_field_type() -> ProtobufFieldType {
+        ProtobufFieldType::Optional(Box::new(T::proto_field_type()))
+    }
+
+    fn proto_collect(builder: &mut ProtobufSchemaBuilder) {
+        T::proto_collect(builder)
+    }
+}
+
+impl<K: ProtobufSchema, V: ProtobufSchema, S> ProtobufSchema for HashMap<K, V, S> {
+    fn proto_field_type() -> ProtobufFieldType {
+        ProtobufFieldType::Map(
+            Box::new(K::proto_field_type()),
+            Box::new(V::proto_field_type()),
+        )
+    }
+
+    fn proto_collect(builder: &mut ProtobufSchemaBuilder) {
+        K::proto_collect(builder);
+        V::proto_collect(builder);
+    }
+}
+
+impl<K: ProtobufSchema, V: ProtobufSchema> ProtobufSchema for BTreeMap<K, V> {
+    fn proto_field_type() -> ProtobufFieldType {
+        ProtobufFieldType::Map(
+            Box::new(K::proto_field_type()),
+            Box::new(V::proto_field_type()),
+        )
+    }
+
+    fn proto_collect(builder: &mut ProtobufSchemaBuilder) {
+        K::proto_collect(builder);
+        V::proto_collect(builder);
+    }
+}
+
+impl<T: ProtobufSchema, S> ProtobufSchema for HashSet<T, S> {
+    fn proto_field_type() -> ProtobufFieldType {
+        ProtobufFieldType::Repeated(Box::new(T::proto_field_type()))
+    }
+
+    fn proto_collect(builder: &mut ProtobufSchemaBuilder) {
+        T::proto_collect(builder)
+    }
+}
+
+impl<T: ProtobufSchema> ProtobufSchema for BTreeSet<T> {
+    fn proto_field_type() -> ProtobufFieldType {
+        ProtobufFieldType::Repeated(Box::new(T::proto_field_type()))
+    }
+
+    fn proto_collect(builder: &mut ProtobufSchemaBuilder) {
+        T::proto_collect(builder)
+    }
+}
+
+protobuf_schema_scalar!(i128, "bytes");
+protobuf_schema_scalar!(u128, "bytes");
+protobuf_schema_scalar!(H256, "bytes");
+protobuf_schema_scalar!(IpAddr, "string");
+protobuf_schema_scalar!(Ipv4Addr, "string");
+protobuf_schema_scalar!(Ipv6Addr, "string");
+protobuf_schema_scalar!(SocketAddr, "string");
+protobuf_schema_scalar!(SocketAddrV4, "string");
+protobuf_schema_scalar!(SocketAddrV6, "string");
+
+impl ProtobufSchema for Duration {
+    fn proto_field_type() -> ProtobufFieldType {
+        ProtobufFieldType::Named("google.protobuf.Duration".to_owned())
+    }
+
+    fn proto_collect(builder: &mut ProtobufSchemaBuilder) {
+        builder.add_import("google/protobuf/duration.proto");
+    }
+}
+
+impl ProtobufSchema for SystemTime {
+    fn proto_field_type() -> ProtobufFieldType {
+        ProtobufFieldType::Named("google.protobuf.Timestamp".to_owned())
+    }
+
+    fn proto_collect(builder: &mut ProtobufSchemaBuilder) {
+        builder.add_import("google/protobuf/timestamp.proto");
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use crate as bucky_raw_codec;
+    use bucky_raw_codec_derive::*;
+    use std::collections::HashMap;
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::TestSubStruct)]
+    struct TestSubStruct {
+        data: Vec<u8>,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(i32)]
+    enum TestLevel {
+        Low,
+        High,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Color)]
+    enum TestColor {
+        Unspecified,
+        #[bucky_protobuf(rename = "LightRed")]
+        Red = 2,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::test_struct::TestEnum)]
+    enum TestEnum {
+        Test1(i32),
+        #[bucky_protobuf_type(crate::proto_rs::TestPair)]
+        Test2(u8, String),
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::TestStruct)]
+    struct TestStruct {
+        string_type: String,
+        u16_type: u16,
+        #[bucky_protobuf(rename = "self_type")]
+        sub: TestSubStruct,
+        option_type: Option<TestSubStruct>,
+        list: Vec<String>,
+        map: HashMap<String, u64>,
+        level: TestLevel,
+        color: TestColor,
+        #[bucky_protobuf(skip)]
+        cache: Option<String>,
+        test_enum: TestEnum,
+    }
+
+    #[test]
+    fn test_schema() {
+        let proto = ProtobufSchemaBuilder::new()
+            .package("test")
+            .add::<TestStruct>()
+            .build()
+            .unwrap();
+
+        let expect = r#"syntax = "proto3";
+
+package test;
+
+message TestStruct {
+  string string_type = 1;
+  uint32 u16_type = 2;
+  TestSubStruct self_type = 3;
+  optional TestSubStruct option_type = 4;
+  repeated string list = 5;
+  map<string, uint64> map = 6;
+  int32 level = 7;
+  Color color = 8;
+  oneof test_enum {
+    int32 test1 = 9;
+    TestPair test2 = 10;
+  }
+}
+
+message TestSubStruct {
+  bytes data = 1;
+}
+
+enum Color {
+  COLOR_UNSPECIFIED = 0;
+  COLOR_LIGHT_RED = 2;
+}
+
+message TestPair {
+  uint32 field1 = 1;
+  string field2 = 2;
+}
+"#;
+        assert_eq!(proto, expect);
+        check_protoc("test_schema.proto", &proto);
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Record)]
+    struct Record {
+        tags: std::collections::BTreeSet<String>,
+        amount: u128,
+        create_time: std::time::SystemTime,
+        ttl: Option<std::time::Duration>,
+        #[bucky_protobuf(raw)]
+        owner: Option<TestRawId>,
+        #[bucky_protobuf(raw)]
+        refs: Vec<TestRawId>,
+    }
+
+    #[derive(RawEncode, RawDecode)]
+    struct TestRawId {
+        value: u64,
+    }
+
+    #[test]
+    fn test_schema_ext() {
+        let proto = ProtobufSchemaBuilder::new().add::<Record>().build().unwrap();
+
+        let expect = r#"syntax = "proto3";
+
+import "google/protobuf/timestamp.proto";
+import "google/protobuf/duration.proto";
+
+message Record {
+  repeated string tags = 1;
+  bytes amount = 2;
+  google.protobuf.Timestamp create_time = 3;
+  optional google.protobuf.Duration ttl = 4;
+  optional bytes owner = 5;
+  repeated bytes refs = 6;
+}
+"#;
+        assert_eq!(proto, expect);
+        check_protoc("test_schema_ext.proto", &proto);
+    }
+
+    // 用vendored的protoc检查生成的.proto是否有效
+    fn check_protoc(name: &str, proto: &str) {
+        let dir = std::env::temp_dir().join(format!("bucky-raw-codec-schema-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(name), proto).unwrap();
+
+        let output = std::process::Command::new(protoc_bin_vendored::protoc_bin_path().unwrap())
+            .arg("-I")
+            .arg(&dir)
+            .arg("-I")
+            .arg(protoc_bin_vendored::include_path().unwrap())
+            .arg("-o")
+            .arg(dir.join(format!("{}.pb", name)))
+            .arg(dir.join(name))
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "protoc failed: {}\n{}",
+            String::from_utf8_lossy(&output.stderr),
+            proto
+        );
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::TaggedShape)]
+    enum TaggedShape {
+        #[bucky_protobuf(tag = 5)]
+        Circle(u32),
+        #[bucky_protobuf(tag = 7)]
+        Label(String),
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Kind)]
+    enum Kind {
+        First = 1,
+        Second = 0,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Tagged)]
+    struct Tagged {
+        #[bucky_protobuf(tag = 3)]
+        name: String,
+        #[bucky_protobuf(tag = 1)]
+        id: u64,
+        shape: TaggedShape,
+        #[bucky_protobuf(tag = 20000)]
+        kind: Kind,
+    }
+
+    #[test]
+    fn test_schema_tag() {
+        let proto = ProtobufSchemaBuilder::new().add::<Tagged>().build().unwrap();
+
+        let expect = r#"syntax = "proto3";
+
+message Tagged {
+  string name = 3;
+  uint64 id = 1;
+  oneof shape {
+    uint32 circle = 5;
+    string label = 7;
+  }
+  Kind kind = 20000;
+}
+
+enum Kind {
+  KIND_SECOND = 0;
+  KIND_FIRST = 1;
+}
+"#;
+        assert_eq!(proto, expect);
+        check_protoc("test_schema_tag.proto", &proto);
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Fruit)]
+    enum Fruit {
+        Unknown,
+        Apple,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Vegetable)]
+    enum Vegetable {
+        Unknown,
+        Carrot,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Basket)]
+    struct Basket {
+        fruit: Fruit,
+        vegetable: Vegetable,
+    }
+
+    // 同一个package中不同枚举的同名值需要加上枚举名的前缀
+    #[test]
+    fn test_schema_enum_prefix() {
+        let proto = ProtobufSchemaBuilder::new().add::<Basket>().build().unwrap();
+
+        let expect = r#"syntax = "proto3";
+
+message Basket {
+  Fruit fruit = 1;
+  Vegetable vegetable = 2;
+}
+
+enum Fruit {
+  FRUIT_UNKNOWN = 0;
+  FRUIT_APPLE = 1;
+}
+
+enum Vegetable {
+  VEGETABLE_UNKNOWN = 0;
+  VEGETABLE_CARROT = 1;
+}
+"#;
+        assert_eq!(proto, expect);
+        check_protoc("test_schema_enum_prefix.proto", &proto);
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::ListShape)]
+    enum ListShape {
+        List(Vec<String>),
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::NoZero)]
+    enum NoZero {
+        A = 1,
+        B,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Invalid)]
+    struct RepeatedInOneof {
+        shape: ListShape,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Invalid)]
+    struct OptionalOneof {
+        shape: Option<TaggedShape>,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Invalid)]
+    struct RepeatedOneof {
+        shapes: Vec<TaggedShape>,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Invalid)]
+    struct NoZeroEnum {
+        value: NoZero,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Invalid)]
+    struct MixedTag {
+        #[bucky_protobuf(tag = 1)]
+        id: u64,
+        name: String,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Invalid)]
+    struct DuplicateTag {
+        #[bucky_protobuf(tag = 2)]
+        id: u64,
+        #[bucky_protobuf(tag = 2)]
+        name: String,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Invalid)]
+    struct BytesKey {
+        map: std::collections::BTreeMap<Vec<u8>, u32>,
+    }
+
+    // 与TestSubStruct使用同一个message名字
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::TestSubStruct)]
+    struct OtherSubStruct {
+        name: String,
+    }
+
+    #[derive(ProtobufSchema)]
+    #[bucky_protobuf_type(crate::proto_rs::Invalid)]
+    struct SameName {
+        sub: TestSubStruct,
+        other: OtherSubStruct,
+    }
+
+    #[test]
+    fn test_schema_invalid() {
+        fn build<T: ProtobufSchema>() -> CodecErrorCode {
+            ProtobufSchemaBuilder::new().add::<T>().build().unwrap_err().code()
+        }
+
+        assert_eq!(build::<RepeatedInOneof>(), CodecErrorCode::NotSupport);
+        assert_eq!(build::<OptionalOneof>(), CodecErrorCode::NotSupport);
+        assert_eq!(build::<RepeatedOneof>(), CodecErrorCode::NotSupport);
+        assert_eq!(build::<NoZeroEnum>(), CodecErrorCode::NotSupport);
+        assert_eq!(build::<BytesKey>(), CodecErrorCode::NotSupport);
+        assert_eq!(build::<MixedTag>(), CodecErrorCode::InvalidParam);
+        assert_eq!(build::<DuplicateTag>(), CodecErrorCode::InvalidParam);
+        assert_eq!(build::<SameName>(), CodecErrorCode::InvalidParam);
+    }
+}
+
@@ -1,7 +1,11 @@
 mod raw_codec;
+mod raw_hash;
+mod raw_time;
 mod raw_types;
 mod raw_util;
 
 pub use raw_codec::*;
+pub use raw_hash::*;
 pub use raw_types::*;
 pub use raw_util::*;
+pub(crate) use raw_time::{system_time_from_timestamp, system_time_to_timestamp, NANOS_PER_SEC};
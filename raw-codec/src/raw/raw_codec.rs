@@ -1,6 +1,7 @@
 use crate::*;
 
 use std::any::Any;
+use std::cell::Cell;
 use crate::error::CodecResult;
 
 //能静态确定编码后大小
@@ -16,19 +17,31 @@ pub trait RawFixedBytes {
     }
 }
 
+// 后续可能增加新的用途，外部match时需要处理通配分支
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum RawEncodePurpose {
     // 默认值，为序列化而编码，需要是完整编码
     Serialize,
 
     // 为计算hash而编码
     Hash,
+
+    // 为签名而编码
+    Sign,
+
+    // 应用自定义的编码用途，可以用来定义结构体的不同投影
+    Custom(u8),
 }
 
 #[derive(Debug, Clone)]
 pub struct RawDecodeOption {
     pub version: u8,
     pub format: u8,
+
+    // 严格模式，开启后raw_decode_with_option要求buf被完整消费，否则返回InvalidFormat
+    // 只对传入opt的这一层解码生效，自定义的raw_decode_with_option解码内部字段时不要传递开启了strict的opt
+    pub strict: bool,
 }
 
 impl Default for RawDecodeOption {
@@ -36,10 +49,97 @@ impl Default for RawDecodeOption {
         Self {
             version: 0,
             format: OBJECT_CONTENT_CODEC_FORMAT_RAW,
+            strict: false,
         }
     }
 }
 
+// 规范解码模式，开启后解码器会拒绝非最短的变长size、乱序或者重复的容器key、非0/1的bool等
+// 保证decode(bytes)成功时encode(value) == bytes，避免同一个对象存在多种编码导致hash不一致
+// 只在decode_canonical等调用的作用域内生效，不影响其它调用
+thread_local! {
+    static CANONICAL_DECODE_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn is_canonical_decode() -> bool {
+    CANONICAL_DECODE_DEPTH.with(|v| v.get() > 0)
+}
+
+// 在当前线程的作用域内开启规范解码模式，drop后恢复
+pub struct CanonicalDecodeGuard(());
+
+impl CanonicalDecodeGuard {
+    pub fn new() -> Self {
+        CANONICAL_DECODE_DEPTH.with(|v| v.set(v.get() + 1));
+        Self(())
+    }
+}
+
+impl Default for CanonicalDecodeGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for CanonicalDecodeGuard {
+    fn drop(&mut self) {
+        CANONICAL_DECODE_DEPTH.with(|v| v.set(v.get() - 1));
+    }
+}
+
+pub fn non_canonical_error(msg: String) -> CodecError {
+    error!("{}", msg);
+    CodecError::new(CodecErrorCode::InvalidFormat, msg)
+}
+
+// 跨平台size模式，开启后容器长度(USize)按32bit平台的usize范围做检查
+// 64bit平台编码时就会拒绝32bit/wasm32平台无法解码的长度，解码行为也与32bit平台一致
+// 只在PortableSizeGuard的作用域内生效，不影响其它线程
+thread_local! {
+    static PORTABLE_SIZE_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn is_portable_size() -> bool {
+    PORTABLE_SIZE_DEPTH.with(|v| v.get() > 0)
+}
+
+// 在当前线程的作用域内开启跨平台size模式，drop后恢复
+pub struct PortableSizeGuard(());
+
+impl PortableSizeGuard {
+    pub fn new() -> Self {
+        PORTABLE_SIZE_DEPTH.with(|v| v.set(v.get() + 1));
+        Self(())
+    }
+}
+
+impl Default for PortableSizeGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for PortableSizeGuard {
+    fn drop(&mut self) {
+        PORTABLE_SIZE_DEPTH.with(|v| v.set(v.get() - 1));
+    }
+}
+
+// 检查解码后是否还有剩余数据
+pub fn check_no_trailing_data<T>(remain: &[u8]) -> CodecResult<()> {
+    if !remain.is_empty() {
+        let msg = format!(
+            "decode {} got {} trailing bytes",
+            std::any::type_name::<T>(),
+            remain.len()
+        );
+        error!("{}", msg);
+        return Err(CodecError::new(CodecErrorCode::InvalidFormat, msg));
+    }
+
+    Ok(())
+}
+
 //编码
 pub trait RawEncode {
     fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize>;
@@ -78,6 +178,32 @@ pub trait RawEncode {
 
         Ok(buf)
     }
+
+    // 流式编码，默认实现整体编码后写入，容器等可能很大的对象可以覆盖此方法逐个元素写入
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        writer.write_encoded(self, purpose)
+    }
+
+    // 定长数组[T; N]的编码，逐个元素编码；u8等可以覆盖为整体拷贝
+    #[doc(hidden)]
+    fn raw_encode_array<'a, const N: usize>(
+        array: &[Self; N],
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]>
+    where
+        Self: Sized,
+    {
+        let mut buf = buf;
+        for e in array {
+            buf = e.raw_encode(buf, purpose)?;
+        }
+        Ok(buf)
+    }
 }
 
 pub trait RawEncodeWithContext<Context> {
@@ -109,12 +235,57 @@ pub trait RawDecode<'de>: Sized {
     // 不带opt的解码，默认一般实现此方法
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])>;
 
-    // 带opt的解码，如果想使用版本等高级解码特性，需要实现此方法
+    // 带opt的解码，如果想使用版本等高级解码特性，需要实现此方法，并且处理opt.strict
     fn raw_decode_with_option(
         buf: &'de [u8],
-        _opt: &RawDecodeOption,
+        opt: &RawDecodeOption,
     ) -> CodecResult<(Self, &'de [u8])> {
-        Self::raw_decode(buf)
+        let (obj, remain) = Self::raw_decode(buf)?;
+        if opt.strict {
+            check_no_trailing_data::<Self>(remain)?;
+        }
+        Ok((obj, remain))
+    }
+
+    // 严格解码，buf必须被完整消费，否则返回InvalidFormat
+    fn decode_exact(buf: &'de [u8]) -> CodecResult<Self> {
+        let (obj, remain) = Self::raw_decode(buf)?;
+        check_no_trailing_data::<Self>(remain)?;
+        Ok(obj)
+    }
+
+    fn decode_exact_with_option(buf: &'de [u8], opt: &RawDecodeOption) -> CodecResult<Self> {
+        let (obj, remain) = Self::raw_decode_with_option(buf, opt)?;
+        check_no_trailing_data::<Self>(remain)?;
+        Ok(obj)
+    }
+
+    // 规范解码，要求buf是对象唯一的规范编码，并且被完整消费
+    fn decode_canonical(buf: &'de [u8]) -> CodecResult<Self> {
+        let _guard = CanonicalDecodeGuard::new();
+        Self::decode_exact(buf)
+    }
+
+    fn decode_canonical_with_option(buf: &'de [u8], opt: &RawDecodeOption) -> CodecResult<Self> {
+        let _guard = CanonicalDecodeGuard::new();
+        Self::decode_exact_with_option(buf, opt)
+    }
+
+    // 定长数组[T; N]的解码，逐个元素解码；u8等可以覆盖为整体拷贝
+    #[doc(hidden)]
+    fn raw_decode_array<const N: usize>(buf: &'de [u8]) -> CodecResult<([Self; N], &'de [u8])> {
+        let mut list = Vec::with_capacity(N);
+        let mut buf = buf;
+        for _ in 0..N {
+            let (e, remain) = Self::raw_decode(buf)?;
+            list.push(e);
+            buf = remain;
+        }
+
+        match list.try_into() {
+            Ok(array) => Ok((array, buf)),
+            Err(_) => unreachable!(),
+        }
     }
 }
 
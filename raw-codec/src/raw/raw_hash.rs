@@ -0,0 +1,269 @@
+use crate::*;
+
+pub use primitive_types::H256;
+pub use sha2::digest;
+use sha2::digest::{Digest, Output, Update};
+use sha2::Sha256;
+
+const DIGEST_WRITER_BUFFER_SIZE: usize = 1024;
+
+// hash编码的流式输出，小对象先缓存到固定大小的buffer，满了以后再批量写入hasher
+pub struct RawDigestWriter<'a> {
+    hasher: &'a mut dyn Update,
+    buf: [u8; DIGEST_WRITER_BUFFER_SIZE],
+    len: usize,
+}
+
+impl<'a> RawDigestWriter<'a> {
+    pub fn new(hasher: &'a mut dyn Update) -> Self {
+        Self {
+            hasher,
+            buf: [0u8; DIGEST_WRITER_BUFFER_SIZE],
+            len: 0,
+        }
+    }
+
+    // 写入已经编码好的数据
+    pub fn write(&mut self, data: &[u8]) {
+        if self.len + data.len() > DIGEST_WRITER_BUFFER_SIZE {
+            self.flush();
+        }
+
+        if data.len() >= DIGEST_WRITER_BUFFER_SIZE {
+            self.hasher.update(data);
+        } else {
+            self.buf[self.len..self.len + data.len()].copy_from_slice(data);
+            self.len += data.len();
+        }
+    }
+
+    // 写入对象的完整编码，能放进内部buffer的直接编码到buffer，否则使用临时buffer
+    pub fn write_encoded<T: RawEncode + ?Sized>(
+        &mut self,
+        value: &T,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        let size = value.raw_measure(purpose)?;
+        if size > DIGEST_WRITER_BUFFER_SIZE {
+            let mut buf = vec![0u8; size];
+            let remain = value.raw_encode(&mut buf, purpose)?;
+            let used = size - remain.len();
+            self.flush();
+            self.hasher.update(&buf[..used]);
+            return Ok(());
+        }
+
+        if self.len + size > DIGEST_WRITER_BUFFER_SIZE {
+            self.flush();
+        }
+        let remain = value.raw_encode(&mut self.buf[self.len..self.len + size], purpose)?;
+        self.len += size - remain.len();
+
+        Ok(())
+    }
+
+    pub fn flush(&mut self) {
+        if self.len > 0 {
+            self.hasher.update(&self.buf[..self.len]);
+            self.len = 0;
+        }
+    }
+}
+
+impl Drop for RawDigestWriter<'_> {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
+
+// 基于RawEncodePurpose::Hash编码计算hash，编码结果直接流式写入hasher，不需要分配完整的buffer
+pub trait RawHash {
+    fn raw_hash<D: Digest + Update>(&self) -> CodecResult<Output<D>>;
+
+    fn raw_hash_sha256(&self) -> CodecResult<H256>;
+}
+
+impl<T: RawEncode + ?Sized> RawHash for T {
+    fn raw_hash<D: Digest + Update>(&self) -> CodecResult<Output<D>> {
+        let mut hasher = D::new();
+        {
+            let mut writer = RawDigestWriter::new(&mut hasher);
+            self.raw_digest(&mut writer, &Some(RawEncodePurpose::Hash))?;
+        }
+
+        Ok(hasher.finalize())
+    }
+
+    fn raw_hash_sha256(&self) -> CodecResult<H256> {
+        let hash = self.raw_hash::<Sha256>()?;
+        Ok(H256::from_slice(&hash))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use sha2::{Digest, Sha256};
+    use std::collections::{BTreeMap, HashMap};
+
+    fn check_hash<T: RawEncode>(v: &T) {
+        let buf = v.raw_hash_encode().unwrap();
+        let hash = H256::from_slice(&Sha256::digest(&buf));
+        assert_eq!(v.raw_hash_sha256().unwrap(), hash);
+    }
+
+    #[test]
+    fn test_raw_hash() {
+        check_hash(&100u32);
+        check_hash(&"hello".to_owned());
+        check_hash(&vec![1u8; 5000]);
+        check_hash(&vec!["test".to_owned(); 1000]);
+        check_hash(&Some(vec![0x12345678u32; 3000]));
+
+        let mut map = HashMap::new();
+        for i in 0..1000u32 {
+            map.insert(i, vec![i as u8; i as usize]);
+        }
+        check_hash(&map);
+
+        let map: BTreeMap<String, u64> = (0..100).map(|i| (i.to_string(), i)).collect();
+        check_hash(&map);
+    }
+
+    mod derive {
+        use super::check_hash;
+        use crate as bucky_raw_codec;
+        use crate::*;
+        use bucky_raw_codec_derive::*;
+
+        #[derive(RawEncode, RawDecode)]
+        #[bucky(hash_id)]
+        struct TestStruct {
+            id: u32,
+            data: Vec<u8>,
+            name: Option<String>,
+            list: Vec<TestEnum>,
+        }
+
+        #[derive(RawEncode, RawDecode)]
+        enum TestEnum {
+            Unit,
+            Newtype(Vec<u8>),
+            Tuple(u8, String),
+            Struct { a: u64, b: Vec<u16> },
+        }
+
+        #[derive(RawEncode, RawDecode)]
+        struct TestTuple(u16, Vec<u8>);
+
+        #[test]
+        fn test_derive_raw_hash() {
+            let v = TestStruct {
+                id: 1,
+                data: vec![7u8; 3000],
+                name: Some("test".to_owned()),
+                list: vec![
+                    TestEnum::Unit,
+                    TestEnum::Newtype(vec![1, 2, 3]),
+                    TestEnum::Tuple(1, "tuple".to_owned()),
+                    TestEnum::Struct { a: 100, b: vec![1000; 1000] },
+                ],
+            };
+            check_hash(&v);
+            assert_eq!(v.hash_id().unwrap(), v.raw_hash_sha256().unwrap());
+
+            check_hash(&TestTuple(1, vec![2u8; 2000]));
+        }
+
+        #[derive(RawEncode, RawDecode, Debug, PartialEq)]
+        #[bucky(optimize_option)]
+        struct TestPurpose {
+            id: u32,
+            #[bucky(skip_hash)]
+            nonce: Option<u64>,
+            #[bucky(skip_purpose(Sign, Custom(1)))]
+            signs: Vec<u8>,
+            #[bucky(only_purpose(Serialize))]
+            cache: Option<String>,
+            #[bucky(only_purpose(Hash, Sign))]
+            extra: u16,
+            desc: Option<String>,
+        }
+
+        #[derive(RawEncode, RawDecode, Debug, PartialEq)]
+        enum TestPurposeEnum {
+            Struct {
+                a: u8,
+                #[bucky(skip_hash)]
+                b: u32,
+            },
+        }
+
+        fn encode_with<T: RawEncode>(v: &T, purpose: RawEncodePurpose) -> Vec<u8> {
+            let purpose = Some(purpose);
+            let size = v.raw_measure(&purpose).unwrap();
+            let mut buf = vec![0u8; size];
+            let remain = v.raw_encode(&mut buf, &purpose).unwrap();
+            assert_eq!(remain.len(), 0);
+            buf
+        }
+
+        #[test]
+        fn test_derive_purpose() {
+            let v = TestPurpose {
+                id: 1,
+                nonce: Some(2),
+                signs: vec![3u8; 10],
+                cache: Some("cache".to_owned()),
+                extra: 4,
+                desc: Some("desc".to_owned()),
+            };
+
+            let buf = encode_with(&v, RawEncodePurpose::Serialize);
+            assert_eq!(buf, v.to_vec().unwrap());
+            let decoded = TestPurpose::clone_from_slice(&buf).unwrap();
+            // extra不参与序列化，解码后为默认值
+            assert_eq!(decoded.extra, 0);
+            assert_eq!(decoded.nonce, v.nonce);
+            assert_eq!(decoded.cache, v.cache);
+            assert_eq!(decoded.desc, v.desc);
+
+            // Hash: 去掉nonce，不包含cache，包含extra
+            let buf = encode_with(&v, RawEncodePurpose::Hash);
+            let mut expect = Vec::new();
+            expect.extend_from_slice(&0b100u8.to_vec().unwrap());
+            expect.extend_from_slice(&1u32.to_vec().unwrap());
+            expect.extend_from_slice(&v.signs.to_vec().unwrap());
+            expect.extend_from_slice(&4u16.to_vec().unwrap());
+            expect.extend_from_slice(&"desc".to_owned().to_vec().unwrap());
+            assert_eq!(buf, expect);
+            assert_eq!(v.raw_hash_encode().unwrap(), expect);
+            check_hash(&v);
+
+            // Sign: 去掉signs，包含nonce和extra
+            let buf = encode_with(&v, RawEncodePurpose::Sign);
+            let mut expect = Vec::new();
+            expect.extend_from_slice(&0b101u8.to_vec().unwrap());
+            expect.extend_from_slice(&1u32.to_vec().unwrap());
+            expect.extend_from_slice(&2u64.to_vec().unwrap());
+            expect.extend_from_slice(&4u16.to_vec().unwrap());
+            expect.extend_from_slice(&"desc".to_owned().to_vec().unwrap());
+            assert_eq!(buf, expect);
+
+            // Custom(1)同样去掉signs，但不包含extra
+            let buf = encode_with(&v, RawEncodePurpose::Custom(1));
+            let mut expect = Vec::new();
+            expect.extend_from_slice(&0b101u8.to_vec().unwrap());
+            expect.extend_from_slice(&1u32.to_vec().unwrap());
+            expect.extend_from_slice(&2u64.to_vec().unwrap());
+            expect.extend_from_slice(&"desc".to_owned().to_vec().unwrap());
+            assert_eq!(buf, expect);
+
+            let e = TestPurposeEnum::Struct { a: 1, b: 2 };
+            let buf = e.to_vec().unwrap();
+            assert_eq!(TestPurposeEnum::clone_from_slice(&buf).unwrap(), e);
+            assert_eq!(e.raw_hash_encode().unwrap(), vec![0u8, 1u8]);
+            check_hash(&e);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use crate::*;
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub(crate) const NANOS_PER_SEC: u32 = 1_000_000_000;
+
+fn check_nanos(nanos: u32, name: &str) -> CodecResult<()> {
+    if nanos >= NANOS_PER_SEC {
+        let msg = format!("invalid {} nanos: {}", name, nanos);
+        error!("{}", msg);
+        return Err(CodecError::new(CodecErrorCode::InvalidFormat, msg));
+    }
+
+    Ok(())
+}
+
+// 从UNIX_EPOCH开始的有符号秒数 + 纳秒，纳秒总是非负，所以早于UNIX_EPOCH的时间秒数向下取整
+fn encode_timestamp<'a>(
+    secs: i64,
+    nanos: u32,
+    buf: &'a mut [u8],
+    purpose: &Option<RawEncodePurpose>,
+) -> CodecResult<&'a mut [u8]> {
+    let buf = secs.raw_encode(buf, purpose)?;
+    nanos.raw_encode(buf, purpose)
+}
+
+fn decode_timestamp<'de>(buf: &'de [u8], name: &str) -> CodecResult<((i64, u32), &'de [u8])> {
+    let (secs, buf) = i64::raw_decode(buf)?;
+    let (nanos, buf) = u32::raw_decode(buf)?;
+    check_nanos(nanos, name)?;
+
+    Ok(((secs, nanos), buf))
+}
+
+fn timestamp_out_of_range(name: &str) -> CodecError {
+    let msg = format!("{} out of range", name);
+    error!("{}", msg);
+    CodecError::new(CodecErrorCode::OutOfLimit, msg)
+}
+
+// Duration，u64秒数 + u32纳秒
+impl RawFixedBytes for Duration {
+    fn raw_bytes() -> Option<usize> {
+        Some(u64::raw_bytes().unwrap() + u32::raw_bytes().unwrap())
+    }
+}
+
+impl RawEncode for Duration {
+    fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        Ok(Self::raw_bytes().unwrap())
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let buf = self.as_secs().raw_encode(buf, purpose)?;
+        self.subsec_nanos().raw_encode(buf, purpose)
+    }
+}
+
+impl<'de> RawDecode<'de> for Duration {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (secs, buf) = u64::raw_decode(buf)?;
+        let (nanos, buf) = u32::raw_decode(buf)?;
+        check_nanos(nanos, "Duration")?;
+
+        Ok((Duration::new(secs, nanos), buf))
+    }
+}
+
+// SystemTime，相对UNIX_EPOCH的i64秒数 + u32纳秒
+impl RawFixedBytes for SystemTime {
+    fn raw_bytes() -> Option<usize> {
+        Some(i64::raw_bytes().unwrap() + u32::raw_bytes().unwrap())
+    }
+}
+
+pub(crate) fn system_time_to_timestamp(time: &SystemTime) -> CodecResult<(i64, u32)> {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => {
+            let secs = i64::try_from(d.as_secs()).map_err(|_| timestamp_out_of_range("SystemTime"))?;
+            Ok((secs, d.subsec_nanos()))
+        }
+        Err(e) => {
+            let d = e.duration();
+            let secs = i64::try_from(d.as_secs()).map_err(|_| timestamp_out_of_range("SystemTime"))?;
+            if d.subsec_nanos() == 0 {
+                Ok((-secs, 0))
+            } else {
+                let secs = (-secs)
+                    .checked_sub(1)
+                    .ok_or_else(|| timestamp_out_of_range("SystemTime"))?;
+                Ok((secs, NANOS_PER_SEC - d.subsec_nanos()))
+            }
+        }
+    }
+}
+
+pub(crate) fn system_time_from_timestamp(secs: i64, nanos: u32) -> CodecResult<SystemTime> {
+    let time = if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::new(secs as u64, nanos))
+    } else {
+        UNIX_EPOCH
+            .checked_sub(Duration::from_secs(secs.unsigned_abs()))
+            .and_then(|t| t.checked_add(Duration::from_nanos(nanos as u64)))
+    };
+
+    time.ok_or_else(|| timestamp_out_of_range("SystemTime"))
+}
+
+impl RawEncode for SystemTime {
+    fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        Ok(Self::raw_bytes().unwrap())
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let (secs, nanos) = system_time_to_timestamp(self)?;
+        encode_timestamp(secs, nanos, buf, purpose)
+    }
+}
+
+impl<'de> RawDecode<'de> for SystemTime {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let ((secs, nanos), buf) = decode_timestamp(buf, "SystemTime")?;
+        let time = system_time_from_timestamp(secs, nanos)?;
+
+        Ok((time, buf))
+    }
+}
+
+// chrono的时间类型，与SystemTime使用相同的编码
+#[cfg(feature = "chrono")]
+mod chrono_impl {
+    use super::*;
+    use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+
+    impl RawFixedBytes for DateTime<Utc> {
+        fn raw_bytes() -> Option<usize> {
+            SystemTime::raw_bytes()
+        }
+    }
+
+    impl RawEncode for DateTime<Utc> {
+        fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+            Ok(Self::raw_bytes().unwrap())
+        }
+
+        fn raw_encode<'a>(
+            &self,
+            buf: &'a mut [u8],
+            purpose: &Option<RawEncodePurpose>,
+        ) -> CodecResult<&'a mut [u8]> {
+            // 闰秒的纳秒部分>=1_000_000_000，无法用SystemTime相同的格式表示，解码时也会被拒绝
+            let nanos = self.timestamp_subsec_nanos();
+            check_nanos(nanos, "DateTime")?;
+            encode_timestamp(self.timestamp(), nanos, buf, purpose)
+        }
+    }
+
+    impl<'de> RawDecode<'de> for DateTime<Utc> {
+        fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+            let ((secs, nanos), buf) = decode_timestamp(buf, "DateTime")?;
+            let time = DateTime::from_timestamp(secs, nanos)
+                .ok_or_else(|| timestamp_out_of_range("DateTime"))?;
+
+            Ok((time, buf))
+        }
+    }
+
+    // NaiveDateTime按UTC时间编码
+    impl RawFixedBytes for NaiveDateTime {
+        fn raw_bytes() -> Option<usize> {
+            SystemTime::raw_bytes()
+        }
+    }
+
+    impl RawEncode for NaiveDateTime {
+        fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+            Ok(Self::raw_bytes().unwrap())
+        }
+
+        fn raw_encode<'a>(
+            &self,
+            buf: &'a mut [u8],
+            purpose: &Option<RawEncodePurpose>,
+        ) -> CodecResult<&'a mut [u8]> {
+            self.and_utc().raw_encode(buf, purpose)
+        }
+    }
+
+    impl<'de> RawDecode<'de> for NaiveDateTime {
+        fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+            let (time, buf) = DateTime::<Utc>::raw_decode(buf)?;
+            Ok((time.naive_utc(), buf))
+        }
+    }
+
+    // NaiveDate，从公元1年1月1日开始的i32天数
+    impl RawFixedBytes for NaiveDate {
+        fn raw_bytes() -> Option<usize> {
+            i32::raw_bytes()
+        }
+    }
+
+    impl RawEncode for NaiveDate {
+        fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+            Ok(Self::raw_bytes().unwrap())
+        }
+
+        fn raw_encode<'a>(
+            &self,
+            buf: &'a mut [u8],
+            purpose: &Option<RawEncodePurpose>,
+        ) -> CodecResult<&'a mut [u8]> {
+            use chrono::Datelike;
+            self.num_days_from_ce().raw_encode(buf, purpose)
+        }
+    }
+
+    impl<'de> RawDecode<'de> for NaiveDate {
+        fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+            let (days, buf) = i32::raw_decode(buf)?;
+            let date = NaiveDate::from_num_days_from_ce_opt(days)
+                .ok_or_else(|| timestamp_out_of_range("NaiveDate"))?;
+
+            Ok((date, buf))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    fn test_codec<T>(v: &T)
+    where
+        for<'de> T: RawEncode + RawDecode<'de> + std::fmt::Debug + std::cmp::PartialEq,
+    {
+        let buf = v.to_vec().unwrap();
+        assert_eq!(buf.len(), v.raw_measure(&None).unwrap());
+        let v2 = T::clone_from_slice(&buf).unwrap();
+        assert_eq!(*v, v2);
+    }
+
+    #[test]
+    fn test_duration() {
+        test_codec(&Duration::new(0, 0));
+        test_codec(&Duration::new(u64::MAX, 999_999_999));
+        test_codec(&Duration::from_millis(1500));
+
+        let buf = Duration::new(1, 2).to_vec().unwrap();
+        assert_eq!(buf, vec![0u8, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2]);
+        assert_eq!(Duration::raw_bytes(), Some(12));
+
+        // 纳秒超出范围
+        let mut buf = 1u64.to_vec().unwrap();
+        buf.extend_from_slice(&1_000_000_000u32.to_vec().unwrap());
+        let err = Duration::clone_from_slice(&buf).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+    }
+
+    #[test]
+    fn test_system_time() {
+        test_codec(&UNIX_EPOCH);
+        test_codec(&SystemTime::now());
+        test_codec(&(UNIX_EPOCH + Duration::new(1_700_000_000, 123)));
+        test_codec(&(UNIX_EPOCH - Duration::new(10, 0)));
+        test_codec(&(UNIX_EPOCH - Duration::new(10, 250_000_000)));
+
+        // 早于UNIX_EPOCH的时间，秒数向下取整，纳秒非负
+        let buf = (UNIX_EPOCH - Duration::from_millis(1500)).to_vec().unwrap();
+        let mut expect = (-2i64).to_vec().unwrap();
+        expect.extend_from_slice(&500_000_000u32.to_vec().unwrap());
+        assert_eq!(buf, expect);
+
+        let mut buf = 0i64.to_vec().unwrap();
+        buf.extend_from_slice(&u32::MAX.to_vec().unwrap());
+        let err = SystemTime::clone_from_slice(&buf).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use chrono::{DateTime, NaiveDate, Utc};
+
+        let now = Utc::now();
+        test_codec(&now);
+        test_codec(&now.naive_utc());
+        test_codec(&NaiveDate::from_ymd_opt(2024, 2, 29).unwrap());
+
+        // 与SystemTime编码一致
+        let time = UNIX_EPOCH - Duration::from_millis(1500);
+        let dt: DateTime<Utc> = time.into();
+        assert_eq!(dt.to_vec().unwrap(), time.to_vec().unwrap());
+
+        let mut buf = i64::MAX.to_vec().unwrap();
+        buf.extend_from_slice(&0u32.to_vec().unwrap());
+        DateTime::<Utc>::clone_from_slice(&buf).unwrap_err();
+
+        // 闰秒无法编码
+        let leap = NaiveDate::from_ymd_opt(2016, 12, 31)
+            .unwrap()
+            .and_hms_nano_opt(23, 59, 59, 1_500_000_000)
+            .unwrap();
+        let err = leap.to_vec().unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+        leap.and_utc().to_vec().unwrap_err();
+    }
+}
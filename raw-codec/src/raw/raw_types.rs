@@ -5,9 +5,10 @@ use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::fmt;
-use std::hash::Hash;
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-use std::ops::Range;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::ops::{Bound, Range, RangeFrom, RangeInclusive, RangeTo};
 
 // u8
 impl RawFixedBytes for u8 {
@@ -35,6 +36,22 @@ impl RawEncode for u8 {
 
         Ok(&mut buf[1..])
     }
+
+    fn raw_encode_array<'a, const N: usize>(
+        array: &[Self; N],
+        buf: &'a mut [u8],
+        _purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        if buf.len() < N {
+            return Err(CodecError::new(
+                CodecErrorCode::OutOfLimit,
+                "not enough buffer for [u8; N]",
+            ));
+        }
+        buf[..N].copy_from_slice(array);
+
+        Ok(&mut buf[N..])
+    }
 }
 
 impl<'de> RawDecode<'de> for u8 {
@@ -47,6 +64,19 @@ impl<'de> RawDecode<'de> for u8 {
         }
         Ok((buf[0], &buf[1..]))
     }
+
+    fn raw_decode_array<const N: usize>(buf: &'de [u8]) -> CodecResult<([Self; N], &'de [u8])> {
+        if buf.len() < N {
+            return Err(CodecError::new(
+                CodecErrorCode::OutOfLimit,
+                "not enough buffer for [u8; N]",
+            ));
+        }
+        let mut array = [0u8; N];
+        array.copy_from_slice(&buf[..N]);
+
+        Ok((array, &buf[N..]))
+    }
 }
 
 // bool
@@ -89,6 +119,9 @@ impl<'de> RawDecode<'de> for bool {
                 "not enough buffer for u8",
             ));
         }
+        if buf[0] > 1 && is_canonical_decode() {
+            return Err(non_canonical_error(format!("invalid bool value: {}", buf[0])));
+        }
         if buf[0] == 0 {
             Ok((false, &buf[1..]))
         } else {
@@ -360,6 +393,194 @@ impl<'de> RawDecode<'de> for u128 {
     }
 }
 
+// 固定长度的大端整数：i8, i16, i128
+macro_rules! raw_codec_be_int {
+    ($ty:ty, $size:expr, $name:expr) => {
+        impl RawFixedBytes for $ty {
+            fn raw_bytes() -> Option<usize> {
+                Some($size)
+            }
+        }
+
+        impl RawEncode for $ty {
+            fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+                Ok($size)
+            }
+
+            fn raw_encode<'a>(
+                &self,
+                buf: &'a mut [u8],
+                _purpose: &Option<RawEncodePurpose>,
+            ) -> CodecResult<&'a mut [u8]> {
+                if buf.len() < $size {
+                    return Err(CodecError::new(
+                        CodecErrorCode::OutOfLimit,
+                        concat!("not enough buffer for ", $name),
+                    ));
+                }
+
+                buf[..$size].copy_from_slice(&self.to_be_bytes());
+
+                Ok(&mut buf[$size..])
+            }
+        }
+
+        impl<'de> RawDecode<'de> for $ty {
+            fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+                if buf.len() < $size {
+                    return Err(CodecError::new(
+                        CodecErrorCode::OutOfLimit,
+                        concat!("not enough buffer for ", $name),
+                    ));
+                }
+
+                let mut b = [0u8; $size];
+                b.copy_from_slice(&buf[..$size]);
+                let v = <$ty>::from_be_bytes(b);
+                Ok((v, &buf[$size..]))
+            }
+        }
+    };
+}
+
+raw_codec_be_int!(i8, 1, "i8");
+raw_codec_be_int!(i16, 2, "i16");
+raw_codec_be_int!(i128, 16, "i128");
+
+// 浮点数按IEEE754位模式大端编码，计算hash时所有NaN统一为标准NaN，保证相同语义的值hash一致
+macro_rules! raw_codec_float {
+    ($ty:ty, $bits:ty, $size:expr, $name:expr) => {
+        impl RawFixedBytes for $ty {
+            fn raw_bytes() -> Option<usize> {
+                Some($size)
+            }
+        }
+
+        impl RawEncode for $ty {
+            fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+                Ok($size)
+            }
+
+            fn raw_encode<'a>(
+                &self,
+                buf: &'a mut [u8],
+                purpose: &Option<RawEncodePurpose>,
+            ) -> CodecResult<&'a mut [u8]> {
+                if buf.len() < $size {
+                    return Err(CodecError::new(
+                        CodecErrorCode::OutOfLimit,
+                        concat!("not enough buffer for ", $name),
+                    ));
+                }
+
+                // hash编码时统一NaN和-0.0，保证相等的值hash一致
+                let bits = if purpose == &Some(RawEncodePurpose::Hash) {
+                    if self.is_nan() {
+                        <$ty>::NAN.to_bits()
+                    } else if *self == 0.0 {
+                        (0.0 as $ty).to_bits()
+                    } else {
+                        self.to_bits()
+                    }
+                } else {
+                    self.to_bits()
+                };
+                buf[..$size].copy_from_slice(&bits.to_be_bytes());
+
+                Ok(&mut buf[$size..])
+            }
+        }
+
+        impl<'de> RawDecode<'de> for $ty {
+            fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+                if buf.len() < $size {
+                    return Err(CodecError::new(
+                        CodecErrorCode::OutOfLimit,
+                        concat!("not enough buffer for ", $name),
+                    ));
+                }
+
+                let mut b = [0u8; $size];
+                b.copy_from_slice(&buf[..$size]);
+                let v = <$ty>::from_bits(<$bits>::from_be_bytes(b));
+                Ok((v, &buf[$size..]))
+            }
+        }
+    };
+}
+
+raw_codec_float!(f32, u32, 4, "f32");
+raw_codec_float!(f64, u64, 8, "f64");
+
+// char，按u32编码，解码时校验是否为合法的unicode标量值
+impl RawFixedBytes for char {
+    fn raw_bytes() -> Option<usize> {
+        Some(4)
+    }
+}
+
+impl RawEncode for char {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        (*self as u32).raw_measure(purpose)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        (*self as u32).raw_encode(buf, purpose)
+    }
+}
+
+impl<'de> RawDecode<'de> for char {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (v, buf) = u32::raw_decode(buf)?;
+        let c = char::from_u32(v).ok_or_else(|| {
+            CodecError::new(
+                CodecErrorCode::InvalidFormat,
+                format!("invalid char value: {:#x}", v),
+            )
+        })?;
+
+        Ok((c, buf))
+    }
+}
+
+// usize，固定按u64编码，保证32位和64位平台的编码一致
+impl RawFixedBytes for usize {
+    fn raw_bytes() -> Option<usize> {
+        Some(8)
+    }
+}
+
+impl RawEncode for usize {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        (*self as u64).raw_measure(purpose)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        (*self as u64).raw_encode(buf, purpose)
+    }
+}
+
+impl<'de> RawDecode<'de> for usize {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (v, buf) = u64::raw_decode(buf)?;
+        let v = usize::try_from(v).map_err(|_| {
+            let msg = format!("usize value out of range! value={}, usize::MAX={}", v, usize::MAX);
+            error!("{}", msg);
+            CodecError::new(CodecErrorCode::OutOfLimit, msg)
+        })?;
+
+        Ok((v, buf))
+    }
+}
+
 const U6_MAX: u64 = (u8::MAX >> 2) as u64;
 const U14_MAX: u64 = (u16::MAX >> 2) as u64;
 const U30_MAX: u64 = (u32::MAX >> 2) as u64;
@@ -424,7 +645,8 @@ impl VarSizeHelper {
         Ok(buf)
     }
 
-    fn raw_decode(buf: &[u8]) -> CodecResult<(u64, &[u8])> {
+    fn raw_decode(origin_buf: &[u8]) -> CodecResult<(u64, &[u8])> {
+        let buf = origin_buf;
         let (first_byte, _buf) = u8::raw_decode(buf)?;
 
         let (len, buf) = if first_byte & 0b_11_000000 == 0b_00_000000 {
@@ -448,14 +670,25 @@ impl VarSizeHelper {
             panic!("invalid first byte: {}", first_byte);
         };
 
+        if is_canonical_decode() {
+            let used = origin_buf.len() - buf.len();
+            if used != Self::raw_measure(len, &None)? {
+                return Err(non_canonical_error(format!(
+                    "non-minimal size encoding: value={}, bytes={}",
+                    len, used
+                )));
+            }
+        }
+
         Ok((len, buf))
     }
 }
 
 // 可变长度size，包括VarSize和USize两种基础类型
-// USize最大支持的size范围是[0, usize::MAX>>2]，会根据size的实际大小，占用1-8个bytes，其中usize会根据32bit还是64bit不同而不同
+// USize最大支持的size范围是[0, min(usize::MAX, u64::MAX>>2)]，会根据size的实际大小，占用1-8个bytes
 //      在需要表示一些容器的大小时候使用此值，比如vec,map等
-//      如果要同时支持32bit和64bit平台，那么要仔细考虑这个差异，可能会导致32bit平台无法解码64bit平台的一些结构(会被截断)
+//      USize与BuckySize的编码完全一致，解码时先按BuckySize读出u64，再检查是否超出当前平台的usize范围
+//      超出时返回OutOfLimit，不会被截断；在PortableSizeGuard作用域内按32bit平台的范围检查，保证各平台行为一致
 // BuckySize最大支持的size范围是[0, u64::MAX>>2]，会根据size的实际大小，占用1-8个bytes，在需要明确的size情况下使用该结构体
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -528,7 +761,8 @@ impl DerefMut for USize {
 
 impl RawEncode for USize {
     fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
-        VarSizeHelper::raw_measure(self.0 as u64, purpose)
+        check_usize_range(self.0 as u64, usize_max())?;
+        BuckySize::from(self).raw_measure(purpose)
     }
 
     fn raw_encode<'a>(
@@ -536,26 +770,62 @@ impl RawEncode for USize {
         buf: &'a mut [u8],
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]> {
-        VarSizeHelper::raw_encode(self.0 as u64, buf, purpose)
+        check_usize_range(self.0 as u64, usize_max())?;
+        BuckySize::from(self).raw_encode(buf, purpose)
     }
 }
 
 impl<'de> RawDecode<'de> for USize {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
-        let (len, buf) = VarSizeHelper::raw_decode(buf)?;
+        let (size, buf) = BuckySize::raw_decode(buf)?;
 
-        if len > usize::MAX as u64 {
-            let msg = format!(
-                "len extend usize max size on 32bit platform! len={}, usize::MAX={}",
-                len,
-                usize::MAX
-            );
-            error!("{}", msg);
+        Ok((USize::try_from(size)?, buf))
+    }
+}
 
-            return Err(CodecError::new(CodecErrorCode::OutOfLimit, msg));
-        }
+// 32bit平台usize的上限，跨平台size模式下使用
+const PORTABLE_USIZE_MAX: u64 = u32::MAX as u64;
+
+fn usize_max() -> u64 {
+    if is_portable_size() {
+        std::cmp::min(usize::MAX as u64, PORTABLE_USIZE_MAX)
+    } else {
+        usize::MAX as u64
+    }
+}
+
+fn check_usize_range(len: u64, max: u64) -> CodecResult<usize> {
+    if len > max {
+        let msg = format!("len extend usize max size! len={}, max={}", len, max);
+        error!("{}", msg);
+
+        return Err(CodecError::new(CodecErrorCode::OutOfLimit, msg));
+    }
+
+    usize::try_from(len).map_err(|_| {
+        let msg = format!("len extend usize max size! len={}, usize::MAX={}", len, usize::MAX);
+        error!("{}", msg);
+        CodecError::new(CodecErrorCode::OutOfLimit, msg)
+    })
+}
+
+impl From<&USize> for BuckySize {
+    fn from(size: &USize) -> Self {
+        BuckySize(size.0 as u64)
+    }
+}
+
+impl From<USize> for BuckySize {
+    fn from(size: USize) -> Self {
+        BuckySize::from(&size)
+    }
+}
+
+impl TryFrom<BuckySize> for USize {
+    type Error = CodecError;
 
-        Ok((USize(len as usize), buf))
+    fn try_from(size: BuckySize) -> CodecResult<Self> {
+        Ok(USize(check_usize_range(size.0, usize_max())?))
     }
 }
 
@@ -641,14 +911,65 @@ fn usize_x() {
     println!("\ntest {}", 4611686018427387903u64);
     {
         let len = 4611686018427387903u64;
-        let ulen = USize(len as usize);
+        let buf = BuckySize(len).to_vec().unwrap();
 
         #[cfg(target_pointer_width = "32")]
-        assert!(!ulen.raw_measure(&None).is_err());
+        assert_eq!(
+            USize::raw_decode(&buf).unwrap_err().code(),
+            CodecErrorCode::OutOfLimit
+        );
 
         #[cfg(target_pointer_width = "64")]
-        assert!(!ulen.raw_measure(&None).is_err());
+        {
+            let ulen = USize(len as usize);
+            assert!(ulen.raw_measure(&None).is_ok());
+            let (decode_ulen, _decode_buf) = USize::raw_decode(&buf).unwrap();
+            assert_eq!(len as usize, decode_ulen.value());
+        }
+    }
+}
+
+#[test]
+fn usize_32bit_range() {
+    // 模拟32bit平台的usize范围
+    let max = u32::MAX as u64;
+    assert_eq!(check_usize_range(max, max).unwrap(), u32::MAX as usize);
+    assert_eq!(
+        check_usize_range(max + 1, max).unwrap_err().code(),
+        CodecErrorCode::OutOfLimit
+    );
+
+    // USize与BuckySize编码一致
+    let len = u32::MAX as u64 + 1;
+    let buf = BuckySize(len).to_vec().unwrap();
+    #[cfg(target_pointer_width = "64")]
+    assert_eq!(USize(len as usize).to_vec().unwrap(), buf);
+
+    #[cfg(target_pointer_width = "32")]
+    assert_eq!(
+        USize::raw_decode(&buf).unwrap_err().code(),
+        CodecErrorCode::OutOfLimit
+    );
+}
+
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn usize_portable_size() {
+    let len = u32::MAX as u64 + 1;
+    let buf = BuckySize(len).to_vec().unwrap();
+    assert_eq!(USize::raw_decode(&buf).unwrap().0.value(), len as usize);
+
+    {
+        let _guard = PortableSizeGuard::new();
+        let err = USize(len as usize).to_vec().unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::OutOfLimit);
+        let err = USize::raw_decode(&buf).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::OutOfLimit);
+        USize(u32::MAX as usize).to_vec().unwrap();
     }
+
+    assert!(!is_portable_size());
+    USize(len as usize).to_vec().unwrap();
 }
 
 // [T]
@@ -687,6 +1008,18 @@ impl<T: RawEncode> RawEncode for [T] {
         }
         Ok(&mut buf[offset..])
     }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        USize(self.len()).raw_digest(writer, purpose)?;
+        for e in self {
+            e.raw_digest(writer, purpose)?;
+        }
+        Ok(())
+    }
 }
 
 // Vec<T>
@@ -708,6 +1041,14 @@ impl<T: RawEncode> RawEncode for Vec<T> {
     ) -> CodecResult<&'a mut [u8]> {
         self.as_slice().raw_encode(buf, purpose)
     }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        self.as_slice().raw_digest(writer, purpose)
+    }
 }
 
 impl<'de, T: RawEncode + RawDecode<'de>> RawDecode<'de> for Vec<T> {
@@ -722,12 +1063,7 @@ impl<'de, T: RawEncode + RawDecode<'de>> RawDecode<'de> for Vec<T> {
         let len = ulen.value();
         let mut offset: usize = 0;
         // println!("vec len {}", len);
-        if len > u32::max_value() as usize / 32 {
-            return Err(CodecError::new(
-                CodecErrorCode::OutOfLimit,
-                "vec len overflow",
-            ));
-        }
+        check_container_len(len)?;
 
         let mut vec = std::vec::Vec::with_capacity(len);
         for _ in 0..len {
@@ -739,9 +1075,78 @@ impl<'de, T: RawEncode + RawDecode<'de>> RawDecode<'de> for Vec<T> {
     }
 }
 
+// 容器元素个数上限，防止恶意数据导致分配过大的内存
+pub const MAX_CONTAINER_LEN: usize = u32::MAX as usize / 32;
+
+fn check_container_len(len: usize) -> CodecResult<()> {
+    if len > MAX_CONTAINER_LEN {
+        return Err(CodecError::new(
+            CodecErrorCode::OutOfLimit,
+            "vec len overflow",
+        ));
+    }
+
+    Ok(())
+}
+
+// 序列容器的通用编码：USize长度 + 依次编码每个元素
+fn raw_measure_seq<'a, T: RawEncode + 'a>(
+    len: usize,
+    iter: impl Iterator<Item = &'a T>,
+    purpose: &Option<RawEncodePurpose>,
+) -> CodecResult<usize> {
+    let mut bytes = USize(len).raw_measure(purpose)?;
+    for e in iter {
+        bytes += e.raw_measure(purpose)?;
+    }
+    Ok(bytes)
+}
+
+fn raw_encode_seq<'a, 'b, T: RawEncode + 'b>(
+    len: usize,
+    iter: impl Iterator<Item = &'b T>,
+    buf: &'a mut [u8],
+    purpose: &Option<RawEncodePurpose>,
+) -> CodecResult<&'a mut [u8]> {
+    let mut buf = USize(len).raw_encode(buf, purpose)?;
+    for e in iter {
+        buf = e.raw_encode(buf, purpose)?;
+    }
+    Ok(buf)
+}
+
+fn raw_digest_seq<'a, T: RawEncode + 'a>(
+    len: usize,
+    iter: impl Iterator<Item = &'a T>,
+    writer: &mut RawDigestWriter,
+    purpose: &Option<RawEncodePurpose>,
+) -> CodecResult<()> {
+    USize(len).raw_digest(writer, purpose)?;
+    for e in iter {
+        e.raw_digest(writer, purpose)?;
+    }
+    Ok(())
+}
+
+fn raw_decode_seq<'de, T: RawDecode<'de>>(
+    buf: &'de [u8],
+    mut push: impl FnMut(T),
+) -> CodecResult<(usize, &'de [u8])> {
+    let (ulen, mut buf) = USize::raw_decode(buf)?;
+    let len = ulen.value();
+    check_container_len(len)?;
+
+    for _ in 0..len {
+        let (e, next) = T::raw_decode(buf)?;
+        buf = next;
+        push(e);
+    }
+    Ok((len, buf))
+}
+
 // HashSet<T>
 
-impl<T: RawEncode + std::cmp::Ord> RawEncode for HashSet<T> {
+impl<T: RawEncode + std::cmp::Ord, S> RawEncode for HashSet<T, S> {
     fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
         let ulen = USize(self.len());
         let mut bytes = ulen.raw_measure(purpose).unwrap();
@@ -768,19 +1173,45 @@ impl<T: RawEncode + std::cmp::Ord> RawEncode for HashSet<T> {
         }
         Ok(buf)
     }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        USize(self.len()).raw_digest(writer, purpose)?;
+
+        let mut values: Vec<&T> = self.iter().collect();
+        values.sort();
+        for e in values {
+            e.raw_digest(writer, purpose)?;
+        }
+        Ok(())
+    }
 }
 
-impl<'de, T: Eq + Hash + RawEncode + RawDecode<'de>> RawDecode<'de> for HashSet<T> {
+// 编码时按Ord排序，规范模式下检查元素严格递增(同时排除了重复的元素)
+impl<'de, T: Eq + Hash + Ord + RawEncode + RawDecode<'de>, S: BuildHasher + Default> RawDecode<'de>
+    for HashSet<T, S>
+{
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
         let (ulen, mut buf) = USize::raw_decode(buf)?;
         let len = ulen.value();
-        if len > u32::max_value() as usize / 32 {
-            return Err(CodecError::new(
-                CodecErrorCode::OutOfLimit,
-                "vec len overflow",
-            ));
+        check_container_len(len)?;
+
+        if is_canonical_decode() {
+            let mut values: Vec<T> = Vec::with_capacity(len);
+            for _ in 0..len {
+                let (e, _buf) = T::raw_decode(buf)?;
+                buf = _buf;
+                check_canonical_key_order(values.last(), &e, "HashSet")?;
+                values.push(e);
+            }
+
+            return Ok((values.into_iter().collect(), buf));
         }
-        let mut set = HashSet::with_capacity(len as usize);
+
+        let mut set = HashSet::with_capacity_and_hasher(len, S::default());
         for _ in 0..len {
             let (e, _buf) = T::raw_decode(buf)?;
             buf = _buf;
@@ -793,7 +1224,7 @@ impl<'de, T: Eq + Hash + RawEncode + RawDecode<'de>> RawDecode<'de> for HashSet<
 
 // HashMap<K,V>
 
-impl<K: RawEncode + std::cmp::Ord + std::hash::Hash, V: RawEncode> RawEncode for HashMap<K, V> {
+impl<K: RawEncode + std::cmp::Ord, V: RawEncode, S> RawEncode for HashMap<K, V, S> {
     fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
         let ulen = USize(self.len());
         let mut size = ulen.raw_measure(purpose).unwrap();
@@ -812,27 +1243,55 @@ impl<K: RawEncode + std::cmp::Ord + std::hash::Hash, V: RawEncode> RawEncode for
         let mut buf = ulen.raw_encode(buf, purpose)?;
 
         // stable sort
-        let mut keys: Vec<&K> = self.keys().collect();
-        keys.sort();
-        for key in keys {
+        let mut entries: Vec<(&K, &V)> = self.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in entries {
             buf = key.raw_encode(buf, purpose)?;
-
-            let value = self.get(key).unwrap();
             buf = value.raw_encode(buf, purpose)?;
         }
-        // for (key, value) in self {
-        //     buf = key.raw_encode(buf, purpose)?;
-        //     buf = value.raw_encode(buf, purpose)?;
-        // }
         Ok(buf)
     }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        USize(self.len()).raw_digest(writer, purpose)?;
+
+        let mut entries: Vec<(&K, &V)> = self.iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        for (key, value) in entries {
+            key.raw_digest(writer, purpose)?;
+            value.raw_digest(writer, purpose)?;
+        }
+        Ok(())
+    }
 }
 
-impl<'de, K: RawDecode<'de> + Hash + Eq, V: RawDecode<'de>> RawDecode<'de> for HashMap<K, V> {
+// 编码时按key的Ord排序，规范模式下检查key严格递增(同时排除了重复的key)
+impl<'de, K: RawDecode<'de> + Hash + Eq + Ord, V: RawDecode<'de>, S: BuildHasher + Default>
+    RawDecode<'de> for HashMap<K, V, S>
+{
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
         let (ulen, mut buf) = USize::raw_decode(buf)?;
         let len = ulen.value();
-        let mut map = HashMap::new();
+        check_container_len(len)?;
+
+        if is_canonical_decode() {
+            let mut entries: Vec<(K, V)> = Vec::with_capacity(len);
+            for _ in 0..len {
+                let (key, tmp_buf) = K::raw_decode(buf)?;
+                let (v, tmp_buf) = V::raw_decode(tmp_buf)?;
+                buf = tmp_buf;
+                check_canonical_key_order(entries.last().map(|(k, _)| k), &key, "HashMap")?;
+                entries.push((key, v));
+            }
+
+            return Ok((entries.into_iter().collect(), buf));
+        }
+
+        let mut map = HashMap::with_capacity_and_hasher(len, S::default());
         for _ in 0..len {
             let (key, tmp_buf) = K::raw_decode(buf)?;
             let (v, tmp_buf) = V::raw_decode(tmp_buf)?;
@@ -843,16 +1302,104 @@ impl<'de, K: RawDecode<'de> + Hash + Eq, V: RawDecode<'de>> RawDecode<'de> for H
     }
 }
 
-// BTreeSet<T>
+// RawOrderedHashSet<T>和RawOrderedHashMap<K, V>
+// 按元素(key)编码后的字节序排序，不要求元素实现Ord，同样可以得到确定的编码
 
-impl<T: RawEncode> RawEncode for BTreeSet<T> {
-    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
-        let ulen = USize(self.len());
-        let mut bytes = ulen.raw_measure(purpose).unwrap();
-        for e in self {
-            bytes += e.raw_measure(purpose)?;
+// 编码后的key和对应的value，按key的字节序排序
+fn sort_by_encoded_key<'v, K: RawEncode + 'v, V>(
+    iter: impl Iterator<Item = (&'v K, V)>,
+    purpose: &Option<RawEncodePurpose>,
+) -> CodecResult<Vec<(Vec<u8>, V)>> {
+    let mut entries = Vec::new();
+    for (key, value) in iter {
+        let size = key.raw_measure(purpose)?;
+        let mut buf = vec![0u8; size];
+        let remain = key.raw_encode(&mut buf, purpose)?;
+        let len = size - remain.len();
+        buf.truncate(len);
+        entries.push((buf, value));
+    }
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(entries)
+}
+
+fn write_encoded_bytes<'a>(buf: &'a mut [u8], bytes: &[u8], name: &str) -> CodecResult<&'a mut [u8]> {
+    if buf.len() < bytes.len() {
+        let msg = format!("not enough buffer for {}", name);
+        error!("{}", msg);
+        return Err(CodecError::new(CodecErrorCode::OutOfLimit, msg));
+    }
+    buf[..bytes.len()].copy_from_slice(bytes);
+    Ok(&mut buf[bytes.len()..])
+}
+
+// 解码一个key，同时返回其编码后的字节，规范模式下检查字节序严格递增
+fn decode_ordered_key<'de, K: RawDecode<'de>>(
+    buf: &'de [u8],
+    prev: Option<&'de [u8]>,
+    name: &str,
+) -> CodecResult<(K, &'de [u8], &'de [u8])> {
+    let (key, remain) = K::raw_decode(buf)?;
+    let key_bytes = &buf[..buf.len() - remain.len()];
+    if is_canonical_decode() {
+        if let Some(prev) = prev {
+            if prev >= key_bytes {
+                return Err(non_canonical_error(format!(
+                    "{} keys are not in canonical order",
+                    name
+                )));
+            }
         }
-        Ok(bytes)
+    }
+    Ok((key, key_bytes, remain))
+}
+
+#[derive(Clone, Debug)]
+pub struct RawOrderedHashSet<T, S = RandomState>(pub HashSet<T, S>);
+
+impl<T, S: Default> Default for RawOrderedHashSet<T, S> {
+    fn default() -> Self {
+        Self(HashSet::default())
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> PartialEq for RawOrderedHashSet<T, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<T: Eq + Hash, S: BuildHasher> Eq for RawOrderedHashSet<T, S> {}
+
+impl<T, S> Deref for RawOrderedHashSet<T, S> {
+    type Target = HashSet<T, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T, S> DerefMut for RawOrderedHashSet<T, S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T, S> From<HashSet<T, S>> for RawOrderedHashSet<T, S> {
+    fn from(v: HashSet<T, S>) -> Self {
+        Self(v)
+    }
+}
+
+impl<T, S> From<RawOrderedHashSet<T, S>> for HashSet<T, S> {
+    fn from(v: RawOrderedHashSet<T, S>) -> Self {
+        v.0
+    }
+}
+
+impl<T: RawEncode, S> RawEncode for RawOrderedHashSet<T, S> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        raw_measure_seq(self.0.len(), self.0.iter(), purpose)
     }
 
     fn raw_encode<'a>(
@@ -860,23 +1407,216 @@ impl<T: RawEncode> RawEncode for BTreeSet<T> {
         buf: &'a mut [u8],
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]> {
-        let ulen = USize(self.len());
-        let mut buf = ulen.raw_encode(buf, purpose)?;
-        for e in self {
-            buf = e.raw_encode(buf, purpose)?;
+        let entries = sort_by_encoded_key(self.0.iter().map(|e| (e, ())), purpose)?;
+        let mut buf = USize(entries.len()).raw_encode(buf, purpose)?;
+        for (bytes, _) in entries {
+            buf = write_encoded_bytes(buf, &bytes, "RawOrderedHashSet")?;
         }
         Ok(buf)
     }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        let entries = sort_by_encoded_key(self.0.iter().map(|e| (e, ())), purpose)?;
+        USize(entries.len()).raw_digest(writer, purpose)?;
+        for (bytes, _) in entries {
+            writer.write(&bytes);
+        }
+        Ok(())
+    }
+}
+
+impl<'de, T: RawDecode<'de> + Hash + Eq, S: BuildHasher + Default> RawDecode<'de>
+    for RawOrderedHashSet<T, S>
+{
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (ulen, mut buf) = USize::raw_decode(buf)?;
+        let len = ulen.value();
+        check_container_len(len)?;
+
+        let mut set = HashSet::with_capacity_and_hasher(len, S::default());
+        let mut prev = None;
+        for _ in 0..len {
+            let (e, bytes, remain) = decode_ordered_key(buf, prev, "RawOrderedHashSet")?;
+            prev = Some(bytes);
+            buf = remain;
+            set.insert(e);
+        }
+
+        Ok((Self(set), buf))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RawOrderedHashMap<K, V, S = RandomState>(pub HashMap<K, V, S>);
+
+impl<K, V, S: Default> Default for RawOrderedHashMap<K, V, S> {
+    fn default() -> Self {
+        Self(HashMap::default())
+    }
+}
+
+impl<K: Eq + Hash, V: PartialEq, S: BuildHasher> PartialEq for RawOrderedHashMap<K, V, S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl<K: Eq + Hash, V: Eq, S: BuildHasher> Eq for RawOrderedHashMap<K, V, S> {}
+
+impl<K, V, S> Deref for RawOrderedHashMap<K, V, S> {
+    type Target = HashMap<K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<K, V, S> DerefMut for RawOrderedHashMap<K, V, S> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<K, V, S> From<HashMap<K, V, S>> for RawOrderedHashMap<K, V, S> {
+    fn from(v: HashMap<K, V, S>) -> Self {
+        Self(v)
+    }
+}
+
+impl<K, V, S> From<RawOrderedHashMap<K, V, S>> for HashMap<K, V, S> {
+    fn from(v: RawOrderedHashMap<K, V, S>) -> Self {
+        v.0
+    }
+}
+
+impl<K: RawEncode, V: RawEncode, S> RawEncode for RawOrderedHashMap<K, V, S> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        let mut size = USize(self.0.len()).raw_measure(purpose)?;
+        for (key, value) in &self.0 {
+            size += key.raw_measure(purpose)? + value.raw_measure(purpose)?;
+        }
+        Ok(size)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let entries = sort_by_encoded_key(self.0.iter(), purpose)?;
+        let mut buf = USize(entries.len()).raw_encode(buf, purpose)?;
+        for (bytes, value) in entries {
+            buf = write_encoded_bytes(buf, &bytes, "RawOrderedHashMap")?;
+            buf = value.raw_encode(buf, purpose)?;
+        }
+        Ok(buf)
+    }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        let entries = sort_by_encoded_key(self.0.iter(), purpose)?;
+        USize(entries.len()).raw_digest(writer, purpose)?;
+        for (bytes, value) in entries {
+            writer.write(&bytes);
+            value.raw_digest(writer, purpose)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de, K: RawDecode<'de> + Hash + Eq, V: RawDecode<'de>, S: BuildHasher + Default> RawDecode<'de>
+    for RawOrderedHashMap<K, V, S>
+{
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (ulen, mut buf) = USize::raw_decode(buf)?;
+        let len = ulen.value();
+        check_container_len(len)?;
+
+        let mut map = HashMap::with_hasher(S::default());
+        let mut prev = None;
+        for _ in 0..len {
+            let (key, bytes, remain) = decode_ordered_key(buf, prev, "RawOrderedHashMap")?;
+            prev = Some(bytes);
+            let (value, remain) = V::raw_decode(remain)?;
+            buf = remain;
+            map.insert(key, value);
+        }
+
+        Ok((Self(map), buf))
+    }
+}
+
+// 规范编码要求容器的key严格递增，不能乱序或者重复
+fn check_canonical_key_order<T: Ord>(prev: Option<&T>, next: &T, name: &str) -> CodecResult<()> {
+    if let Some(prev) = prev {
+        if prev >= next {
+            return Err(non_canonical_error(format!(
+                "{} keys are not in canonical order",
+                name
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+// BTreeSet<T>
+
+impl<T: RawEncode> RawEncode for BTreeSet<T> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        let ulen = USize(self.len());
+        let mut bytes = ulen.raw_measure(purpose).unwrap();
+        for e in self {
+            bytes += e.raw_measure(purpose)?;
+        }
+        Ok(bytes)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let ulen = USize(self.len());
+        let mut buf = ulen.raw_encode(buf, purpose)?;
+        for e in self {
+            buf = e.raw_encode(buf, purpose)?;
+        }
+        Ok(buf)
+    }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        USize(self.len()).raw_digest(writer, purpose)?;
+        for e in self {
+            e.raw_digest(writer, purpose)?;
+        }
+        Ok(())
+    }
 }
 
 impl<'de, T: Ord + RawDecode<'de>> RawDecode<'de> for BTreeSet<T> {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
         let (ulen, mut buf) = USize::raw_decode(buf)?;
         let len = ulen.value();
+        let canonical = is_canonical_decode();
         let mut set = BTreeSet::new();
         for _ in 0..len {
             let (e, _buf) = T::raw_decode(buf)?;
             buf = _buf;
+            if canonical {
+                check_canonical_key_order(set.last(), &e, "BTreeSet")?;
+            }
             set.insert(e);
         }
 
@@ -909,62 +1649,465 @@ impl<K: RawEncode + std::cmp::Ord, V: RawEncode> RawEncode for BTreeMap<K, V> {
             buf = key.raw_encode(buf, purpose)?;
             buf = value.raw_encode(buf, purpose)?;
         }
-
-        Ok(buf)
-    }
+
+        Ok(buf)
+    }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        USize(self.len()).raw_digest(writer, purpose)?;
+        for (key, value) in self {
+            key.raw_digest(writer, purpose)?;
+            value.raw_digest(writer, purpose)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'de, K: RawDecode<'de> + std::cmp::Ord + Eq, V: RawDecode<'de>> RawDecode<'de>
+    for BTreeMap<K, V>
+{
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (ulen, mut buf) = USize::raw_decode(buf)?;
+        let len = ulen.value();
+        let canonical = is_canonical_decode();
+        let mut map = BTreeMap::new();
+        for _ in 0..len {
+            let (key, tmp_buf) = K::raw_decode(buf)?;
+            let (v, tmp_buf) = V::raw_decode(tmp_buf)?;
+            buf = tmp_buf;
+            if canonical {
+                check_canonical_key_order(map.last_key_value().map(|(k, _)| k), &key, "BTreeMap")?;
+            }
+            map.insert(key, v);
+        }
+        Ok((map, buf))
+    }
+}
+
+// VecDeque<T>，与Vec编码一致
+use std::collections::{BinaryHeap, LinkedList, VecDeque};
+
+impl<T: RawEncode> RawFixedBytes for VecDeque<T> {
+    fn raw_min_bytes() -> Option<usize> {
+        u8::raw_bytes()
+    }
+}
+
+impl<T: RawEncode> RawEncode for VecDeque<T> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        raw_measure_seq(self.len(), self.iter(), purpose)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        raw_encode_seq(self.len(), self.iter(), buf, purpose)
+    }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        raw_digest_seq(self.len(), self.iter(), writer, purpose)
+    }
+}
+
+impl<'de, T: RawDecode<'de>> RawDecode<'de> for VecDeque<T> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let mut list = VecDeque::new();
+        let (_, buf) = raw_decode_seq(buf, |e| list.push_back(e))?;
+        Ok((list, buf))
+    }
+}
+
+// LinkedList<T>，与Vec编码一致
+impl<T: RawEncode> RawFixedBytes for LinkedList<T> {
+    fn raw_min_bytes() -> Option<usize> {
+        u8::raw_bytes()
+    }
+}
+
+impl<T: RawEncode> RawEncode for LinkedList<T> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        raw_measure_seq(self.len(), self.iter(), purpose)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        raw_encode_seq(self.len(), self.iter(), buf, purpose)
+    }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        raw_digest_seq(self.len(), self.iter(), writer, purpose)
+    }
+}
+
+impl<'de, T: RawDecode<'de>> RawDecode<'de> for LinkedList<T> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let mut list = LinkedList::new();
+        let (_, buf) = raw_decode_seq(buf, |e| list.push_back(e))?;
+        Ok((list, buf))
+    }
+}
+
+// BinaryHeap<T>，内部顺序不确定，按升序编码保证相同内容的编码一致
+impl<T: RawEncode + Ord> RawFixedBytes for BinaryHeap<T> {
+    fn raw_min_bytes() -> Option<usize> {
+        u8::raw_bytes()
+    }
+}
+
+impl<T: RawEncode + Ord> RawEncode for BinaryHeap<T> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        raw_measure_seq(self.len(), self.iter(), purpose)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let mut values: Vec<&T> = self.iter().collect();
+        values.sort();
+        raw_encode_seq(values.len(), values.into_iter(), buf, purpose)
+    }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        let mut values: Vec<&T> = self.iter().collect();
+        values.sort();
+        raw_digest_seq(values.len(), values.into_iter(), writer, purpose)
+    }
+}
+
+impl<'de, T: RawDecode<'de> + Ord> RawDecode<'de> for BinaryHeap<T> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let mut values: Vec<T> = Vec::new();
+        let (_, buf) = raw_decode_seq(buf, |e| values.push(e))?;
+
+        // 编码时按升序排列，允许相同的元素
+        if is_canonical_decode() && values.windows(2).any(|w| w[0] > w[1]) {
+            return Err(non_canonical_error(
+                "BinaryHeap values are not in canonical order".to_owned(),
+            ));
+        }
+        Ok((BinaryHeap::from(values), buf))
+    }
+}
+
+// IndexMap<K, V>和IndexSet<T>，按插入顺序编码
+#[cfg(feature = "indexmap")]
+mod indexmap_impl {
+    use super::*;
+    use indexmap::{IndexMap, IndexSet};
+    use std::hash::BuildHasher;
+
+    impl<T: RawEncode, S> RawEncode for IndexSet<T, S> {
+        fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+            raw_measure_seq(self.len(), self.iter(), purpose)
+        }
+
+        fn raw_encode<'a>(
+            &self,
+            buf: &'a mut [u8],
+            purpose: &Option<RawEncodePurpose>,
+        ) -> CodecResult<&'a mut [u8]> {
+            raw_encode_seq(self.len(), self.iter(), buf, purpose)
+        }
+
+        fn raw_digest(
+            &self,
+            writer: &mut RawDigestWriter,
+            purpose: &Option<RawEncodePurpose>,
+        ) -> CodecResult<()> {
+            raw_digest_seq(self.len(), self.iter(), writer, purpose)
+        }
+    }
+
+    impl<'de, T: RawDecode<'de> + Hash + Eq, S: BuildHasher + Default> RawDecode<'de> for IndexSet<T, S> {
+        fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+            // 与HashSet一致，重复的元素会被合并，规范模式下视为错误
+            let mut set = IndexSet::with_hasher(S::default());
+            let mut duplicated = false;
+            let (_, buf) = raw_decode_seq(buf, |e| {
+                duplicated |= !set.insert(e);
+            })?;
+            if duplicated && is_canonical_decode() {
+                return Err(non_canonical_error("IndexSet has duplicate keys".to_owned()));
+            }
+            Ok((set, buf))
+        }
+    }
+
+    impl<K: RawEncode, V: RawEncode, S> RawEncode for IndexMap<K, V, S> {
+        fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+            let mut size = USize(self.len()).raw_measure(purpose)?;
+            for (key, value) in self {
+                size += key.raw_measure(purpose)? + value.raw_measure(purpose)?;
+            }
+            Ok(size)
+        }
+
+        fn raw_encode<'a>(
+            &self,
+            buf: &'a mut [u8],
+            purpose: &Option<RawEncodePurpose>,
+        ) -> CodecResult<&'a mut [u8]> {
+            let mut buf = USize(self.len()).raw_encode(buf, purpose)?;
+            for (key, value) in self {
+                buf = key.raw_encode(buf, purpose)?;
+                buf = value.raw_encode(buf, purpose)?;
+            }
+            Ok(buf)
+        }
+
+        fn raw_digest(
+            &self,
+            writer: &mut RawDigestWriter,
+            purpose: &Option<RawEncodePurpose>,
+        ) -> CodecResult<()> {
+            USize(self.len()).raw_digest(writer, purpose)?;
+            for (key, value) in self {
+                key.raw_digest(writer, purpose)?;
+                value.raw_digest(writer, purpose)?;
+            }
+            Ok(())
+        }
+    }
+
+    impl<'de, K: RawDecode<'de> + Hash + Eq, V: RawDecode<'de>, S: BuildHasher + Default> RawDecode<'de>
+        for IndexMap<K, V, S>
+    {
+        fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+            let (ulen, mut buf) = USize::raw_decode(buf)?;
+            let len = ulen.value();
+            check_container_len(len)?;
+
+            // 与HashMap一致，重复的key后面的值覆盖前面的，规范模式下视为错误
+            let canonical = is_canonical_decode();
+            let mut map = IndexMap::with_hasher(S::default());
+            for _ in 0..len {
+                let (key, tmp_buf) = K::raw_decode(buf)?;
+                let (v, tmp_buf) = V::raw_decode(tmp_buf)?;
+                buf = tmp_buf;
+                if map.insert(key, v).is_some() && canonical {
+                    return Err(non_canonical_error("IndexMap has duplicate keys".to_owned()));
+                }
+            }
+            Ok((map, buf))
+        }
+    }
+}
+
+// SmallVec，与Vec编码一致
+#[cfg(feature = "smallvec")]
+mod smallvec_impl {
+    use super::*;
+    use smallvec::{Array, SmallVec};
+
+    impl<A: Array> RawEncode for SmallVec<A>
+    where
+        A::Item: RawEncode,
+    {
+        fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+            self.as_slice().raw_measure(purpose)
+        }
+
+        fn raw_encode<'a>(
+            &self,
+            buf: &'a mut [u8],
+            purpose: &Option<RawEncodePurpose>,
+        ) -> CodecResult<&'a mut [u8]> {
+            self.as_slice().raw_encode(buf, purpose)
+        }
+
+        fn raw_digest(
+            &self,
+            writer: &mut RawDigestWriter,
+            purpose: &Option<RawEncodePurpose>,
+        ) -> CodecResult<()> {
+            self.as_slice().raw_digest(writer, purpose)
+        }
+    }
+
+    impl<'de, A: Array> RawDecode<'de> for SmallVec<A>
+    where
+        A::Item: RawDecode<'de>,
+    {
+        fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+            let mut list = SmallVec::new();
+            let (_, buf) = raw_decode_seq(buf, |e| list.push(e))?;
+            Ok((list, buf))
+        }
+    }
+}
+
+// Mutex
+
+use std::sync::Mutex;
+
+// 锁被污染时返回错误，而不是panic
+fn poisoned_error<T: ?Sized>(name: &str) -> CodecError {
+    let msg = format!("{} is poisoned: {}", name, std::any::type_name::<T>());
+    error!("{}", msg);
+    CodecError::new(CodecErrorCode::ErrorState, msg)
+}
+
+impl<V: RawEncode + ?Sized> RawEncode for Mutex<V> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        let v = self.lock().map_err(|_| poisoned_error::<Self>("Mutex"))?;
+        v.raw_measure(purpose)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let v = self.lock().map_err(|_| poisoned_error::<Self>("Mutex"))?;
+        v.raw_encode(buf, purpose)
+    }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        let v = self.lock().map_err(|_| poisoned_error::<Self>("Mutex"))?;
+        v.raw_digest(writer, purpose)
+    }
+}
+
+impl<'de, V: RawDecode<'de>> RawDecode<'de> for Mutex<V> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (v, buf) = V::raw_decode(buf)?;
+        Ok((Mutex::new(v), buf))
+    }
+}
+
+// Arc
+use std::sync::Arc;
+
+impl<V: RawEncode + ?Sized> RawEncode for Arc<V> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        let v = self.deref();
+        v.raw_measure(purpose)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let v = self.deref();
+        v.raw_encode(buf, purpose)
+    }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        let v = self.deref();
+        v.raw_digest(writer, purpose)
+    }
+}
+
+impl<'de, V: RawDecode<'de>> RawDecode<'de> for Arc<V> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (v, buf) = V::raw_decode(buf)?;
+        Ok((Arc::new(v), buf))
+    }
+}
+
+// 透明编码的智能指针，编码结果与内部对象一致
+macro_rules! raw_codec_deref_encode {
+    ($ty:ident) => {
+        impl<V: RawEncode + ?Sized> RawEncode for $ty<V> {
+            fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+                self.deref().raw_measure(purpose)
+            }
+
+            fn raw_encode<'a>(
+                &self,
+                buf: &'a mut [u8],
+                purpose: &Option<RawEncodePurpose>,
+            ) -> CodecResult<&'a mut [u8]> {
+                self.deref().raw_encode(buf, purpose)
+            }
+
+            fn raw_digest(
+                &self,
+                writer: &mut RawDigestWriter,
+                purpose: &Option<RawEncodePurpose>,
+            ) -> CodecResult<()> {
+                self.deref().raw_digest(writer, purpose)
+            }
+        }
+    };
 }
 
-impl<'de, K: RawDecode<'de> + std::cmp::Ord + Eq, V: RawDecode<'de>> RawDecode<'de>
-    for BTreeMap<K, V>
-{
+// Box
+raw_codec_deref_encode!(Box);
+
+impl<'de, V: RawDecode<'de>> RawDecode<'de> for Box<V> {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
-        let (ulen, mut buf) = USize::raw_decode(buf)?;
-        let len = ulen.value();
-        let mut map = BTreeMap::new();
-        for _ in 0..len {
-            let (key, tmp_buf) = K::raw_decode(buf)?;
-            let (v, tmp_buf) = V::raw_decode(tmp_buf)?;
-            buf = tmp_buf;
-            map.insert(key, v);
-        }
-        Ok((map, buf))
+        let (v, buf) = V::raw_decode(buf)?;
+        Ok((Box::new(v), buf))
     }
 }
 
-// Mutex
-
-use std::sync::Mutex;
-
-impl<V: RawEncode> RawEncode for Mutex<V> {
-    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
-        let v = self.lock().unwrap();
-        v.raw_measure(purpose)
+// Box<[T]>与Vec<T>编码一致
+impl<'de, T: RawEncode + RawDecode<'de>> RawDecode<'de> for Box<[T]> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (v, buf) = Vec::<T>::raw_decode(buf)?;
+        Ok((v.into_boxed_slice(), buf))
     }
+}
 
-    fn raw_encode<'a>(
-        &self,
-        buf: &'a mut [u8],
-        purpose: &Option<RawEncodePurpose>,
-    ) -> CodecResult<&'a mut [u8]> {
-        let v = self.lock().unwrap();
-        v.raw_encode(buf, purpose)
+// Box<str>与String编码一致
+impl<'de> RawDecode<'de> for Box<str> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (v, buf) = String::raw_decode(buf)?;
+        Ok((v.into_boxed_str(), buf))
     }
 }
 
-impl<'de, V: RawDecode<'de>> RawDecode<'de> for Mutex<V> {
+// Rc
+use std::rc::Rc;
+
+raw_codec_deref_encode!(Rc);
+
+impl<'de, V: RawDecode<'de>> RawDecode<'de> for Rc<V> {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
         let (v, buf) = V::raw_decode(buf)?;
-        Ok((Mutex::new(v), buf))
+        Ok((Rc::new(v), buf))
     }
 }
 
-// Arc
-use std::sync::Arc;
+// Cow，解码后总是Owned
+use std::borrow::Cow;
 
-impl<V: RawEncode> RawEncode for Arc<V> {
+impl<'b, V: RawEncode + ToOwned + ?Sized> RawEncode for Cow<'b, V> {
     fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
-        let v = self.deref();
-        v.raw_measure(purpose)
+        self.as_ref().raw_measure(purpose)
     }
 
     fn raw_encode<'a>(
@@ -972,25 +2115,34 @@ impl<V: RawEncode> RawEncode for Arc<V> {
         buf: &'a mut [u8],
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]> {
-        let v = self.deref();
-        v.raw_encode(buf, purpose)
+        self.as_ref().raw_encode(buf, purpose)
+    }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        self.as_ref().raw_digest(writer, purpose)
     }
 }
 
-impl<'de, V: RawDecode<'de>> RawDecode<'de> for Arc<V> {
+impl<'de, 'b, V: ToOwned + ?Sized> RawDecode<'de> for Cow<'b, V>
+where
+    V::Owned: RawDecode<'de>,
+{
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
-        let (v, buf) = V::raw_decode(buf)?;
-        Ok((Arc::new(v), buf))
+        let (v, buf) = V::Owned::raw_decode(buf)?;
+        Ok((Cow::Owned(v), buf))
     }
 }
 
-// Atomici32
-
-use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+// RwLock
+use std::sync::RwLock;
 
-impl RawEncode for AtomicI32 {
+impl<V: RawEncode + ?Sized> RawEncode for RwLock<V> {
     fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
-        let v = self.load(Ordering::SeqCst);
+        let v = self.read().map_err(|_| poisoned_error::<Self>("RwLock"))?;
         v.raw_measure(purpose)
     }
 
@@ -999,23 +2151,39 @@ impl RawEncode for AtomicI32 {
         buf: &'a mut [u8],
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]> {
-        let v = self.load(Ordering::SeqCst);
+        let v = self.read().map_err(|_| poisoned_error::<Self>("RwLock"))?;
         v.raw_encode(buf, purpose)
     }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        let v = self.read().map_err(|_| poisoned_error::<Self>("RwLock"))?;
+        v.raw_digest(writer, purpose)
+    }
 }
 
-impl<'de> RawDecode<'de> for AtomicI32 {
+impl<'de, V: RawDecode<'de>> RawDecode<'de> for RwLock<V> {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
-        let (v, buf) = i32::raw_decode(buf)?;
-        Ok((AtomicI32::new(v), buf))
+        let (v, buf) = V::raw_decode(buf)?;
+        Ok((RwLock::new(v), buf))
     }
 }
 
-// Atomicu32
+// RefCell，已经被可变借用时返回错误
+use std::cell::{Cell, RefCell};
 
-impl RawEncode for AtomicU32 {
+fn borrowed_error<T: ?Sized>() -> CodecError {
+    let msg = format!("RefCell is already mutably borrowed: {}", std::any::type_name::<T>());
+    error!("{}", msg);
+    CodecError::new(CodecErrorCode::ErrorState, msg)
+}
+
+impl<V: RawEncode + ?Sized> RawEncode for RefCell<V> {
     fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
-        let v = self.load(Ordering::SeqCst);
+        let v = self.try_borrow().map_err(|_| borrowed_error::<Self>())?;
         v.raw_measure(purpose)
     }
 
@@ -1024,24 +2192,43 @@ impl RawEncode for AtomicU32 {
         buf: &'a mut [u8],
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]> {
-        let v = self.load(Ordering::SeqCst);
+        let v = self.try_borrow().map_err(|_| borrowed_error::<Self>())?;
         v.raw_encode(buf, purpose)
     }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        let v = self.try_borrow().map_err(|_| borrowed_error::<Self>())?;
+        v.raw_digest(writer, purpose)
+    }
 }
 
-impl<'de> RawDecode<'de> for AtomicU32 {
+impl<'de, V: RawDecode<'de>> RawDecode<'de> for RefCell<V> {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
-        let (v, buf) = u32::raw_decode(buf)?;
-        Ok((AtomicU32::new(v), buf))
+        let (v, buf) = V::raw_decode(buf)?;
+        Ok((RefCell::new(v), buf))
     }
 }
 
-// tuple
+// Cell
+impl<V: RawFixedBytes + Copy> RawFixedBytes for Cell<V> {
+    fn raw_bytes() -> Option<usize> {
+        V::raw_bytes()
+    }
+    fn raw_max_bytes() -> Option<usize> {
+        V::raw_max_bytes()
+    }
+    fn raw_min_bytes() -> Option<usize> {
+        V::raw_min_bytes()
+    }
+}
 
-impl<T: RawEncode, U: RawEncode> RawEncode for (T, U) {
+impl<V: RawEncode + Copy> RawEncode for Cell<V> {
     fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
-        let bytes = self.0.raw_measure(purpose)? + self.1.raw_measure(purpose)?;
-        Ok(bytes)
+        self.get().raw_measure(purpose)
     }
 
     fn raw_encode<'a>(
@@ -1049,62 +2236,159 @@ impl<T: RawEncode, U: RawEncode> RawEncode for (T, U) {
         buf: &'a mut [u8],
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]> {
-        let buf = self.0.raw_encode(buf, purpose)?;
-        let buf = self.1.raw_encode(buf, purpose)?;
-        Ok(buf)
+        self.get().raw_encode(buf, purpose)
     }
 }
 
-impl<'de, T: RawEncode + RawDecode<'de>, U: RawEncode + RawDecode<'de>> RawDecode<'de> for (T, U) {
+impl<'de, V: RawDecode<'de>> RawDecode<'de> for Cell<V> {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
-        let (e, buf) = T::raw_decode(buf)?;
-        let (u, buf) = U::raw_decode(buf)?;
-        Ok(((e, u), buf))
+        let (v, buf) = V::raw_decode(buf)?;
+        Ok((Cell::new(v), buf))
     }
 }
 
-impl<T1: RawEncode, T2: RawEncode, T3: RawEncode> RawEncode for (T1, T2, T3) {
-    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
-        let bytes = self.0.raw_measure(purpose)?
-            + self.1.raw_measure(purpose)?
-            + self.2.raw_measure(purpose)?;
-        Ok(bytes)
+// PhantomData，不占用编码空间
+impl<V: ?Sized> RawFixedBytes for PhantomData<V> {
+    fn raw_bytes() -> Option<usize> {
+        Some(0)
+    }
+}
+
+impl<V: ?Sized> RawEncode for PhantomData<V> {
+    fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        Ok(0)
     }
 
     fn raw_encode<'a>(
         &self,
         buf: &'a mut [u8],
-        purpose: &Option<RawEncodePurpose>,
+        _purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]> {
-        let buf = self.0.raw_encode(buf, purpose)?;
-        let buf = self.1.raw_encode(buf, purpose)?;
-        let buf = self.2.raw_encode(buf, purpose)?;
         Ok(buf)
     }
 }
 
-impl<
-        'de,
-        T1: RawEncode + RawDecode<'de>,
-        T2: RawEncode + RawDecode<'de>,
-        T3: RawEncode + RawDecode<'de>,
-    > RawDecode<'de> for (T1, T2, T3)
-{
+impl<'de, V: ?Sized> RawDecode<'de> for PhantomData<V> {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
-        let (t1, buf) = T1::raw_decode(buf)?;
-        let (t2, buf) = T2::raw_decode(buf)?;
-        let (t3, buf) = T3::raw_decode(buf)?;
-        Ok(((t1, t2, t3), buf))
+        Ok((PhantomData, buf))
     }
 }
 
-impl<T1: RawEncode, T2: RawEncode, T3: RawEncode, T4: RawEncode> RawEncode for (T1, T2, T3, T4) {
+// Reverse和Wrapping，与内部对象编码一致
+macro_rules! raw_codec_newtype {
+    ($ty:ident) => {
+        impl<V: RawFixedBytes> RawFixedBytes for $ty<V> {
+            fn raw_bytes() -> Option<usize> {
+                V::raw_bytes()
+            }
+            fn raw_max_bytes() -> Option<usize> {
+                V::raw_max_bytes()
+            }
+            fn raw_min_bytes() -> Option<usize> {
+                V::raw_min_bytes()
+            }
+        }
+
+        impl<V: RawEncode> RawEncode for $ty<V> {
+            fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+                self.0.raw_measure(purpose)
+            }
+
+            fn raw_encode<'a>(
+                &self,
+                buf: &'a mut [u8],
+                purpose: &Option<RawEncodePurpose>,
+            ) -> CodecResult<&'a mut [u8]> {
+                self.0.raw_encode(buf, purpose)
+            }
+
+            fn raw_digest(
+                &self,
+                writer: &mut RawDigestWriter,
+                purpose: &Option<RawEncodePurpose>,
+            ) -> CodecResult<()> {
+                self.0.raw_digest(writer, purpose)
+            }
+        }
+
+        impl<'de, V: RawDecode<'de>> RawDecode<'de> for $ty<V> {
+            fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+                let (v, buf) = V::raw_decode(buf)?;
+                Ok(($ty(v), buf))
+            }
+        }
+    };
+}
+
+use std::cmp::Reverse;
+use std::num::Wrapping;
+
+raw_codec_newtype!(Reverse);
+raw_codec_newtype!(Wrapping);
+
+// NonZero*，与对应的整数编码一致，解码时拒绝0
+macro_rules! raw_codec_non_zero {
+    ($ty:ident, $inner:ty) => {
+        impl RawFixedBytes for $ty {
+            fn raw_bytes() -> Option<usize> {
+                <$inner>::raw_bytes()
+            }
+        }
+
+        impl RawEncode for $ty {
+            fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+                self.get().raw_measure(purpose)
+            }
+
+            fn raw_encode<'a>(
+                &self,
+                buf: &'a mut [u8],
+                purpose: &Option<RawEncodePurpose>,
+            ) -> CodecResult<&'a mut [u8]> {
+                self.get().raw_encode(buf, purpose)
+            }
+        }
+
+        impl<'de> RawDecode<'de> for $ty {
+            fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+                let (v, buf) = <$inner>::raw_decode(buf)?;
+                let v = $ty::new(v).ok_or_else(|| {
+                    CodecError::new(
+                        CodecErrorCode::InvalidFormat,
+                        concat!("zero value for ", stringify!($ty)),
+                    )
+                })?;
+                Ok((v, buf))
+            }
+        }
+    };
+}
+
+use std::num::{
+    NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroU128, NonZeroU16,
+    NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize,
+};
+
+raw_codec_non_zero!(NonZeroU8, u8);
+raw_codec_non_zero!(NonZeroU16, u16);
+raw_codec_non_zero!(NonZeroU32, u32);
+raw_codec_non_zero!(NonZeroU64, u64);
+raw_codec_non_zero!(NonZeroU128, u128);
+raw_codec_non_zero!(NonZeroUsize, usize);
+raw_codec_non_zero!(NonZeroI8, i8);
+raw_codec_non_zero!(NonZeroI16, i16);
+raw_codec_non_zero!(NonZeroI32, i32);
+raw_codec_non_zero!(NonZeroI64, i64);
+raw_codec_non_zero!(NonZeroI128, i128);
+
+// Atomici32
+
+use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+
+impl RawEncode for AtomicI32 {
     fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
-        let bytes = self.0.raw_measure(purpose)?
-            + self.1.raw_measure(purpose)?
-            + self.2.raw_measure(purpose)?
-            + self.3.raw_measure(purpose)?;
-        Ok(bytes)
+        let v = self.load(Ordering::SeqCst);
+        v.raw_measure(purpose)
     }
 
     fn raw_encode<'a>(
@@ -1112,41 +2396,24 @@ impl<T1: RawEncode, T2: RawEncode, T3: RawEncode, T4: RawEncode> RawEncode for (
         buf: &'a mut [u8],
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]> {
-        let buf = self.0.raw_encode(buf, purpose)?;
-        let buf = self.1.raw_encode(buf, purpose)?;
-        let buf = self.2.raw_encode(buf, purpose)?;
-        let buf = self.3.raw_encode(buf, purpose)?;
-        Ok(buf)
+        let v = self.load(Ordering::SeqCst);
+        v.raw_encode(buf, purpose)
     }
 }
 
-impl<
-        'de,
-        T1: RawEncode + RawDecode<'de>,
-        T2: RawEncode + RawDecode<'de>,
-        T3: RawEncode + RawDecode<'de>,
-        T4: RawEncode + RawDecode<'de>,
-    > RawDecode<'de> for (T1, T2, T3, T4)
-{
+impl<'de> RawDecode<'de> for AtomicI32 {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
-        let (t1, buf) = T1::raw_decode(buf)?;
-        let (t2, buf) = T2::raw_decode(buf)?;
-        let (t3, buf) = T3::raw_decode(buf)?;
-        let (t4, buf) = T4::raw_decode(buf)?;
-        Ok(((t1, t2, t3, t4), buf))
+        let (v, buf) = i32::raw_decode(buf)?;
+        Ok((AtomicI32::new(v), buf))
     }
 }
 
-impl<T1: RawEncode, T2: RawEncode, T3: RawEncode, T4: RawEncode, T5: RawEncode> RawEncode
-    for (T1, T2, T3, T4, T5)
-{
+// Atomicu32
+
+impl RawEncode for AtomicU32 {
     fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
-        let bytes = self.0.raw_measure(purpose)?
-            + self.1.raw_measure(purpose)?
-            + self.2.raw_measure(purpose)?
-            + self.3.raw_measure(purpose)?
-            + self.4.raw_measure(purpose)?;
-        Ok(bytes)
+        let v = self.load(Ordering::SeqCst);
+        v.raw_measure(purpose)
     }
 
     fn raw_encode<'a>(
@@ -1154,34 +2421,81 @@ impl<T1: RawEncode, T2: RawEncode, T3: RawEncode, T4: RawEncode, T5: RawEncode>
         buf: &'a mut [u8],
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]> {
-        let buf = self.0.raw_encode(buf, purpose)?;
-        let buf = self.1.raw_encode(buf, purpose)?;
-        let buf = self.2.raw_encode(buf, purpose)?;
-        let buf = self.3.raw_encode(buf, purpose)?;
-        let buf = self.4.raw_encode(buf, purpose)?;
-        Ok(buf)
+        let v = self.load(Ordering::SeqCst);
+        v.raw_encode(buf, purpose)
     }
 }
 
-impl<
-        'de,
-        T1: RawEncode + RawDecode<'de>,
-        T2: RawEncode + RawDecode<'de>,
-        T3: RawEncode + RawDecode<'de>,
-        T4: RawEncode + RawDecode<'de>,
-        T5: RawEncode + RawDecode<'de>,
-    > RawDecode<'de> for (T1, T2, T3, T4, T5)
-{
+impl<'de> RawDecode<'de> for AtomicU32 {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
-        let (t1, buf) = T1::raw_decode(buf)?;
-        let (t2, buf) = T2::raw_decode(buf)?;
-        let (t3, buf) = T3::raw_decode(buf)?;
-        let (t4, buf) = T4::raw_decode(buf)?;
-        let (t5, buf) = T5::raw_decode(buf)?;
-        Ok(((t1, t2, t3, t4, t5), buf))
+        let (v, buf) = u32::raw_decode(buf)?;
+        Ok((AtomicU32::new(v), buf))
     }
 }
 
+// tuple，包括()和单元素元组，依次编码每个元素
+macro_rules! raw_codec_tuple {
+    ($($name:ident $index:tt),*) => {
+        impl<$($name: RawFixedBytes),*> RawFixedBytes for ($($name,)*) {
+            fn raw_bytes() -> Option<usize> {
+                Some(0 $(+ $name::raw_bytes()?)*)
+            }
+            fn raw_max_bytes() -> Option<usize> {
+                Some(0 $(+ $name::raw_max_bytes()?)*)
+            }
+            fn raw_min_bytes() -> Option<usize> {
+                Some(0 $(+ $name::raw_min_bytes()?)*)
+            }
+        }
+
+        impl<$($name: RawEncode),*> RawEncode for ($($name,)*) {
+            fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+                Ok(0 $(+ self.$index.raw_measure(purpose)?)*)
+            }
+
+            fn raw_encode<'a>(
+                &self,
+                buf: &'a mut [u8],
+                purpose: &Option<RawEncodePurpose>,
+            ) -> CodecResult<&'a mut [u8]> {
+                $(let buf = self.$index.raw_encode(buf, purpose)?;)*
+                Ok(buf)
+            }
+
+            fn raw_digest(
+                &self,
+                writer: &mut RawDigestWriter,
+                purpose: &Option<RawEncodePurpose>,
+            ) -> CodecResult<()> {
+                $(self.$index.raw_digest(writer, purpose)?;)*
+                Ok(())
+            }
+        }
+
+        impl<'de, $($name: RawDecode<'de>),*> RawDecode<'de> for ($($name,)*) {
+            #[allow(non_snake_case)]
+            fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+                $(let ($name, buf) = $name::raw_decode(buf)?;)*
+                Ok((($($name,)*), buf))
+            }
+        }
+    };
+}
+
+raw_codec_tuple!();
+raw_codec_tuple!(T1 0);
+raw_codec_tuple!(T1 0, T2 1);
+raw_codec_tuple!(T1 0, T2 1, T3 2);
+raw_codec_tuple!(T1 0, T2 1, T3 2, T4 3);
+raw_codec_tuple!(T1 0, T2 1, T3 2, T4 3, T5 4);
+raw_codec_tuple!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5);
+raw_codec_tuple!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6);
+raw_codec_tuple!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7);
+raw_codec_tuple!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8);
+raw_codec_tuple!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9);
+raw_codec_tuple!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10);
+raw_codec_tuple!(T1 0, T2 1, T3 2, T4 3, T5 4, T6 5, T7 6, T8 7, T9 8, T10 9, T11 10, T12 11);
+
 // &str只是单纯用来计算编码后的大小
 impl RawFixedBytes for &str {
     fn raw_min_bytes() -> Option<usize> {
@@ -1488,9 +2802,46 @@ impl<'de, T: RawEncode + RawDecode<'de> + Default, U: ArrayLength<T>> RawDecode<
     }
 }
 
+// [T; N]，定长数组，不编码长度
+impl<T: RawFixedBytes, const N: usize> RawFixedBytes for [T; N] {
+    fn raw_bytes() -> Option<usize> {
+        T::raw_bytes().map(|size| size * N)
+    }
+    fn raw_max_bytes() -> Option<usize> {
+        T::raw_max_bytes().map(|size| size * N)
+    }
+    fn raw_min_bytes() -> Option<usize> {
+        T::raw_min_bytes().map(|size| size * N)
+    }
+}
+
+impl<T: RawEncode, const N: usize> RawEncode for [T; N] {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        let mut bytes = 0;
+        for e in self {
+            bytes += e.raw_measure(purpose)?;
+        }
+        Ok(bytes)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        T::raw_encode_array(self, buf, purpose)
+    }
+}
+
+impl<'de, T: RawDecode<'de>, const N: usize> RawDecode<'de> for [T; N] {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        T::raw_decode_array(buf)
+    }
+}
+
 // SizedOwnedData
 // 包含编码大小的数据段，从buf中拷贝出来
-use primitive_types::H256;
+use primitive_types::{H128, H160, H256, H512, U128, U256, U512};
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 
@@ -1739,23 +3090,147 @@ impl<'de, T: From<usize> + RawDecode<'de> + Into<usize>> RawDecode<'de> for Size
                 "not enough buffer for SizedOwnedData",
             ));
         }
-        let mut data = Vec::with_capacity(size);
-        unsafe {
-            data.set_len(size);
-            std::ptr::copy(buf.as_ptr(), data.as_mut_ptr(), size);
+        let mut data = Vec::with_capacity(size);
+        unsafe {
+            data.set_len(size);
+            std::ptr::copy(buf.as_ptr(), data.as_mut_ptr(), size);
+        }
+        let buf = &buf[data.len()..];
+        Ok((Self(data, None), buf))
+    }
+}
+
+// SizePrefixed
+// 带BuckySize长度前缀的编码，解码时按长度截取buf后再解码内部的值
+// 用于解码需要消费整个buf的类型(比如protobuf编码的类型)，可以和raw编码的字段混合在同一个结构体中
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct SizePrefixed<T>(pub T);
+
+impl<T> SizePrefixed<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for SizePrefixed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for SizePrefixed<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> From<T> for SizePrefixed<T> {
+    fn from(v: T) -> Self {
+        Self(v)
+    }
+}
+
+impl<T: RawEncode> RawEncode for SizePrefixed<T> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        let size = self.0.raw_measure(purpose)?;
+        Ok(USize(size).raw_measure(purpose)? + size)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let size = self.0.raw_measure(purpose)?;
+        let buf = USize(size).raw_encode(buf, purpose)?;
+        if buf.len() < size {
+            let msg = format!(
+                "not enough buffer for SizePrefixed, required={}, remaining={}",
+                size,
+                buf.len()
+            );
+            error!("{}", msg);
+            return Err(CodecError::new(CodecErrorCode::OutOfLimit, msg));
+        }
+
+        // 内部的值只能写入长度前缀声明的范围
+        let (data, buf) = buf.split_at_mut(size);
+        let remain = self.0.raw_encode(data, purpose)?;
+        if !remain.is_empty() {
+            let msg = format!(
+                "SizePrefixed encode size mismatch, measure={}, encoded={}",
+                size,
+                size - remain.len()
+            );
+            error!("{}", msg);
+            return Err(CodecError::new(CodecErrorCode::ErrorState, msg));
+        }
+
+        Ok(buf)
+    }
+}
+
+impl<'de, T: RawDecode<'de>> SizePrefixed<T> {
+    fn split_data(buf: &'de [u8]) -> CodecResult<(&'de [u8], &'de [u8])> {
+        let (size, buf) = USize::raw_decode(buf)?;
+        let size = size.value();
+        if buf.len() < size {
+            let msg = format!(
+                "not enough buffer for SizePrefixed, required={}, remaining={}",
+                size,
+                buf.len()
+            );
+            error!("{}", msg);
+            return Err(CodecError::new(CodecErrorCode::OutOfLimit, msg));
+        }
+
+        Ok(buf.split_at(size))
+    }
+}
+
+impl<'de, T: RawDecode<'de>> RawDecode<'de> for SizePrefixed<T> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (data, buf) = Self::split_data(buf)?;
+        Ok((Self(T::decode_exact(data)?), buf))
+    }
+
+    fn raw_decode_with_option(
+        buf: &'de [u8],
+        opt: &RawDecodeOption,
+    ) -> CodecResult<(Self, &'de [u8])> {
+        let (data, buf) = Self::split_data(buf)?;
+        let (value, remain) = T::raw_decode_with_option(data, opt)?;
+        check_no_trailing_data::<T>(remain)?;
+        if opt.strict {
+            check_no_trailing_data::<Self>(buf)?;
         }
-        let buf = &buf[data.len()..];
-        Ok((Self(data, None), buf))
+        Ok((Self(value), buf))
     }
 }
 
 // SizedSharedData
 // 包含编码大小的数据段，从buf引用
 pub struct SizedSharedData<'de>(&'de [u8]);
+
+impl AsRef<[u8]> for SizedSharedData<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.0
+    }
+}
+
 impl<'de> RawDecode<'de> for SizedSharedData<'de> {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
         let (len, buf) = u16::raw_decode(buf)?;
-        Ok((Self(&buf[len as usize..]), buf))
+        let len = len as usize;
+        if buf.len() < len {
+            return Err(CodecError::new(
+                CodecErrorCode::OutOfLimit,
+                "not enough buffer for SizedSharedData",
+            ));
+        }
+        Ok((Self(&buf[..len]), &buf[len..]))
     }
 }
 
@@ -1879,11 +3354,97 @@ impl<'de> RawDecode<'de> for TailedSharedData<'de> {
     }
 }
 
+// Ipv4Addr，4字节网络序
+impl RawFixedBytes for Ipv4Addr {
+    fn raw_bytes() -> Option<usize> {
+        Some(4)
+    }
+}
+
+impl RawEncode for Ipv4Addr {
+    fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        Ok(4)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        _purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        if buf.len() < 4 {
+            return Err(CodecError::new(
+                CodecErrorCode::OutOfLimit,
+                "not enough buffer for Ipv4Addr",
+            ));
+        }
+        buf[..4].copy_from_slice(&self.octets());
+
+        Ok(&mut buf[4..])
+    }
+}
+
+impl<'de> RawDecode<'de> for Ipv4Addr {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        if buf.len() < 4 {
+            return Err(CodecError::new(
+                CodecErrorCode::OutOfLimit,
+                "not enough buffer for Ipv4Addr",
+            ));
+        }
+        let addr = Ipv4Addr::new(buf[0], buf[1], buf[2], buf[3]);
+        Ok((addr, &buf[4..]))
+    }
+}
+
+// Ipv6Addr，16字节网络序
+impl RawFixedBytes for Ipv6Addr {
+    fn raw_bytes() -> Option<usize> {
+        Some(16)
+    }
+}
+
+impl RawEncode for Ipv6Addr {
+    fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        Ok(16)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        _purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        if buf.len() < 16 {
+            return Err(CodecError::new(
+                CodecErrorCode::OutOfLimit,
+                "not enough buffer for Ipv6Addr",
+            ));
+        }
+        buf[..16].copy_from_slice(&self.octets());
+
+        Ok(&mut buf[16..])
+    }
+}
+
+impl<'de> RawDecode<'de> for Ipv6Addr {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        if buf.len() < 16 {
+            return Err(CodecError::new(
+                CodecErrorCode::OutOfLimit,
+                "not enough buffer for Ipv6Addr",
+            ));
+        }
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&buf[..16]);
+        Ok((Ipv6Addr::from(octets), &buf[16..]))
+    }
+}
+
+// IpAddr，1字节类型(0: v4, 1: v6) + 地址
 impl RawEncode for IpAddr {
     fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
         Ok(match self {
-            IpAddr::V4(_) => u8::raw_bytes().unwrap() + 4,
-            IpAddr::V6(_) => u8::raw_bytes().unwrap() + 16,
+            IpAddr::V4(_) => u8::raw_bytes().unwrap() + Ipv4Addr::raw_bytes().unwrap(),
+            IpAddr::V6(_) => u8::raw_bytes().unwrap() + Ipv6Addr::raw_bytes().unwrap(),
         })
     }
 
@@ -1893,43 +3454,13 @@ impl RawEncode for IpAddr {
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]> {
         match self {
-            IpAddr::V4(ref sock_addr) => {
+            IpAddr::V4(addr) => {
                 let buf = (0u8).raw_encode(buf, purpose)?;
-
-                if buf.len() < 4 {
-                    Err(CodecError::new(
-                        CodecErrorCode::OutOfLimit,
-                        "not enough buffer for IpAddr",
-                    ))
-                } else {
-                    unsafe {
-                        std::ptr::copy(
-                            sock_addr.octets().as_ptr() as *const u8,
-                            buf.as_mut_ptr(),
-                            4,
-                        );
-                    }
-                    Ok(&mut buf[4..])
-                }
+                addr.raw_encode(buf, purpose)
             }
-            IpAddr::V6(ref sock_addr) => {
+            IpAddr::V6(addr) => {
                 let buf = (1u8).raw_encode(buf, purpose)?;
-
-                if buf.len() < 16 {
-                    Err(CodecError::new(
-                        CodecErrorCode::OutOfLimit,
-                        "not enough buffer for IpAddr",
-                    ))
-                } else {
-                    unsafe {
-                        std::ptr::copy(
-                            sock_addr.octets().as_ptr() as *const u8,
-                            buf.as_mut_ptr(),
-                            16,
-                        );
-                    }
-                    Ok(&mut buf[16..])
-                }
+                addr.raw_encode(buf, purpose)
             }
         }
     }
@@ -1940,28 +3471,121 @@ impl<'de> RawDecode<'de> for IpAddr {
         let (ty, buf) = u8::raw_decode(buf)?;
         match ty {
             0 => {
-                if buf.len() < 4 {
-                    return Err(CodecError::new(
-                        CodecErrorCode::OutOfLimit,
-                        "not enough buffer for IpAddr",
-                    ));
-                }
-                let addr = IpAddr::V4(Ipv4Addr::new(buf[0], buf[1], buf[2], buf[3]));
-                Ok((addr, &buf[4..]))
+                let (addr, buf) = Ipv4Addr::raw_decode(buf)?;
+                Ok((IpAddr::V4(addr), buf))
             }
             1 => {
-                if buf.len() < 16 {
-                    return Err(CodecError::new(
-                        CodecErrorCode::OutOfLimit,
-                        "not enough buffer for IpAddr",
-                    ));
-                }
+                // IpAddr本身不带flowinfo和scope_id，需要的话使用SocketAddrV6
+                let (addr, buf) = Ipv6Addr::raw_decode(buf)?;
+                Ok((IpAddr::V6(addr), buf))
+            }
+            _ => Err(CodecError::new(CodecErrorCode::NotSupport, "NotSupport")),
+        }
+    }
+}
+
+// SocketAddrV4，地址 + 端口
+impl RawFixedBytes for SocketAddrV4 {
+    fn raw_bytes() -> Option<usize> {
+        Some(Ipv4Addr::raw_bytes().unwrap() + u16::raw_bytes().unwrap())
+    }
+}
+
+impl RawEncode for SocketAddrV4 {
+    fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        Ok(Self::raw_bytes().unwrap())
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let buf = self.ip().raw_encode(buf, purpose)?;
+        self.port().raw_encode(buf, purpose)
+    }
+}
+
+impl<'de> RawDecode<'de> for SocketAddrV4 {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (ip, buf) = Ipv4Addr::raw_decode(buf)?;
+        let (port, buf) = u16::raw_decode(buf)?;
+        Ok((SocketAddrV4::new(ip, port), buf))
+    }
+}
+
+// SocketAddrV6，地址 + 端口 + flowinfo + scope_id
+impl RawFixedBytes for SocketAddrV6 {
+    fn raw_bytes() -> Option<usize> {
+        Some(Ipv6Addr::raw_bytes().unwrap() + u16::raw_bytes().unwrap() + u32::raw_bytes().unwrap() * 2)
+    }
+}
+
+impl RawEncode for SocketAddrV6 {
+    fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        Ok(Self::raw_bytes().unwrap())
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let buf = self.ip().raw_encode(buf, purpose)?;
+        let buf = self.port().raw_encode(buf, purpose)?;
+        let buf = self.flowinfo().raw_encode(buf, purpose)?;
+        self.scope_id().raw_encode(buf, purpose)
+    }
+}
+
+impl<'de> RawDecode<'de> for SocketAddrV6 {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (ip, buf) = Ipv6Addr::raw_decode(buf)?;
+        let (port, buf) = u16::raw_decode(buf)?;
+        let (flowinfo, buf) = u32::raw_decode(buf)?;
+        let (scope_id, buf) = u32::raw_decode(buf)?;
+        Ok((SocketAddrV6::new(ip, port, flowinfo, scope_id), buf))
+    }
+}
+
+// SocketAddr，与IpAddr相同的1字节类型(0: v4, 1: v6) + SocketAddrV4/SocketAddrV6
+impl RawEncode for SocketAddr {
+    fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        Ok(match self {
+            SocketAddr::V4(_) => u8::raw_bytes().unwrap() + SocketAddrV4::raw_bytes().unwrap(),
+            SocketAddr::V6(_) => u8::raw_bytes().unwrap() + SocketAddrV6::raw_bytes().unwrap(),
+        })
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        match self {
+            SocketAddr::V4(addr) => {
+                let buf = (0u8).raw_encode(buf, purpose)?;
+                addr.raw_encode(buf, purpose)
+            }
+            SocketAddr::V6(addr) => {
+                let buf = (1u8).raw_encode(buf, purpose)?;
+                addr.raw_encode(buf, purpose)
+            }
+        }
+    }
+}
 
-                let mut vp_buf = [0u8; 16];
-                unsafe {std::ptr::copy(buf.as_ptr(), vp_buf.as_mut_ptr(), 16);}
-                // TOFIX: flow and scope
-                let addr = IpAddr::V6(Ipv6Addr::from(vp_buf));
-                Ok((addr, &buf[16..]))
+impl<'de> RawDecode<'de> for SocketAddr {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (ty, buf) = u8::raw_decode(buf)?;
+        match ty {
+            0 => {
+                let (addr, buf) = SocketAddrV4::raw_decode(buf)?;
+                Ok((SocketAddr::V4(addr), buf))
+            }
+            1 => {
+                let (addr, buf) = SocketAddrV6::raw_decode(buf)?;
+                Ok((SocketAddr::V6(addr), buf))
             }
             _ => Err(CodecError::new(CodecErrorCode::NotSupport, "NotSupport")),
         }
@@ -1989,6 +3613,19 @@ impl<T: RawEncode> RawEncode for Option<T> {
             0u8.raw_encode(buf, purpose)
         }
     }
+
+    fn raw_digest(
+        &self,
+        writer: &mut RawDigestWriter,
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<()> {
+        if let Some(t) = self {
+            1u8.raw_digest(writer, purpose)?;
+            t.raw_digest(writer, purpose)
+        } else {
+            0u8.raw_digest(writer, purpose)
+        }
+    }
 }
 
 pub struct OptionRef<'o, T>(Option<&'o T>);
@@ -2042,6 +3679,9 @@ impl<'o, T: RawEncode> RawEncode for OptionRef<'o, T> {
 impl<'de, T: RawDecode<'de>> RawDecode<'de> for Option<T> {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
         let (option, buf) = u8::raw_decode(buf)?;
+        if option > 1 && is_canonical_decode() {
+            return Err(non_canonical_error(format!("invalid option flag: {}", option)));
+        }
 
         let (t, buf) = if option == 1 {
             let (t, buf) = T::raw_decode(buf)?;
@@ -2163,72 +3803,217 @@ where
         Ok(bytes)
     }
 
-    fn raw_encode<'a>(
-        &self,
-        buf: &'a mut [u8],
-        purpose: &Option<RawEncodePurpose>,
-    ) -> CodecResult<&'a mut [u8]> {
-        let bytes = self.obj.raw_measure(purpose)?;
-        let buf = USize(bytes).raw_encode(buf, purpose)?;
-        let buf = self.obj.raw_encode(buf, purpose)?;
-        Ok(buf)
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let bytes = self.obj.raw_measure(purpose)?;
+        let buf = USize(bytes).raw_encode(buf, purpose)?;
+        let buf = self.obj.raw_encode(buf, purpose)?;
+        Ok(buf)
+    }
+}
+
+impl<'de, T> RawDecode<'de> for TypeBuffer<T>
+where
+    for<'e> T: RawEncode + RawDecode<'e>,
+{
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (_, buf) = USize::raw_decode(buf)?;
+        let (obj, buf) = T::raw_decode(buf)?;
+        Ok((Self { obj }, buf))
+    }
+}
+
+// primitive-types的定长hash，按字节原样编码
+macro_rules! raw_codec_fixed_hash {
+    ($ty:ident, $size:expr) => {
+        impl RawFixedBytes for $ty {
+            fn raw_bytes() -> Option<usize> {
+                Some($size)
+            }
+        }
+
+        impl RawEncode for $ty {
+            fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+                Ok($size)
+            }
+            fn raw_encode<'a>(
+                &self,
+                buf: &'a mut [u8],
+                purpose: &Option<RawEncodePurpose>,
+            ) -> CodecResult<&'a mut [u8]> {
+                let bytes = self.raw_measure(purpose)?;
+                if buf.len() < bytes {
+                    return Err(CodecError::new(
+                        CodecErrorCode::OutOfLimit,
+                        concat!("not enough buffer for ", stringify!($ty)),
+                    ));
+                }
+                buf[..bytes].copy_from_slice(self.as_bytes());
+                Ok(&mut buf[bytes..])
+            }
+        }
+
+        impl<'de> RawDecode<'de> for $ty {
+            fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+                let bytes = Self::raw_bytes().unwrap();
+                if buf.len() < bytes {
+                    return Err(CodecError::new(
+                        CodecErrorCode::OutOfLimit,
+                        concat!("not enough buffer for ", stringify!($ty)),
+                    ));
+                }
+                let obj = Self::from_slice(&buf[..bytes]);
+                Ok((obj, &buf[bytes..]))
+            }
+        }
+    };
+}
+
+raw_codec_fixed_hash!(H128, 16);
+raw_codec_fixed_hash!(H160, 20);
+raw_codec_fixed_hash!(H256, 32);
+raw_codec_fixed_hash!(H512, 64);
+
+// primitive-types的大整数，定长大端编码
+macro_rules! raw_codec_big_uint {
+    ($ty:ident, $size:expr) => {
+        impl RawFixedBytes for $ty {
+            fn raw_bytes() -> Option<usize> {
+                Some($size)
+            }
+        }
+
+        impl RawEncode for $ty {
+            fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+                Ok($size)
+            }
+            fn raw_encode<'a>(
+                &self,
+                buf: &'a mut [u8],
+                _purpose: &Option<RawEncodePurpose>,
+            ) -> CodecResult<&'a mut [u8]> {
+                if buf.len() < $size {
+                    return Err(CodecError::new(
+                        CodecErrorCode::OutOfLimit,
+                        concat!("not enough buffer for ", stringify!($ty)),
+                    ));
+                }
+                self.to_big_endian(&mut buf[..$size]);
+                Ok(&mut buf[$size..])
+            }
+        }
+
+        impl<'de> RawDecode<'de> for $ty {
+            fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+                if buf.len() < $size {
+                    return Err(CodecError::new(
+                        CodecErrorCode::OutOfLimit,
+                        concat!("not enough buffer for ", stringify!($ty)),
+                    ));
+                }
+                let v = $ty::from_big_endian(&buf[..$size]);
+                Ok((v, &buf[$size..]))
+            }
+        }
+    };
+}
+
+raw_codec_big_uint!(U128, 16);
+raw_codec_big_uint!(U256, 32);
+raw_codec_big_uint!(U512, 64);
+
+// U256的紧凑编码：1字节长度 + 去掉前导0的大端字节，适合大部分值都比较小的场景(比如余额)
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CompactU256(pub U256);
+
+impl CompactU256 {
+    pub fn value(&self) -> U256 {
+        self.0
+    }
+}
+
+impl Deref for CompactU256 {
+    type Target = U256;
+
+    fn deref(&self) -> &U256 {
+        &self.0
+    }
+}
+
+impl From<U256> for CompactU256 {
+    fn from(v: U256) -> Self {
+        Self(v)
     }
 }
 
-impl<'de, T> RawDecode<'de> for TypeBuffer<T>
-where
-    for<'e> T: RawEncode + RawDecode<'e>,
-{
-    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
-        let (_, buf) = USize::raw_decode(buf)?;
-        let (obj, buf) = T::raw_decode(buf)?;
-        Ok((Self { obj }, buf))
+impl From<CompactU256> for U256 {
+    fn from(v: CompactU256) -> Self {
+        v.0
     }
 }
 
-impl RawFixedBytes for H256 {
-    fn raw_bytes() -> Option<usize> {
-        Some(32)
+impl RawFixedBytes for CompactU256 {
+    fn raw_max_bytes() -> Option<usize> {
+        Some(1 + 32)
+    }
+    fn raw_min_bytes() -> Option<usize> {
+        Some(1)
     }
 }
 
-impl RawEncode for H256 {
+impl RawEncode for CompactU256 {
     fn raw_measure(&self, _purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
-        Ok(32)
+        Ok(1 + self.0.bits().div_ceil(8))
     }
+
     fn raw_encode<'a>(
         &self,
         buf: &'a mut [u8],
         purpose: &Option<RawEncodePurpose>,
     ) -> CodecResult<&'a mut [u8]> {
-        let bytes = self.raw_measure(purpose)?;
-        if buf.len() < bytes {
+        let size = self.raw_measure(purpose)?;
+        if buf.len() < size {
             return Err(CodecError::new(
                 CodecErrorCode::OutOfLimit,
-                "not enough buffer for H256",
+                "not enough buffer for CompactU256",
             ));
         }
-        unsafe {
-            std::ptr::copy::<u8>(self.as_ptr(), buf.as_mut_ptr(), bytes);
-        }
-        Ok(&mut buf[bytes..])
+
+        let len = size - 1;
+        let mut bytes = [0u8; 32];
+        self.0.to_big_endian(&mut bytes);
+        let buf = (len as u8).raw_encode(buf, purpose)?;
+        buf[..len].copy_from_slice(&bytes[32 - len..]);
+        Ok(&mut buf[len..])
     }
 }
 
-impl<'de> RawDecode<'de> for H256 {
+impl<'de> RawDecode<'de> for CompactU256 {
     fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
-        let bytes = Self::raw_bytes().unwrap();
-        if buf.len() < bytes {
+        let (len, buf) = u8::raw_decode(buf)?;
+        let len = len as usize;
+        if len > 32 {
+            let msg = format!("invalid CompactU256 length: {}", len);
+            error!("{}", msg);
+            return Err(CodecError::new(CodecErrorCode::InvalidFormat, msg));
+        }
+        if buf.len() < len {
             return Err(CodecError::new(
                 CodecErrorCode::OutOfLimit,
-                "not enough buffer for H256",
+                "not enough buffer for CompactU256",
             ));
         }
-        let mut obj = Self::default();
-        unsafe {
-            std::ptr::copy::<u8>(buf.as_ptr(), obj.as_mut_ptr(), bytes);
+        if len > 0 && buf[0] == 0 && is_canonical_decode() {
+            return Err(non_canonical_error(
+                "CompactU256 with leading zero byte".to_owned(),
+            ));
         }
-        Ok((obj, &buf[bytes..]))
+
+        let v = U256::from_big_endian(&buf[..len]);
+        Ok((Self(v), &buf[len..]))
     }
 }
 
@@ -2266,6 +4051,170 @@ impl<'de, T: RawEncode + RawDecode<'de>> RawDecode<'de> for Range<T> {
     }
 }
 
+// RangeInclusive<T>，start + end
+impl<T: RawFixedBytes> RawFixedBytes for RangeInclusive<T> {
+    fn raw_bytes() -> Option<usize> {
+        T::raw_bytes().map(|s| s + s)
+    }
+    fn raw_max_bytes() -> Option<usize> {
+        T::raw_max_bytes().map(|s| s + s)
+    }
+    fn raw_min_bytes() -> Option<usize> {
+        T::raw_min_bytes().map(|s| s + s)
+    }
+}
+
+impl<T: RawEncode> RawEncode for RangeInclusive<T> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        Ok(self.start().raw_measure(purpose)? + self.end().raw_measure(purpose)?)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        let buf = self.start().raw_encode(buf, purpose)?;
+        self.end().raw_encode(buf, purpose)
+    }
+}
+
+impl<'de, T: RawDecode<'de>> RawDecode<'de> for RangeInclusive<T> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (start, buf) = T::raw_decode(buf)?;
+        let (end, buf) = T::raw_decode(buf)?;
+        Ok((RangeInclusive::new(start, end), buf))
+    }
+}
+
+// RangeFrom<T>，只编码start
+impl<T: RawFixedBytes> RawFixedBytes for RangeFrom<T> {
+    fn raw_bytes() -> Option<usize> {
+        T::raw_bytes()
+    }
+    fn raw_max_bytes() -> Option<usize> {
+        T::raw_max_bytes()
+    }
+    fn raw_min_bytes() -> Option<usize> {
+        T::raw_min_bytes()
+    }
+}
+
+impl<T: RawEncode> RawEncode for RangeFrom<T> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        self.start.raw_measure(purpose)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        self.start.raw_encode(buf, purpose)
+    }
+}
+
+impl<'de, T: RawDecode<'de>> RawDecode<'de> for RangeFrom<T> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (start, buf) = T::raw_decode(buf)?;
+        Ok((RangeFrom { start }, buf))
+    }
+}
+
+// RangeTo<T>，只编码end
+impl<T: RawFixedBytes> RawFixedBytes for RangeTo<T> {
+    fn raw_bytes() -> Option<usize> {
+        T::raw_bytes()
+    }
+    fn raw_max_bytes() -> Option<usize> {
+        T::raw_max_bytes()
+    }
+    fn raw_min_bytes() -> Option<usize> {
+        T::raw_min_bytes()
+    }
+}
+
+impl<T: RawEncode> RawEncode for RangeTo<T> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        self.end.raw_measure(purpose)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        self.end.raw_encode(buf, purpose)
+    }
+}
+
+impl<'de, T: RawDecode<'de>> RawDecode<'de> for RangeTo<T> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (end, buf) = T::raw_decode(buf)?;
+        Ok((RangeTo { end }, buf))
+    }
+}
+
+// Bound<T>，1字节类型(0: Included, 1: Excluded, 2: Unbounded) + 值
+impl<T: RawFixedBytes> RawFixedBytes for Bound<T> {
+    fn raw_max_bytes() -> Option<usize> {
+        T::raw_max_bytes().map(|s| u8::raw_bytes().unwrap() + s)
+    }
+    fn raw_min_bytes() -> Option<usize> {
+        u8::raw_bytes()
+    }
+}
+
+impl<T: RawEncode> RawEncode for Bound<T> {
+    fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+        let bytes = match self {
+            Bound::Included(v) | Bound::Excluded(v) => v.raw_measure(purpose)?,
+            Bound::Unbounded => 0,
+        };
+        Ok(u8::raw_bytes().unwrap() + bytes)
+    }
+
+    fn raw_encode<'a>(
+        &self,
+        buf: &'a mut [u8],
+        purpose: &Option<RawEncodePurpose>,
+    ) -> CodecResult<&'a mut [u8]> {
+        match self {
+            Bound::Included(v) => {
+                let buf = 0u8.raw_encode(buf, purpose)?;
+                v.raw_encode(buf, purpose)
+            }
+            Bound::Excluded(v) => {
+                let buf = 1u8.raw_encode(buf, purpose)?;
+                v.raw_encode(buf, purpose)
+            }
+            Bound::Unbounded => 2u8.raw_encode(buf, purpose),
+        }
+    }
+}
+
+impl<'de, T: RawDecode<'de>> RawDecode<'de> for Bound<T> {
+    fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+        let (ty, buf) = u8::raw_decode(buf)?;
+        match ty {
+            0 => {
+                let (v, buf) = T::raw_decode(buf)?;
+                Ok((Bound::Included(v), buf))
+            }
+            1 => {
+                let (v, buf) = T::raw_decode(buf)?;
+                Ok((Bound::Excluded(v), buf))
+            }
+            2 => Ok((Bound::Unbounded, buf)),
+            _ => {
+                let msg = format!("invalid Bound type: {}", ty);
+                error!("{}", msg);
+                Err(CodecError::new(CodecErrorCode::InvalidFormat, msg))
+            }
+        }
+    }
+}
+
 impl RawFixedBytes for str {
     fn raw_min_bytes() -> Option<usize> {
         u16::raw_bytes()
@@ -2289,6 +4238,17 @@ impl RawEncode for str {
             ));
         }
 
+        // 与String保持一致的最大长度限制
+        if self.len() > u16::MAX.into() {
+            let msg = format!(
+                "str extend length max limit: bytes={}, limit={}",
+                self.len(),
+                u16::MAX
+            );
+            error!("{}", msg);
+            return Err(CodecError::new(CodecErrorCode::OutOfLimit, msg));
+        }
+
         let buf = (self.len() as u16).raw_encode(buf, purpose)?;
         if self.len() == 0 {
             Ok(buf)
@@ -2296,7 +4256,6 @@ impl RawEncode for str {
             unsafe {
                 std::ptr::copy::<u8>(self.as_ptr() as *mut u8, buf.as_mut_ptr(), self.len());
             }
-            println!("buf len {}, self len {}", buf.len(), self.len());
             Ok(&mut buf[self.len()..])
         }
     }
@@ -2304,7 +4263,7 @@ impl RawEncode for str {
 
 #[cfg(test)]
 mod raw_codec_test {
-    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
     use crate::*;
 
     fn test_var_string_codec(v: &str) {
@@ -2352,6 +4311,11 @@ mod raw_codec_test {
         // 超出了65536大小，应该会编码失败
         {
             s.to_vec().unwrap_err();
+
+            let v = s.as_str();
+            let mut buf = vec![0u8; v.raw_measure(&None).unwrap()];
+            let err = v.raw_encode(&mut buf, &None).unwrap_err();
+            assert_eq!(err.code(), CodecErrorCode::OutOfLimit);
         }
     }
 
@@ -2490,6 +4454,129 @@ mod raw_codec_test {
         test_codec(&set);
     }
 
+    #[test]
+    fn test_hash_map_with_hasher() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::{HashMap, HashSet};
+        use std::hash::BuildHasherDefault;
+
+        type Hasher = BuildHasherDefault<DefaultHasher>;
+
+        let mut map: HashMap<u32, String, Hasher> = HashMap::default();
+        map.insert(2, "2".to_owned());
+        map.insert(1, "1".to_owned());
+        test_codec(&map);
+
+        // 与默认hasher的编码一致
+        let std_map: HashMap<u32, String> = map.clone().into_iter().collect();
+        assert_eq!(map.to_vec().unwrap(), std_map.to_vec().unwrap());
+
+        let set: HashSet<u8, Hasher> = [3u8, 1, 2].into_iter().collect();
+        test_codec(&set);
+    }
+
+    // 只实现了Hash和Eq，没有实现Ord
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    struct NoOrdKey(i8);
+
+    impl RawEncode for NoOrdKey {
+        fn raw_measure(&self, purpose: &Option<RawEncodePurpose>) -> CodecResult<usize> {
+            self.0.raw_measure(purpose)
+        }
+
+        fn raw_encode<'a>(
+            &self,
+            buf: &'a mut [u8],
+            purpose: &Option<RawEncodePurpose>,
+        ) -> CodecResult<&'a mut [u8]> {
+            self.0.raw_encode(buf, purpose)
+        }
+    }
+
+    impl<'de> RawDecode<'de> for NoOrdKey {
+        fn raw_decode(buf: &'de [u8]) -> CodecResult<(Self, &'de [u8])> {
+            let (v, buf) = i8::raw_decode(buf)?;
+            Ok((NoOrdKey(v), buf))
+        }
+    }
+
+    #[test]
+    fn test_ordered_hash_map() {
+        let mut map = RawOrderedHashMap::<NoOrdKey, u8>::default();
+        map.insert(NoOrdKey(-1), 3);
+        map.insert(NoOrdKey(1), 1);
+        map.insert(NoOrdKey(0), 0);
+        test_codec(&map);
+
+        // 按编码后的字节序排序，-1编码为0xff，排在最后
+        let buf = map.to_vec().unwrap();
+        assert_eq!(buf, vec![3u8, 0x00, 0, 0x01, 1, 0xff, 3]);
+        assert_eq!(map.raw_hash_encode().unwrap(), buf);
+        check_hash_stream(&map);
+        check_hash_stream(&RawOrderedHashSet::<NoOrdKey>::default());
+
+        let mut set = RawOrderedHashSet::<NoOrdKey>::default();
+        set.insert(NoOrdKey(-1));
+        set.insert(NoOrdKey(5));
+        test_codec(&set);
+        let buf = set.to_vec().unwrap();
+        assert_eq!(buf, vec![2u8, 0x05, 0xff]);
+
+        // 规范模式拒绝乱序和重复的key
+        RawOrderedHashSet::<NoOrdKey>::decode_canonical(&buf).unwrap();
+        RawOrderedHashSet::<NoOrdKey>::decode_canonical(&[2u8, 0xff, 0x05]).unwrap_err();
+        RawOrderedHashMap::<NoOrdKey, u8>::decode_canonical(&[2u8, 0x01, 0, 0x01, 0]).unwrap_err();
+        RawOrderedHashMap::<NoOrdKey, u8>::clone_from_slice(&[2u8, 0x01, 0, 0x00, 0]).unwrap();
+
+        // 没有实现Ord的key解码后可以转换为HashMap
+        let map: std::collections::HashMap<NoOrdKey, u8> =
+            RawOrderedHashMap::<NoOrdKey, u8>::clone_from_slice(&[2u8, 0x01, 0, 0x00, 0]).unwrap().into();
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn test_primitive() {
+        test_codec(&-1i8);
+        test_codec(&i16::MIN);
+        test_codec(&i128::MAX);
+        test_codec(&1.5f32);
+        test_codec(&-0.25f64);
+        test_codec(&f64::INFINITY);
+        test_codec(&'中');
+        test_codec(&(u32::MAX as usize));
+        test_codec(&());
+
+        assert_eq!(i16::raw_bytes(), Some(2));
+        assert_eq!(usize::raw_bytes(), Some(8));
+        assert_eq!((-2i16).to_vec().unwrap(), vec![0xff, 0xfe]);
+        assert_eq!(1usize.to_vec().unwrap(), 1u64.to_vec().unwrap());
+        assert_eq!(().to_vec().unwrap().len(), 0);
+
+        // NaN只在计算hash时统一
+        let nan = f64::from_bits(f64::NAN.to_bits() | 1);
+        assert_ne!(nan.to_vec().unwrap(), f64::NAN.to_vec().unwrap());
+        assert_eq!(nan.raw_hash_encode().unwrap(), f64::NAN.raw_hash_encode().unwrap());
+        assert!(f64::clone_from_slice(&nan.to_vec().unwrap()).unwrap().is_nan());
+
+        assert_ne!((-0.0f64).to_vec().unwrap(), 0.0f64.to_vec().unwrap());
+        assert_eq!((-0.0f64).raw_hash_encode().unwrap(), 0.0f64.raw_hash_encode().unwrap());
+        assert_eq!((-0.0f32).raw_hash_encode().unwrap(), 0.0f32.raw_hash_encode().unwrap());
+        test_codec(&-0.0f64);
+
+        // 非法的char
+        let buf = 0xd800u32.to_vec().unwrap();
+        let err = char::clone_from_slice(&buf).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+        let buf = 0x110000u32.to_vec().unwrap();
+        char::clone_from_slice(&buf).unwrap_err();
+
+        #[cfg(target_pointer_width = "32")]
+        {
+            let buf = (u32::MAX as u64 + 1).to_vec().unwrap();
+            usize::clone_from_slice(&buf).unwrap_err();
+        }
+    }
+
     #[test]
     fn test_ip_addr() {
         let ip = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
@@ -2497,5 +4584,413 @@ mod raw_codec_test {
 
         let ip = IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1));
         test_codec(&ip);
+
+        // 布局保持不变：1字节类型 + 地址
+        let buf = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)).to_vec().unwrap();
+        assert_eq!(buf, vec![0u8, 192, 168, 1, 1]);
+
+        test_codec(&Ipv4Addr::new(10, 0, 0, 1));
+        test_codec(&Ipv6Addr::LOCALHOST);
+        assert_eq!(Ipv4Addr::LOCALHOST.to_vec().unwrap(), vec![127u8, 0, 0, 1]);
+    }
+
+    #[test]
+    fn test_array() {
+        let v = [1u8, 2, 3, 4];
+        test_codec(&v);
+        assert_eq!(v.to_vec().unwrap(), vec![1u8, 2, 3, 4]);
+        assert_eq!(<[u8; 32]>::raw_bytes(), Some(32));
+        assert_eq!(<[u32; 4]>::raw_bytes(), Some(16));
+        assert_eq!(<[String; 2]>::raw_bytes(), None);
+
+        let v = [0x1234u16, 0x5678];
+        test_codec(&v);
+        assert_eq!(v.to_vec().unwrap(), vec![0x12u8, 0x34, 0x56, 0x78]);
+
+        test_codec(&[0xffu8; 64]);
+        test_codec(&["a".to_owned(), "bc".to_owned(), "".to_owned()]);
+        test_codec(&[[1u8; 3]; 2]);
+        test_codec(&Vec::<[u8; 0]>::new());
+
+        <[u8; 4]>::clone_from_slice(&[1u8, 2, 3]).unwrap_err();
+        <[u16; 2]>::clone_from_slice(&[1u8, 2, 3]).unwrap_err();
+    }
+
+    #[test]
+    fn test_size_prefixed() {
+        let v = SizePrefixed(0x1234u32);
+        test_codec(&v);
+        let buf = v.to_vec().unwrap();
+        assert_eq!(buf[0], 4);
+        assert_eq!(&buf[1..], 0x1234u32.to_vec().unwrap().as_slice());
+
+        // 长度前缀之后的数据不属于内部的值
+        let mut buf2 = buf.clone();
+        buf2.push(0xff);
+        let (v2, remain) = SizePrefixed::<u32>::raw_decode(&buf2).unwrap();
+        assert_eq!(v, v2);
+        assert_eq!(remain, [0xff]);
+
+        // 声明的长度内有多余的数据
+        let buf3 = [5u8, 0x34, 0x12, 0, 0, 0xff];
+        let err = SizePrefixed::<u32>::raw_decode(&buf3).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+
+        let err = SizePrefixed::<u32>::raw_decode(&buf[..4]).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::OutOfLimit);
+    }
+
+    #[test]
+    fn test_wrapper() {
+        use std::borrow::Cow;
+        use std::cell::{Cell, RefCell};
+        use std::cmp::Reverse;
+        use std::marker::PhantomData;
+        use std::num::{NonZeroI16, NonZeroU32, NonZeroU8, Wrapping};
+        use std::rc::Rc;
+        use std::sync::{Arc, Mutex, RwLock};
+
+        // 透明编码，与内部对象一致
+        let expect = 0x1234u32.to_vec().unwrap();
+        test_codec(&Box::new(0x1234u32));
+        assert_eq!(Box::new(0x1234u32).to_vec().unwrap(), expect);
+        assert_eq!(Rc::new(0x1234u32).to_vec().unwrap(), expect);
+        assert_eq!(RefCell::new(0x1234u32).to_vec().unwrap(), expect);
+        assert_eq!(Cell::new(0x1234u32).to_vec().unwrap(), expect);
+        assert_eq!(RwLock::new(0x1234u32).to_vec().unwrap(), expect);
+        assert_eq!(Reverse(0x1234u32).to_vec().unwrap(), expect);
+        assert_eq!(Wrapping(0x1234u32).to_vec().unwrap(), expect);
+        assert_eq!(NonZeroU32::new(0x1234).unwrap().to_vec().unwrap(), expect);
+        test_codec(&Rc::new("rc".to_owned()));
+        test_codec(&Reverse(5u8));
+        test_codec(&Wrapping(-5i16));
+        test_codec(&PhantomData::<String>);
+        assert_eq!(PhantomData::<u64>.to_vec().unwrap().len(), 0);
+        assert_eq!(Cell::new(1u16).to_vec().unwrap(), 1u16.to_vec().unwrap());
+        assert_eq!(RefCell::<u32>::clone_from_slice(&expect).unwrap().into_inner(), 0x1234u32);
+
+        let list: Box<[u16]> = vec![1u16, 2, 3].into_boxed_slice();
+        test_codec(&list);
+        assert_eq!(list.to_vec().unwrap(), vec![1u16, 2, 3].to_vec().unwrap());
+        let s: Box<str> = "boxed".into();
+        test_codec(&s);
+        assert_eq!(s.to_vec().unwrap(), "boxed".to_owned().to_vec().unwrap());
+
+        let cow: Cow<str> = Cow::Borrowed("cow");
+        let buf = cow.to_vec().unwrap();
+        assert_eq!(buf, "cow".to_owned().to_vec().unwrap());
+        let cow2 = Cow::<str>::clone_from_slice(&buf).unwrap();
+        assert_eq!(cow2, cow);
+        test_codec(&Cow::<[u8]>::Owned(vec![1, 2, 3]));
+
+        // NonZero拒绝0
+        test_codec(&NonZeroU8::new(1).unwrap());
+        test_codec(&NonZeroI16::new(-1).unwrap());
+        let err = NonZeroU32::clone_from_slice(&0u32.to_vec().unwrap()).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+
+        // RefCell已被可变借用
+        let cell = RefCell::new(1u8);
+        {
+            let _v = cell.borrow_mut();
+            cell.to_vec().unwrap_err();
+        }
+        cell.to_vec().unwrap();
+
+        // 锁被污染时返回错误
+        let mutex = Arc::new(Mutex::new(1u8));
+        let lock = Arc::new(RwLock::new(1u8));
+        {
+            let mutex = mutex.clone();
+            let lock = lock.clone();
+            let _ = std::thread::spawn(move || {
+                let _m = mutex.lock().unwrap();
+                let _l = lock.write().unwrap();
+                panic!("poison");
+            })
+            .join();
+        }
+        let err = mutex.to_vec().unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::ErrorState);
+        let err = lock.to_vec().unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::ErrorState);
+    }
+
+    #[test]
+    fn test_tuple() {
+        test_codec(&());
+        test_codec(&(1u8,));
+        test_codec(&(1u8, "2".to_owned()));
+        test_codec(&(1u8, 2u16, 3u32, 4u64, 5i8, 6i16, 7i32, 8i64, 9u128, 10i128, true, "12".to_owned()));
+
+        assert_eq!(<()>::raw_bytes(), Some(0));
+        assert_eq!(<(u8, u16, u32)>::raw_bytes(), Some(7));
+        assert_eq!(<(u8, String)>::raw_bytes(), None);
+        assert_eq!(<(u8, String)>::raw_min_bytes(), Some(3));
+
+        let buf = (1u8, 0x0203u16).to_vec().unwrap();
+        assert_eq!(buf, vec![1u8, 2, 3]);
+        assert_eq!((5u32,).to_vec().unwrap(), 5u32.to_vec().unwrap());
+    }
+
+    #[test]
+    fn test_range() {
+        use std::ops::Bound;
+
+        test_codec(&(1u32..10));
+        test_codec(&(1u32..=10));
+        test_codec(&(1u32..));
+        test_codec(&(..10u32));
+        test_codec(&Bound::Included(1u16));
+        test_codec(&Bound::Excluded("a".to_owned()));
+        test_codec(&Bound::<u8>::Unbounded);
+
+        assert_eq!((1u8..=2).to_vec().unwrap(), vec![1u8, 2]);
+        assert_eq!(<std::ops::RangeInclusive<u32>>::raw_bytes(), Some(8));
+        assert_eq!(<std::ops::RangeFrom<u32>>::raw_bytes(), Some(4));
+        assert_eq!(Bound::<u8>::Unbounded.to_vec().unwrap(), vec![2u8]);
+        assert_eq!(<Bound<u32>>::raw_max_bytes(), Some(5));
+        assert_eq!(<Bound<u32>>::raw_min_bytes(), Some(1));
+
+        let err = Bound::<u8>::clone_from_slice(&[3u8]).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+    }
+
+    #[test]
+    fn test_collections() {
+        use std::collections::{BinaryHeap, LinkedList, VecDeque};
+
+        let list = vec![3u16, 1, 2];
+        let expect = list.to_vec().unwrap();
+
+        let deque: VecDeque<u16> = list.iter().cloned().collect();
+        test_codec(&deque);
+        assert_eq!(deque.to_vec().unwrap(), expect);
+
+        let linked: LinkedList<u16> = list.iter().cloned().collect();
+        test_codec(&linked);
+        assert_eq!(linked.to_vec().unwrap(), expect);
+
+        // BinaryHeap按升序编码
+        let heap: BinaryHeap<u16> = list.iter().cloned().collect();
+        let buf = heap.to_vec().unwrap();
+        assert_eq!(buf, vec![1u16, 2, 3].to_vec().unwrap());
+        let heap2 = BinaryHeap::<u16>::clone_from_slice(&buf).unwrap();
+        assert_eq!(heap2.into_sorted_vec(), vec![1u16, 2, 3]);
+        check_hash_stream(&heap);
+
+        // 超出长度上限
+        let buf = USize(MAX_CONTAINER_LEN + 1).to_vec().unwrap();
+        VecDeque::<u8>::clone_from_slice(&buf).unwrap_err();
+        LinkedList::<u8>::clone_from_slice(&buf).unwrap_err();
+        BinaryHeap::<u8>::clone_from_slice(&buf).unwrap_err();
+    }
+
+    fn check_hash_stream<T: RawEncode>(v: &T) {
+        use sha2::{Digest, Sha256};
+        let buf = v.raw_hash_encode().unwrap();
+        assert_eq!(
+            v.raw_hash_sha256().unwrap(),
+            H256::from_slice(&Sha256::digest(&buf))
+        );
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn test_index_map() {
+        use indexmap::{IndexMap, IndexSet};
+
+        let mut map = IndexMap::new();
+        map.insert("b".to_owned(), 2u32);
+        map.insert("a".to_owned(), 1u32);
+        let buf = map.to_vec().unwrap();
+        let map2 = IndexMap::<String, u32>::clone_from_slice(&buf).unwrap();
+        assert_eq!(map2.keys().collect::<Vec<_>>(), vec!["b", "a"]);
+        assert_eq!(map, map2);
+        check_hash_stream(&map);
+
+        let set: IndexSet<u8> = [3u8, 1, 2].into_iter().collect();
+        let buf = set.to_vec().unwrap();
+        assert_eq!(buf, vec![3u8, 1, 2].to_vec().unwrap());
+        assert_eq!(IndexSet::<u8>::clone_from_slice(&buf).unwrap(), set);
+
+        // 与HashMap/HashSet一致，重复的key只在规范模式下返回错误
+        let buf = vec![3u8, 3].to_vec().unwrap();
+        assert_eq!(IndexSet::<u8>::clone_from_slice(&buf).unwrap().len(), 1);
+        let err = IndexSet::<u8>::decode_canonical(&buf).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+        let buf = vec![(1u8, 2u8), (1u8, 3u8)].to_vec().unwrap();
+        let map = IndexMap::<u8, u8>::clone_from_slice(&buf).unwrap();
+        assert_eq!(map.get(&1), Some(&3));
+        let err = IndexMap::<u8, u8>::decode_canonical(&buf).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+        assert_eq!(IndexMap::<u8, u8>::decode_canonical(&map.to_vec().unwrap()).unwrap(), map);
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn test_small_vec() {
+        use smallvec::{smallvec, SmallVec};
+
+        let v: SmallVec<[u32; 4]> = smallvec![1, 2, 3];
+        let buf = v.to_vec().unwrap();
+        assert_eq!(buf, vec![1u32, 2, 3].to_vec().unwrap());
+        assert_eq!(SmallVec::<[u32; 4]>::clone_from_slice(&buf).unwrap(), v);
+
+        let v: SmallVec<[u32; 2]> = (0..10).collect();
+        test_codec(&v);
+    }
+
+    #[test]
+    fn test_primitive_types() {
+        use primitive_types::{H128, H160, H256, H512, U128, U256, U512};
+
+        test_codec(&H128::repeat_byte(1));
+        test_codec(&H160::from_low_u64_be(0x1234));
+        test_codec(&H256::random());
+        test_codec(&H512::repeat_byte(0xff));
+        assert_eq!(H160::raw_bytes(), Some(20));
+        assert_eq!(H160::from_low_u64_be(1).to_vec().unwrap(), H160::from_low_u64_be(1).as_bytes());
+
+        test_codec(&U128::from(u128::MAX));
+        test_codec(&U256::MAX);
+        test_codec(&U512::from(12345u64));
+        assert_eq!(U256::raw_bytes(), Some(32));
+
+        // 大端编码
+        let buf = U256::from(0x0102u64).to_vec().unwrap();
+        assert_eq!(buf.len(), 32);
+        assert_eq!(&buf[30..], &[1u8, 2]);
+        assert!(buf[..30].iter().all(|v| *v == 0));
+        H160::clone_from_slice(&[0u8; 19]).unwrap_err();
+        U512::clone_from_slice(&[0u8; 63]).unwrap_err();
+    }
+
+    #[test]
+    fn test_compact_u256() {
+        use primitive_types::U256;
+
+        test_codec(&CompactU256(U256::zero()));
+        test_codec(&CompactU256(U256::from(255u64)));
+        test_codec(&CompactU256(U256::MAX));
+
+        assert_eq!(CompactU256(U256::zero()).to_vec().unwrap(), vec![0u8]);
+        assert_eq!(CompactU256(U256::from(0x0100u64)).to_vec().unwrap(), vec![2u8, 1, 0]);
+        assert_eq!(CompactU256(U256::MAX).to_vec().unwrap().len(), 33);
+
+        CompactU256::clone_from_slice(&[33u8]).unwrap_err();
+        CompactU256::clone_from_slice(&[2u8, 1]).unwrap_err();
+
+        // 前导0只在规范模式下拒绝
+        let v = CompactU256::clone_from_slice(&[2u8, 0, 5]).unwrap();
+        assert_eq!(v.value(), U256::from(5u64));
+        CompactU256::decode_canonical(&[2u8, 0, 5]).unwrap_err();
+        CompactU256::decode_canonical(&[1u8, 5]).unwrap();
+    }
+
+    #[test]
+    fn test_socket_addr() {
+        let v4 = SocketAddrV4::new(Ipv4Addr::new(192, 168, 1, 1), 8080);
+        test_codec(&v4);
+        let buf = v4.to_vec().unwrap();
+        assert_eq!(buf, vec![192u8, 168, 1, 1, 0x1f, 0x90]);
+
+        let v6 = SocketAddrV6::new(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1), 443, 0x12345, 3);
+        test_codec(&v6);
+        let decoded = SocketAddrV6::clone_from_slice(&v6.to_vec().unwrap()).unwrap();
+        assert_eq!(decoded.flowinfo(), 0x12345);
+        assert_eq!(decoded.scope_id(), 3);
+
+        let addr = SocketAddr::V4(v4);
+        test_codec(&addr);
+        let buf = addr.to_vec().unwrap();
+        assert_eq!(buf[0], 0);
+        assert_eq!(&buf[1..], &v4.to_vec().unwrap()[..]);
+
+        let addr = SocketAddr::V6(v6);
+        test_codec(&addr);
+        assert_eq!(addr.to_vec().unwrap().len(), 1 + 16 + 2 + 4 + 4);
+
+        let list = vec![SocketAddr::V4(v4), SocketAddr::V6(v6)];
+        test_codec(&list);
+
+        let mut buf = addr.to_vec().unwrap();
+        buf[0] = 2;
+        SocketAddr::clone_from_slice(&buf).unwrap_err();
+        SocketAddr::clone_from_slice(&buf[..10]).unwrap_err();
+    }
+
+    #[test]
+    fn test_canonical_decode() {
+        use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+
+        // 非最短的变长size
+        let buf = [0b0100_0000u8, 10];
+        assert_eq!(USize::clone_from_slice(&buf).unwrap(), USize(10));
+        let err = USize::decode_canonical(&buf).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+        let buf = USize(10).to_vec().unwrap();
+        assert_eq!(USize::decode_canonical(&buf).unwrap(), USize(10));
+
+        // bool/option只接受0/1
+        assert!(bool::clone_from_slice(&[2u8]).unwrap());
+        assert!(bool::decode_canonical(&[2u8]).is_err());
+        assert!(bool::decode_canonical(&[1u8]).unwrap());
+        assert!(Option::<u8>::decode_canonical(&[2u8]).is_err());
+
+        // 乱序和重复的key
+        let mut map = HashMap::new();
+        map.insert(1u32, 10u8);
+        map.insert(2u32, 20u8);
+        let buf = map.to_vec().unwrap();
+        assert_eq!(HashMap::<u32, u8>::decode_canonical(&buf).unwrap(), map);
+
+        let mut unordered = USize(2).to_vec().unwrap();
+        unordered.append(&mut (2u32, 20u8).to_vec().unwrap());
+        unordered.append(&mut (1u32, 10u8).to_vec().unwrap());
+        assert_eq!(HashMap::<u32, u8>::clone_from_slice(&unordered).unwrap(), map);
+        assert!(HashMap::<u32, u8>::decode_canonical(&unordered).is_err());
+        assert!(BTreeMap::<u32, u8>::decode_canonical(&unordered).is_err());
+
+        // 嵌套在结构体中的容器同样检查
+        use crate as bucky_raw_codec;
+        use bucky_raw_codec_derive::{RawDecode, RawEncode};
+
+        #[derive(RawEncode, RawDecode, Debug, PartialEq)]
+        struct Holder {
+            version: u8,
+            map: HashMap<u32, u8>,
+        }
+
+        let mut holder = vec![1u8];
+        holder.extend_from_slice(&unordered);
+        assert!(Holder::clone_from_slice(&holder).is_ok());
+        assert!(Holder::decode_canonical(&holder).is_err());
+        let holder = Holder { version: 1, map: map.clone() }.to_vec().unwrap();
+        assert_eq!(Holder::decode_canonical(&holder).unwrap().map, map);
+
+        // key按Ord排序，而不是按编码后的字节序
+        let mut signed = HashMap::new();
+        signed.insert(-1i32, 1u8);
+        signed.insert(1i32, 2u8);
+        let buf = signed.to_vec().unwrap();
+        assert_eq!(HashMap::<i32, u8>::decode_canonical(&buf).unwrap(), signed);
+
+        let mut dup = USize(2).to_vec().unwrap();
+        dup.append(&mut 1u32.to_vec().unwrap());
+        dup.append(&mut 1u32.to_vec().unwrap());
+        assert!(HashSet::<u32>::decode_canonical(&dup).is_err());
+        assert!(BTreeSet::<u32>::decode_canonical(&dup).is_err());
+        assert_eq!(BinaryHeap::<u32>::decode_canonical(&dup).unwrap().len(), 2);
+
+        let mut unordered = USize(2).to_vec().unwrap();
+        unordered.append(&mut 2u32.to_vec().unwrap());
+        unordered.append(&mut 1u32.to_vec().unwrap());
+        assert!(BinaryHeap::<u32>::decode_canonical(&unordered).is_err());
+        assert!(HashSet::<u32>::decode_canonical(&unordered).is_err());
+
+        let opt = RawDecodeOption::default();
+        assert!(HashSet::<u32>::decode_canonical_with_option(&dup, &opt).is_err());
+        assert!(!is_canonical_decode());
     }
 }
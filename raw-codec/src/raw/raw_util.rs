@@ -55,6 +55,10 @@ where
 
 pub trait FileDecoder<'de>: Sized {
     fn decode_from_file(file: &Path, buf: &'de mut Vec<u8>) -> CodecResult<(Self, usize)>;
+
+    // 从映射的文件直接解码，解码出的对象可以直接引用文件内容
+    #[cfg(feature = "mmap")]
+    fn decode_from_mapped_file(file: &'de MappedFile) -> CodecResult<(Self, usize)>;
 }
 
 impl<'de, D> FileDecoder<'de> for D
@@ -76,6 +80,57 @@ where
             Err(e) => Err(CodecError::from(e)),
         }
     }
+
+    #[cfg(feature = "mmap")]
+    fn decode_from_mapped_file(file: &'de MappedFile) -> CodecResult<(Self, usize)> {
+        file.decode()
+    }
+}
+
+// 只读映射的文件，在解码出的对象的整个生命周期内保持映射有效
+#[cfg(feature = "mmap")]
+pub struct MappedFile {
+    mmap: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedFile {
+    /// 以只读方式映射文件
+    ///
+    /// # Safety
+    ///
+    /// 在返回的MappedFile(以及从中解码出的对象)的生命周期内，文件不能被本进程或者其它进程修改或者截断，
+    /// 否则读取映射的内存属于未定义行为
+    pub unsafe fn open(file: &Path) -> CodecResult<Self> {
+        let file = std::fs::File::open(file).map_err(CodecError::from)?;
+        let mmap = memmap2::Mmap::map(&file).map_err(CodecError::from)?;
+        Ok(Self { mmap })
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.mmap
+    }
+
+    pub fn len(&self) -> usize {
+        self.mmap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mmap.is_empty()
+    }
+
+    pub fn decode<'de, D: RawDecode<'de>>(&'de self) -> CodecResult<(D, usize)> {
+        let buf = self.as_slice();
+        let (obj, rest) = D::raw_decode(buf)?;
+        Ok((obj, buf.len() - rest.len()))
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl AsRef<[u8]> for MappedFile {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
 }
 
 pub trait RawConvertTo<O> {
@@ -86,6 +141,10 @@ pub trait RawConvertTo<O> {
 pub trait RawFrom<'de, O> {
     fn clone_from_slice(buf: &'de [u8]) -> CodecResult<O>;
     fn clone_from_hex(hex_str: &str, buf: &'de mut Vec<u8>) -> CodecResult<O>;
+
+    // 严格模式，存在未消费的尾部数据时返回InvalidFormat
+    fn clone_from_slice_strict(buf: &'de [u8]) -> CodecResult<O>;
+    fn clone_from_hex_strict(hex_str: &str, buf: &'de mut Vec<u8>) -> CodecResult<O>;
 }
 
 impl<T> RawConvertTo<T> for T
@@ -108,22 +167,104 @@ where
 {
     fn clone_from_slice(buf: &'de [u8]) -> CodecResult<O> {
         let (t, _buf) = O::raw_decode(buf)?;
-
-        // println!("buffer_len:{}", buf.len());
-        // assert_eq!(_buf.len(),0);
         Ok(t)
     }
 
     fn clone_from_hex(hex_str: &str, buf: &'de mut Vec<u8>) -> CodecResult<O> {
-        let buf_size = hex_str.len() / 2;
-        buf.resize(buf_size, 0);
-        hex::decode_to_slice(hex_str, buf).map_err(|e| {
-            let msg = format!("decode hex string error! {}", e);
-            CodecError::new(CodecErrorCode::Failed, msg)
-        })?;
-
+        decode_hex_to_buf(hex_str, buf)?;
         let (t, _buf) = O::raw_decode(buf)?;
 
         Ok(t)
     }
+
+    fn clone_from_slice_strict(buf: &'de [u8]) -> CodecResult<O> {
+        O::decode_exact(buf)
+    }
+
+    fn clone_from_hex_strict(hex_str: &str, buf: &'de mut Vec<u8>) -> CodecResult<O> {
+        decode_hex_to_buf(hex_str, buf)?;
+        O::decode_exact(buf)
+    }
+}
+
+fn decode_hex_to_buf(hex_str: &str, buf: &mut Vec<u8>) -> CodecResult<()> {
+    let buf_size = hex_str.len() / 2;
+    buf.resize(buf_size, 0);
+    hex::decode_to_slice(hex_str, buf).map_err(|e| {
+        let msg = format!("decode hex string error! {}", e);
+        CodecError::new(CodecErrorCode::Failed, msg)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::*;
+
+    #[test]
+    fn test_strict_decode() {
+        let mut buf = 100u32.to_vec().unwrap();
+        assert_eq!(u32::clone_from_slice_strict(&buf).unwrap(), 100);
+
+        buf.push(0);
+        assert_eq!(u32::clone_from_slice(&buf).unwrap(), 100);
+        let err = u32::clone_from_slice_strict(&buf).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+        let err = u32::decode_exact(&buf).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+
+        let mut hex_buf = Vec::new();
+        let err = u32::clone_from_hex_strict(&hex::encode(&buf), &mut hex_buf).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+
+        let mut opt = RawDecodeOption::default();
+        let err = u32::decode_exact_with_option(&buf, &opt).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+
+        // 通过opt开启严格模式，默认不检查剩余数据
+        let (v, remain) = u32::raw_decode_with_option(&buf, &opt).unwrap();
+        assert_eq!((v, remain.len()), (100, 1));
+        opt.strict = true;
+        let err = u32::raw_decode_with_option(&buf, &opt).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+        let (v, remain) = u32::raw_decode_with_option(&buf[..4], &opt).unwrap();
+        assert_eq!((v, remain.len()), (100, 0));
+
+        let prefixed = SizePrefixed(100u32).to_vec().unwrap();
+        assert!(SizePrefixed::<u32>::raw_decode_with_option(&prefixed, &opt).is_ok());
+        let mut prefixed2 = prefixed.clone();
+        prefixed2.push(0);
+        let err = SizePrefixed::<u32>::raw_decode_with_option(&prefixed2, &opt).unwrap_err();
+        assert_eq!(err.code(), CodecErrorCode::InvalidFormat);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_decode_from_mapped_file() {
+        let data = SizedOwnedData::<SizeU16>::from(vec![1u8, 2, 3, 4, 5]);
+        let tail = TailedOwnedData::from(vec![6u8, 7]);
+        let mut buf = 0x1234u32.to_vec().unwrap();
+        buf.append(&mut data.to_vec().unwrap());
+        buf.extend_from_slice(tail.as_ref());
+
+        let path = std::env::temp_dir().join(format!("raw_codec_mmap_{}", std::process::id()));
+        std::fs::write(&path, &buf).unwrap();
+
+        // 测试期间文件不会被修改
+        let file = unsafe { MappedFile::open(&path) }.unwrap();
+        assert_eq!(file.len(), buf.len());
+
+        let (v, size) = u32::decode_from_mapped_file(&file).unwrap();
+        assert_eq!(v, 0x1234);
+        assert_eq!(size, 4);
+
+        let rest = &file.as_slice()[size..];
+        let (sized, rest) = SizedSharedData::raw_decode(rest).unwrap();
+        assert_eq!(sized.as_ref(), &[1u8, 2, 3, 4, 5]);
+        let (tailed, rest) = TailedSharedData::raw_decode(rest).unwrap();
+        assert_eq!(tailed.as_ref(), &[6u8, 7]);
+        assert!(rest.is_empty());
+
+        drop(file);
+        std::fs::remove_file(&path).unwrap();
+    }
 }